@@ -61,17 +61,18 @@ async fn main() -> Result<()> {
         Err(e) => eprintln!("Write failed: {e}"),
     }
 
-    // Example: Read with short timeout
+    // Example: Read with short timeout; the buffer is sized from the
+    // report descriptor and the report ID is split off
     println!("\nReading with 100ms timeout...");
-    let mut buffer = vec![0u8; 64];
 
-    match device
-        .read_timeout(&mut buffer, Duration::from_millis(100))
-        .await
-    {
-        Ok(n) => {
-            println!("Read {n} bytes:");
-            println!("Data: {:02x?}", &buffer[..n]);
+    match device.read_report_timeout(Duration::from_millis(100)).await {
+        Ok(report) => {
+            println!(
+                "Read report {id}, {n} bytes:",
+                id = report.report_id,
+                n = report.data.len()
+            );
+            println!("Data: {data:02x?}", data = report.data);
         }
         Err(Error::Timeout) => {
             println!("Read timed out (expected for most devices without continuous input)");
@@ -89,12 +90,11 @@ async fn main() -> Result<()> {
 
     // Example: Feature reports (still synchronous as ioctl doesn't have async variant)
     println!("\nTrying to get feature report 0x01...");
-    let mut feature_buf = vec![0u8; 64];
 
-    match device.get_feature_report(0x01, &mut feature_buf) {
-        Ok(n) => {
-            println!("Got feature report, {n} bytes:");
-            println!("Data: {:02x?}", &feature_buf[..n]);
+    match device.get_feature(0x01) {
+        Ok(report) => {
+            println!("Got feature report, {n} bytes:", n = report.data.len());
+            println!("Data: {data:02x?}", data = report.data);
         }
         Err(e) => {
             println!("Get feature report failed: {e} (this is normal for many devices)");
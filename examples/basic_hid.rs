@@ -42,7 +42,9 @@ fn main() -> Result<()> {
 
     // Example: Read input report with timeout
     println!("\nReading from device (1 second timeout)...");
-    let mut buffer = vec![0u8; 64];
+    // Size the buffer from the report descriptor instead of guessing
+    let report_len = device.max_input_report_len().unwrap_or(64).max(1);
+    let mut buffer = vec![0u8; report_len];
 
     match device.read(&mut buffer) {
         Ok(n) => {
@@ -72,13 +74,30 @@ fn main() -> Result<()> {
         }
     }
 
+    // Example: Read a whole report, allocated from the descriptor
+    println!("\nReading one report with read_report()...");
+    match device.read_report() {
+        Ok(report) => {
+            println!(
+                "Report {id}: {data:02x?}",
+                id = report.report_id,
+                data = report.data
+            );
+        }
+        Err(Error::Timeout) => {
+            println!("Read timed out (no data available)");
+        }
+        Err(e) => {
+            eprintln!("Read failed: {e}");
+        }
+    }
+
     // Example: Get/Set feature report
     println!("\nTrying to get feature report 0x01...");
-    let mut feature_buf = vec![0u8; 64];
-    match device.get_feature_report(0x01, &mut feature_buf) {
-        Ok(n) => {
-            println!("Got feature report, {n} bytes:");
-            println!("Data: {:02x?}", &feature_buf[..n]);
+    match device.get_feature(0x01) {
+        Ok(report) => {
+            println!("Got feature report, {n} bytes:", n = report.data.len());
+            println!("Data: {data:02x?}", data = report.data);
         }
         Err(e) => {
             eprintln!("Get feature report failed: {e}");
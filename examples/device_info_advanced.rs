@@ -93,6 +93,35 @@ fn show_device_info(device_info: &hidraw_rs::DeviceInfo) -> Result<()> {
         Err(e) => println!("Failed to get unique ID: {e}"),
     }
 
+    // Get extended sysfs details
+    match device_info.details() {
+        Ok(details) => {
+            println!("\nSysfs Details:");
+            println!("  Path: {path}", path = details.sysfs_path.display());
+            if let Some(driver) = &details.driver {
+                println!("  Driver: {driver}");
+            }
+            if let Some(usb) = &details.usb {
+                println!("  USB Port: {port}", port = usb.port_path);
+                if let Some(speed) = &usb.speed {
+                    println!("  Speed: {speed} Mbit/s");
+                }
+                if let Some(control) = &usb.power_control {
+                    println!("  Power Control: {control}");
+                }
+                for hub in &usb.hub_chain {
+                    println!(
+                        "  Hub: {name} ({vid:04x}:{pid:04x})",
+                        name = hub.name,
+                        vid = hub.vendor_id,
+                        pid = hub.product_id
+                    );
+                }
+            }
+        }
+        Err(e) => println!("\nFailed to get sysfs details: {e}"),
+    }
+
     // Get report descriptor
     println!("\nReport Descriptor:");
     match device.get_report_descriptor() {
@@ -3,7 +3,7 @@
 #[cfg(feature = "async")]
 use hidraw_rs::async_io::AsyncHidDevice;
 #[cfg(feature = "async")]
-use hidraw_rs::coldcard::{COINKITE_VID, COLDCARD_PID};
+use hidraw_rs::coldcard::{COINKITE_VID, COLDCARD_PID, PACKET_SIZE};
 #[cfg(feature = "async")]
 use hidraw_rs::prelude::*;
 #[cfg(feature = "async")]
@@ -43,7 +43,8 @@ async fn main() -> Result<()> {
     println!("\nTest 1: Async write (ping command)...");
     let ping_cmd = b"ping";
     let test_data = b"Async test!";
-    let mut packet = vec![0u8; 64];
+    // Coldcard frames every message into fixed 64-byte packets
+    let mut packet = vec![0u8; PACKET_SIZE];
     packet[0] = (ping_cmd.len() + test_data.len()) as u8 | 0x80;
     packet[1..5].copy_from_slice(ping_cmd);
     packet[5..5 + test_data.len()].copy_from_slice(test_data);
@@ -55,7 +56,8 @@ async fn main() -> Result<()> {
 
     // Test 2: Async read with timeout
     println!("\nTest 2: Async read with timeout (500ms)...");
-    let mut response = vec![0u8; 64];
+    // Size reads from the report descriptor rather than guessing
+    let mut response = vec![0u8; device.max_input_report_len()?];
 
     match device
         .read_timeout(&mut response, Duration::from_millis(500))
@@ -64,7 +66,7 @@ async fn main() -> Result<()> {
         Ok(n) => {
             println!("Read {n} bytes asynchronously");
             let len = (response[0] & 0x3F) as usize;
-            if len > 0 && len < 64 {
+            if len > 0 && len < n {
                 println!(
                     "Response: {response:?}",
                     response = String::from_utf8_lossy(&response[1..=len])
@@ -78,7 +80,7 @@ async fn main() -> Result<()> {
     // Test 3: Async write with timeout
     println!("\nTest 3: Async write with timeout (version command)...");
     let version_cmd = b"vers";
-    let mut packet = vec![0u8; 64];
+    let mut packet = vec![0u8; PACKET_SIZE];
     packet[0] = version_cmd.len() as u8 | 0x80;
     packet[1..5].copy_from_slice(version_cmd);
 
@@ -99,7 +101,7 @@ async fn main() -> Result<()> {
         Ok(n) => {
             println!("Read {n} bytes");
             let len = (response[0] & 0x3F) as usize;
-            if len > 0 && len < 64 {
+            if len > 0 && len < n {
                 println!(
                     "Version: {version}",
                     version = String::from_utf8_lossy(&response[1..=len])
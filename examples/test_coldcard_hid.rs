@@ -1,6 +1,6 @@
 //! Test HID operations specifically with Coldcard
 
-use hidraw_rs::coldcard::{COINKITE_VID, COLDCARD_PID};
+use hidraw_rs::coldcard::{COINKITE_VID, COLDCARD_PID, PACKET_SIZE};
 use hidraw_rs::prelude::*;
 use std::time::Duration;
 
@@ -31,7 +31,8 @@ fn main() -> Result<()> {
     println!("\nTest 1: Sending ping command...");
     let ping_cmd = b"ping";
     let test_data = b"Test from HID!";
-    let mut packet = vec![0u8; 64];
+    // Coldcard frames every message into fixed 64-byte packets
+    let mut packet = vec![0u8; PACKET_SIZE];
     packet[0] = (ping_cmd.len() + test_data.len()) as u8 | 0x80; // Length + last packet flag
     packet[1..5].copy_from_slice(ping_cmd);
     packet[5..5 + test_data.len()].copy_from_slice(test_data);
@@ -43,14 +44,15 @@ fn main() -> Result<()> {
 
     // Test 2: Read response with timeout
     println!("\nTest 2: Reading response (500ms timeout)...");
-    let mut response = vec![0u8; 64];
+    // Size reads from the report descriptor rather than guessing
+    let mut response = vec![0u8; device.max_input_report_len()?];
 
     match device.read_timeout(&mut response, Duration::from_millis(500)) {
         Ok(n) => {
             println!("Read {n} bytes");
             let len = (response[0] & 0x3F) as usize;
             println!("Response length field: {len}");
-            if len > 0 && len < 64 {
+            if len > 0 && len < n {
                 println!(
                     "Response data: {data:?}",
                     data = String::from_utf8_lossy(&response[1..=len])
@@ -64,7 +66,7 @@ fn main() -> Result<()> {
     // Test 3: Write with timeout
     println!("\nTest 3: Write with timeout (100ms)...");
     let version_cmd = b"vers";
-    let mut packet = vec![0u8; 64];
+    let mut packet = vec![0u8; PACKET_SIZE];
     packet[0] = version_cmd.len() as u8 | 0x80;
     packet[1..5].copy_from_slice(version_cmd);
 
@@ -79,7 +81,7 @@ fn main() -> Result<()> {
         Ok(n) => {
             println!("Read {n} bytes");
             let len = (response[0] & 0x3F) as usize;
-            if len > 0 && len < 64 {
+            if len > 0 && len < n {
                 println!(
                     "Version: {version}",
                     version = String::from_utf8_lossy(&response[1..=len])
@@ -91,11 +93,13 @@ fn main() -> Result<()> {
 
     // Test 4: Feature reports (may not be supported by Coldcard)
     println!("\nTest 4: Testing feature reports...");
-    let mut feature_buf = vec![0u8; 64];
-    match device.get_feature_report(0x00, &mut feature_buf) {
-        Ok(n) => {
-            println!("Got feature report, {n} bytes");
-            println!("Data: {:02x?}", &feature_buf[..n.min(16)]);
+    match device.get_feature(0x00) {
+        Ok(report) => {
+            println!("Got feature report, {n} bytes", n = report.data.len());
+            println!(
+                "Data: {data:02x?}",
+                data = &report.data[..report.data.len().min(16)]
+            );
         }
         Err(e) => {
             println!("Get feature report error: {e} (this is normal for Coldcard)");
@@ -0,0 +1,5 @@
+fn main() {
+    // Same soname as the C library, so binaries linked against this build
+    // load either one
+    println!("cargo:rustc-cdylib-link-arg=-Wl,-soname,libhidapi-hidraw.so.0");
+}
@@ -0,0 +1,80 @@
+//! `struct hid_device_info` lists
+
+use crate::wide;
+use hidapi_compat::DeviceInfo;
+use libc::{c_char, c_int, c_ushort, wchar_t};
+use std::ffi::CString;
+
+/// `struct hid_device_info` from `hidapi.h`
+///
+/// Missing strings are empty rather than null, matching hidapi's hidraw
+/// backend.
+#[repr(C)]
+pub struct HidDeviceInfo {
+    pub path: *mut c_char,
+    pub vendor_id: c_ushort,
+    pub product_id: c_ushort,
+    pub serial_number: *mut wchar_t,
+    pub release_number: c_ushort,
+    pub manufacturer_string: *mut wchar_t,
+    pub product_string: *mut wchar_t,
+    pub usage_page: c_ushort,
+    pub usage: c_ushort,
+    pub interface_number: c_int,
+    pub next: *mut HidDeviceInfo,
+    /// `hid_bus_type`
+    pub bus_type: c_int,
+}
+
+impl HidDeviceInfo {
+    /// Allocate a single unlinked node, released with [`free_list`]
+    pub(crate) fn into_raw(info: &DeviceInfo) -> *mut Self {
+        Box::into_raw(Box::new(Self {
+            path: CString::from(info.path()).into_raw(),
+            vendor_id: info.vendor_id(),
+            product_id: info.product_id(),
+            serial_number: wide::into_raw(info.serial_number().unwrap_or_default()),
+            release_number: info.release_number(),
+            manufacturer_string: wide::into_raw(info.manufacturer_string().unwrap_or_default()),
+            product_string: wide::into_raw(info.product_string().unwrap_or_default()),
+            usage_page: info.usage_page(),
+            usage: info.usage(),
+            interface_number: info.interface_number(),
+            next: std::ptr::null_mut(),
+            bus_type: info.bus_type() as c_int,
+        }))
+    }
+}
+
+/// Build a linked list in enumeration order, null when `devices` is empty
+pub(crate) fn into_list(devices: &[DeviceInfo]) -> *mut HidDeviceInfo {
+    devices
+        .iter()
+        .rev()
+        .fold(std::ptr::null_mut(), |next, info| {
+            let node = HidDeviceInfo::into_raw(info);
+            // SAFETY: node was just allocated by into_raw
+            unsafe { (*node).next = next };
+            node
+        })
+}
+
+/// Release a list from [`into_list`] or a node from
+/// [`HidDeviceInfo::into_raw`]
+///
+/// # Safety
+///
+/// `head` must be null or a list built here that has not been freed.
+pub(crate) unsafe fn free_list(mut head: *mut HidDeviceInfo) {
+    while !head.is_null() {
+        // SAFETY: every node and string was allocated by into_raw
+        unsafe {
+            let node = Box::from_raw(head);
+            head = node.next;
+            drop(CString::from_raw(node.path));
+            wide::free(node.serial_number);
+            wide::free(node.manufacturer_string);
+            wide::free(node.product_string);
+        }
+    }
+}
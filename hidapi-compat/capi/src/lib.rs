@@ -0,0 +1,584 @@
+//! hidapi C ABI backed by hidraw-rs
+//!
+//! Builds `libhidapi_hidraw.so` and `libhidapi_hidraw.a` exporting the
+//! functions declared in hidapi 0.14's `hidapi.h`, so C programs and
+//! Python bindings (cython-hidapi, ctypes) written against
+//! `libhidapi-hidraw.so` run on the hidraw-rs backend without changes.
+//! Compile against the upstream `hidapi.h`. Functions added in hidapi 0.15
+//! (`hid_send_output_report`, `hid_read_error`) are not exported, so
+//! [`hid_version`] stays truthful.
+//!
+//! Error messages follow hidapi: [`hid_error`] with a device returns the
+//! last failure on that device, and with null the last failure of
+//! [`hid_init`], [`hid_enumerate`] or the `hid_open` functions on the
+//! calling thread.
+
+mod info;
+mod wide;
+
+pub use info::HidDeviceInfo;
+
+use hidapi_compat::{HidApi, HidDevice, HidResult};
+use libc::{c_char, c_int, c_uchar, c_ushort, size_t, wchar_t};
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// `struct hid_api_version` from `hidapi.h`
+#[repr(C)]
+pub struct HidApiVersion {
+    pub major: c_int,
+    pub minor: c_int,
+    pub patch: c_int,
+}
+
+/// hidapi version whose ABI is implemented
+static VERSION: HidApiVersion = HidApiVersion {
+    major: 0,
+    minor: 14,
+    patch: 0,
+};
+
+thread_local! {
+    /// Wide message returned by `hid_error(NULL)`
+    static GLOBAL_ERROR: RefCell<Vec<wchar_t>> = RefCell::new(wide::to_wide("Success"));
+}
+
+/// Opaque `hid_device` handle
+///
+/// C callers may use one handle from several threads at once (e.g. a reader
+/// and a writer), so it is only ever borrowed shared and the lazily filled
+/// fields sit behind mutexes.
+pub struct Device {
+    inner: HidDevice,
+    /// Wide message returned by `hid_error(dev)`, kept alive until the next call
+    error: Mutex<Vec<wchar_t>>,
+    /// Returned by `hid_get_device_info`, freed with the device
+    info: Mutex<*mut HidDeviceInfo>,
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        let info = *self.info.get_mut().unwrap_or_else(PoisonError::into_inner);
+        // SAFETY: info is null or was allocated by HidDeviceInfo::into_raw
+        unsafe { info::free_list(info) };
+    }
+}
+
+/// Lock a handle field, ignoring poisoning by a panicked caller thread
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Record the message for `hid_error(NULL)`, "Success" when `None`
+fn set_global_error(message: Option<String>) {
+    let message = message.as_deref().unwrap_or("Success");
+    GLOBAL_ERROR.with(|error| *error.borrow_mut() = wide::to_wide(message));
+}
+
+/// Run a context-level operation, recording its outcome for `hid_error(NULL)`
+fn with_api<T>(f: impl FnOnce(&mut HidApi) -> HidResult<T>) -> Option<T> {
+    let mut api = match HidApi::new_without_enumerate() {
+        Ok(api) => api,
+        Err(e) => {
+            set_global_error(Some(e.to_string()));
+            return None;
+        }
+    };
+    match f(&mut api) {
+        Ok(value) => {
+            set_global_error(None);
+            Some(value)
+        }
+        Err(e) => {
+            set_global_error(Some(api.get_last_error().unwrap_or_else(|| e.to_string())));
+            None
+        }
+    }
+}
+
+/// Turn an open result into a handle, null on failure
+fn into_handle(device: Option<HidDevice>) -> *mut Device {
+    device.map_or(std::ptr::null_mut(), |inner| {
+        Box::into_raw(Box::new(Device {
+            inner,
+            error: Mutex::new(Vec::new()),
+            info: Mutex::new(std::ptr::null_mut()),
+        }))
+    })
+}
+
+/// Map a byte count to hidapi's `int` return, -1 on failure
+fn count(result: HidResult<usize>) -> c_int {
+    result.map_or(-1, |n| c_int::try_from(n).unwrap_or(c_int::MAX))
+}
+
+/// Borrow the handle behind `dev`
+///
+/// # Safety
+///
+/// `dev` must be null or a live handle from one of the `hid_open` functions.
+/// The borrow is shared since other threads may use the handle concurrently.
+unsafe fn device<'a>(dev: *mut Device) -> Option<&'a Device> {
+    // SAFETY: guaranteed by the caller
+    unsafe { dev.cast_const().as_ref() }
+}
+
+/// Borrow a C buffer as a slice, `None` when null
+///
+/// # Safety
+///
+/// `data` must be null or valid for `length` reads.
+unsafe fn input<'a>(data: *const c_uchar, length: size_t) -> Option<&'a [u8]> {
+    // SAFETY: guaranteed by the caller
+    (!data.is_null()).then(|| unsafe { std::slice::from_raw_parts(data, length) })
+}
+
+/// Borrow a C buffer as a mutable slice, `None` when null
+///
+/// # Safety
+///
+/// `data` must be null or valid for `length` writes.
+unsafe fn output<'a>(data: *mut c_uchar, length: size_t) -> Option<&'a mut [u8]> {
+    // SAFETY: guaranteed by the caller
+    (!data.is_null()).then(|| unsafe { std::slice::from_raw_parts_mut(data, length) })
+}
+
+/// Initialize the library; optional, as every call initializes on demand
+#[unsafe(no_mangle)]
+pub extern "C" fn hid_init() -> c_int {
+    with_api(|_| Ok(())).map_or(-1, |()| 0)
+}
+
+/// Release library resources; there are none to release
+#[unsafe(no_mangle)]
+pub extern "C" fn hid_exit() -> c_int {
+    0
+}
+
+/// List the connected devices matching `vendor_id` and `product_id`
+///
+/// 0 matches any ID. The list is released with [`hid_free_enumeration`];
+/// null means no devices or an error, see [`hid_error`].
+#[unsafe(no_mangle)]
+pub extern "C" fn hid_enumerate(vendor_id: c_ushort, product_id: c_ushort) -> *mut HidDeviceInfo {
+    let devices = with_api(|api| {
+        api.add_devices(vendor_id, product_id)?;
+        Ok(api.device_list().collect::<Vec<_>>())
+    });
+    match devices {
+        Some(devices) if !devices.is_empty() => info::into_list(&devices),
+        Some(_) => {
+            let message = if vendor_id == 0 && product_id == 0 {
+                "No HID devices found in the system."
+            } else {
+                "No HID devices with requested VID/PID found in the system."
+            };
+            set_global_error(Some(message.to_string()));
+            std::ptr::null_mut()
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Release a list from [`hid_enumerate`]
+///
+/// # Safety
+///
+/// `devs` must be null or a list from [`hid_enumerate`] not yet freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_free_enumeration(devs: *mut HidDeviceInfo) {
+    // SAFETY: guaranteed by the caller
+    unsafe { info::free_list(devs) };
+}
+
+/// Open the first device with these IDs, and this serial number if not null
+///
+/// # Safety
+///
+/// `serial_number` must be null or a NUL-terminated wide string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_open(
+    vendor_id: c_ushort,
+    product_id: c_ushort,
+    serial_number: *const wchar_t,
+) -> *mut Device {
+    // SAFETY: guaranteed by the caller
+    let serial = unsafe { wide::from_ptr(serial_number) };
+    into_handle(with_api(|api| match &serial {
+        Some(serial) => api.open_serial(vendor_id, product_id, serial),
+        None => api.open(vendor_id, product_id),
+    }))
+}
+
+/// Open a device by its hidraw node path
+///
+/// # Safety
+///
+/// `path` must be null or a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_open_path(path: *const c_char) -> *mut Device {
+    if path.is_null() {
+        set_global_error(Some("Device path is null".to_string()));
+        return std::ptr::null_mut();
+    }
+    // SAFETY: guaranteed by the caller
+    let path = unsafe { CStr::from_ptr(path) };
+    into_handle(with_api(|api| api.open_path(path)))
+}
+
+/// Write an output report; the first byte is the report ID (0 if unused)
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` reads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_write(
+    dev: *mut Device,
+    data: *const c_uchar,
+    length: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { input(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.write(data))
+}
+
+/// Read an input report, waiting up to `milliseconds` (-1 waits forever)
+///
+/// Returns the number of bytes read, 0 on timeout.
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_read_timeout(
+    dev: *mut Device,
+    data: *mut c_uchar,
+    length: size_t,
+    milliseconds: c_int,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { output(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.read_timeout(data, milliseconds))
+}
+
+/// Read an input report, honouring the blocking mode
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_read(dev: *mut Device, data: *mut c_uchar, length: size_t) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { output(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.read(data))
+}
+
+/// Switch between blocking (`nonblock == 0`) and non-blocking reads
+///
+/// # Safety
+///
+/// `dev` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_set_nonblocking(dev: *mut Device, nonblock: c_int) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return -1;
+    };
+    dev.inner
+        .set_blocking_mode(nonblock == 0)
+        .map_or(-1, |()| 0)
+}
+
+/// Send a feature report; the first byte is the report ID
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` reads.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_send_feature_report(
+    dev: *mut Device,
+    data: *const c_uchar,
+    length: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { input(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.send_feature_report(data).map(|()| data.len()))
+}
+
+/// Get a feature report; set the first byte to the report ID
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_feature_report(
+    dev: *mut Device,
+    data: *mut c_uchar,
+    length: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { output(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.get_feature_report(data))
+}
+
+/// Get an input report over the control pipe (Linux 5.11+)
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `data` valid for `length` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_input_report(
+    dev: *mut Device,
+    data: *mut c_uchar,
+    length: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(data)) = (unsafe { device(dev) }, unsafe { output(data, length) }) else {
+        return -1;
+    };
+    count(dev.inner.get_input_report(data))
+}
+
+/// Close a device and release its handle
+///
+/// # Safety
+///
+/// `dev` must be null or a live handle, which is invalid afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_close(dev: *mut Device) {
+    if !dev.is_null() {
+        // SAFETY: handles are allocated by into_handle
+        drop(unsafe { Box::from_raw(dev) });
+    }
+}
+
+/// Copy an optional string into a caller buffer, empty when missing
+///
+/// # Safety
+///
+/// `string` must be valid for `maxlen` writes.
+unsafe fn copy_string(
+    result: HidResult<Option<String>>,
+    string: *mut wchar_t,
+    maxlen: size_t,
+) -> c_int {
+    match result {
+        Ok(value) => {
+            // SAFETY: guaranteed by the caller
+            unsafe { wide::copy_to(value.as_deref().unwrap_or_default(), string, maxlen) };
+            0
+        }
+        Err(_) => -1,
+    }
+}
+
+/// Get the manufacturer string
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `string` valid for `maxlen` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_manufacturer_string(
+    dev: *mut Device,
+    string: *mut wchar_t,
+    maxlen: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return -1;
+    };
+    // SAFETY: guaranteed by the caller
+    unsafe { copy_string(dev.inner.get_manufacturer_string(), string, maxlen) }
+}
+
+/// Get the product string
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `string` valid for `maxlen` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_product_string(
+    dev: *mut Device,
+    string: *mut wchar_t,
+    maxlen: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return -1;
+    };
+    // SAFETY: guaranteed by the caller
+    unsafe { copy_string(dev.inner.get_product_string(), string, maxlen) }
+}
+
+/// Get the serial number string
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `string` valid for `maxlen` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_serial_number_string(
+    dev: *mut Device,
+    string: *mut wchar_t,
+    maxlen: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return -1;
+    };
+    // SAFETY: guaranteed by the caller
+    unsafe { copy_string(dev.inner.get_serial_number_string(), string, maxlen) }
+}
+
+/// Get a USB string descriptor by index
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `string` valid for `maxlen` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_indexed_string(
+    dev: *mut Device,
+    string_index: c_int,
+    string: *mut wchar_t,
+    maxlen: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return -1;
+    };
+    // SAFETY: guaranteed by the caller
+    unsafe { copy_string(dev.inner.get_indexed_string(string_index), string, maxlen) }
+}
+
+/// Get the device information of an open device
+///
+/// The result is owned by the device and valid until [`hid_close`].
+///
+/// # Safety
+///
+/// `dev` must be a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_device_info(dev: *mut Device) -> *mut HidDeviceInfo {
+    // SAFETY: guaranteed by the caller
+    let Some(dev) = (unsafe { device(dev) }) else {
+        return std::ptr::null_mut();
+    };
+    let mut info = lock(&dev.info);
+    if info.is_null()
+        && let Ok(device_info) = dev.inner.get_device_info()
+    {
+        *info = HidDeviceInfo::into_raw(&device_info);
+    }
+    *info
+}
+
+/// Copy the report descriptor into `buf`, returning its length
+///
+/// # Safety
+///
+/// `dev` must be a live handle and `buf` valid for `buf_size` writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_get_report_descriptor(
+    dev: *mut Device,
+    buf: *mut c_uchar,
+    buf_size: size_t,
+) -> c_int {
+    // SAFETY: guaranteed by the caller
+    let (Some(dev), Some(buf)) = (unsafe { device(dev) }, unsafe { output(buf, buf_size) }) else {
+        return -1;
+    };
+    count(dev.inner.get_report_descriptor(buf))
+}
+
+/// Message of the last failure on `dev`, or of the last context-level call
+/// on this thread when `dev` is null; "Success" when it succeeded
+///
+/// The string stays valid until the next `hid_error` call for the same
+/// device or thread.
+///
+/// # Safety
+///
+/// `dev` must be null or a live handle.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn hid_error(dev: *mut Device) -> *const wchar_t {
+    // SAFETY: guaranteed by the caller
+    match unsafe { device(dev) } {
+        Some(dev) => {
+            let message = dev.inner.get_last_error();
+            let mut error = lock(&dev.error);
+            *error = wide::to_wide(message.as_deref().unwrap_or("Success"));
+            error.as_ptr()
+        }
+        // The thread-local buffer is only replaced by later calls on this thread
+        None => GLOBAL_ERROR.with(|error| error.borrow().as_ptr()),
+    }
+}
+
+/// Version of the hidapi ABI implemented
+#[unsafe(no_mangle)]
+pub extern "C" fn hid_version() -> *const HidApiVersion {
+    &VERSION
+}
+
+/// Version of the hidapi ABI implemented, as "major.minor.patch"
+#[unsafe(no_mangle)]
+pub extern "C" fn hid_version_str() -> *const c_char {
+    c"0.14.0".as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn global_error() -> Option<String> {
+        // SAFETY: hid_error(NULL) returns a NUL-terminated thread-local string
+        unsafe { wide::from_ptr(hid_error(std::ptr::null_mut())) }
+    }
+
+    #[test]
+    fn test_open_path_error() {
+        // SAFETY: a valid C string; a null result is not dereferenced
+        let dev = unsafe { hid_open_path(c"/dev/hidraw-does-not-exist".as_ptr()) };
+        assert!(dev.is_null());
+        let message = global_error().unwrap_or_default();
+        assert!(
+            message.contains("/dev/hidraw-does-not-exist") || message.contains("initialize"),
+            "{message}"
+        );
+
+        set_global_error(None);
+        assert_eq!(global_error().as_deref(), Some("Success"));
+    }
+
+    #[test]
+    fn test_null_arguments() {
+        let mut buf = [0u8; 8];
+        let null = std::ptr::null_mut();
+        // SAFETY: null handles are rejected before any access
+        unsafe {
+            assert_eq!(hid_write(null, buf.as_ptr(), buf.len()), -1);
+            assert_eq!(hid_read_timeout(null, buf.as_mut_ptr(), buf.len(), 0), -1);
+            assert!(hid_get_device_info(null).is_null());
+            hid_close(null);
+            hid_free_enumeration(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn test_version() {
+        // SAFETY: both point to statics
+        let (version, text) = unsafe { (&*hid_version(), CStr::from_ptr(hid_version_str())) };
+        assert_eq!((version.major, version.minor, version.patch), (0, 14, 0));
+        assert_eq!(text.to_str(), Ok("0.14.0"));
+    }
+}
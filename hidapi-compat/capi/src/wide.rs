@@ -0,0 +1,126 @@
+//! `wchar_t` strings as used by the hidapi C API
+//!
+//! `wchar_t` is UTF-32 on Linux, so each `char` maps to one unit.
+
+use libc::{size_t, wchar_t};
+
+/// NUL-terminated wide copy of `s`, cut at any interior NUL
+///
+/// C reads up to the first NUL anyway, and [`free`] relies on it being the
+/// only one to recover the allocation length.
+pub(crate) fn to_wide(s: &str) -> Vec<wchar_t> {
+    s.chars()
+        .take_while(|&c| c != '\0')
+        .map(|c| c as wchar_t)
+        .chain(std::iter::once(0))
+        .collect()
+}
+
+/// Heap-allocated wide string owned by C, released with [`free`]
+pub(crate) fn into_raw(s: &str) -> *mut wchar_t {
+    Box::into_raw(to_wide(s).into_boxed_slice()).cast()
+}
+
+/// Release a string from [`into_raw`]
+///
+/// # Safety
+///
+/// `ptr` must be null or come from [`into_raw`] and not have been freed.
+pub(crate) unsafe fn free(ptr: *mut wchar_t) {
+    if ptr.is_null() {
+        return;
+    }
+    // SAFETY: into_raw allocated exactly len + 1 units, ending in the only NUL
+    unsafe {
+        let len = wcslen(ptr) + 1;
+        drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(ptr, len)));
+    }
+}
+
+/// Decode a NUL-terminated wide string, `None` for null or invalid text
+///
+/// # Safety
+///
+/// `ptr` must be null or point to a NUL-terminated `wchar_t` string.
+pub(crate) unsafe fn from_ptr(ptr: *const wchar_t) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+    // SAFETY: the caller guarantees a NUL within the allocation
+    let units = unsafe { std::slice::from_raw_parts(ptr, wcslen(ptr)) };
+    units
+        .iter()
+        .map(|&unit| u32::try_from(unit).ok().and_then(char::from_u32))
+        .collect()
+}
+
+/// Copy `s` into a caller buffer of `maxlen` units, truncating and always
+/// NUL-terminating as hidapi does
+///
+/// # Safety
+///
+/// `dst` must be valid for `maxlen` writes.
+pub(crate) unsafe fn copy_to(s: &str, dst: *mut wchar_t, maxlen: size_t) {
+    if dst.is_null() || maxlen == 0 {
+        return;
+    }
+    let wide = to_wide(s);
+    let n = (wide.len() - 1).min(maxlen - 1);
+    // SAFETY: n + 1 <= maxlen units are written
+    unsafe {
+        std::ptr::copy_nonoverlapping(wide.as_ptr(), dst, n);
+        dst.add(n).write(0);
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must point to a NUL-terminated `wchar_t` string.
+unsafe fn wcslen(ptr: *const wchar_t) -> usize {
+    let mut len = 0;
+    // SAFETY: reads stop at the terminating NUL
+    while unsafe { *ptr.add(len) } != 0 {
+        len += 1;
+    }
+    len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let raw = into_raw("Coldcard ✓");
+        // SAFETY: raw comes from into_raw and is freed once
+        unsafe {
+            assert_eq!(from_ptr(raw).as_deref(), Some("Coldcard ✓"));
+            free(raw);
+            assert_eq!(from_ptr(std::ptr::null()), None);
+        }
+    }
+
+    #[test]
+    fn test_interior_nul() {
+        assert_eq!(to_wide("ab\0cd"), to_wide("ab"));
+        let raw = into_raw("serial\0junk");
+        // SAFETY: raw comes from into_raw and is freed once, with the length
+        // it was allocated with
+        unsafe {
+            assert_eq!(from_ptr(raw).as_deref(), Some("serial"));
+            free(raw);
+        }
+    }
+
+    #[test]
+    fn test_copy_truncates() {
+        let mut buf = [-1 as wchar_t; 4];
+        // SAFETY: buf holds 4 units
+        unsafe {
+            copy_to("Coinkite", buf.as_mut_ptr(), buf.len());
+            assert_eq!(from_ptr(buf.as_ptr()).as_deref(), Some("Coi"));
+            copy_to("ok", buf.as_mut_ptr(), buf.len());
+            assert_eq!(from_ptr(buf.as_ptr()).as_deref(), Some("ok"));
+        }
+    }
+}
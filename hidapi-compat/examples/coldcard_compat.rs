@@ -46,7 +46,7 @@ fn main() -> HidResult<()> {
             println!("\nTrying to open device with serial: {serial}");
 
             match api.open_serial(COINKITE_VID, CKCC_PID, serial) {
-                Ok(mut device) => {
+                Ok(device) => {
                     println!("✓ Opened device successfully");
 
                     // Test write operation (ping-like)
@@ -106,7 +106,7 @@ fn test_device_operations() -> Result<(), HidError> {
     const TEST_PID: u16 = 0xcc10;
 
     match api.open(TEST_VID, TEST_PID) {
-        Ok(mut device) => {
+        Ok(device) => {
             println!("✅ open() works");
 
             // Test write
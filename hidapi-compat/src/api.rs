@@ -1,11 +1,15 @@
 //! HidApi implementation compatible with hidapi
 
+use crate::error::LastError;
 use crate::{DeviceInfo, DeviceInfoList, HidDevice, HidError, HidResult};
+use hidraw_rs::DeviceFilter;
+use hidraw_rs::hidraw::Enumerator;
 use std::ffi::CStr;
 
 /// Main hidapi context
 pub struct HidApi {
     devices: Vec<DeviceInfo>,
+    last_error: LastError,
 }
 
 impl HidApi {
@@ -13,13 +17,48 @@ impl HidApi {
     ///
     /// This initializes the HID API and caches the device list.
     pub fn new() -> HidResult<Self> {
-        let devices = Self::enumerate_devices()?;
-        Ok(Self { devices })
+        let mut api = Self::new_without_enumerate()?;
+        api.refresh_devices()?;
+        Ok(api)
+    }
+
+    /// Create a new hidapi instance with an empty device list
+    ///
+    /// Only checks that hidraw is available, so opening a known device never
+    /// reads the sysfs attributes of every other HID device. Fill the list
+    /// with [`add_devices`](Self::add_devices) or
+    /// [`refresh_devices`](Self::refresh_devices).
+    pub fn new_without_enumerate() -> HidResult<Self> {
+        if !Enumerator::new().is_available() {
+            return Err(HidError::InitializationError);
+        }
+        Ok(Self {
+            devices: Vec::new(),
+            last_error: LastError::default(),
+        })
     }
 
     /// Refresh the device list
     pub fn refresh_devices(&mut self) -> HidResult<()> {
-        self.devices = Self::enumerate_devices()?;
+        self.devices = self.enumerate_devices(&DeviceFilter::new())?;
+        Ok(())
+    }
+
+    /// Clear the device list
+    ///
+    /// Use [`add_devices`](Self::add_devices) to fill it again selectively.
+    pub fn reset_devices(&mut self) -> HidResult<()> {
+        self.devices.clear();
+        Ok(())
+    }
+
+    /// Append the connected devices matching a vendor and product ID
+    ///
+    /// An ID of 0 matches any value. As in hidapi, devices already in the
+    /// list are not deduplicated.
+    pub fn add_devices(&mut self, vendor_id: u16, product_id: u16) -> HidResult<()> {
+        let matching = self.enumerate_devices(&id_filter(vendor_id, product_id))?;
+        self.devices.extend(matching);
         Ok(())
     }
 
@@ -35,13 +74,19 @@ impl HidApi {
     }
 
     /// Open a device by vendor ID and product ID
+    ///
+    /// Like hidapi's `hid_open`, this enumerates the matching devices afresh
+    /// rather than consulting the device list.
     pub fn open(&self, vendor_id: u16, product_id: u16) -> HidResult<HidDevice> {
+        let filter = DeviceFilter::new()
+            .vendor_id(vendor_id)
+            .product_id(product_id);
         // Find the first matching device
-        let device_info = self
-            .devices
-            .iter()
-            .find(|d| d.vendor_id() == vendor_id && d.product_id() == product_id)
-            .ok_or(HidError::OpenHidDeviceError)?;
+        let Some(device_info) = self.enumerate_devices(&filter)?.into_iter().next() else {
+            return self.last_error.track(Err(HidError::HidApiError {
+                message: format!("No device {vendor_id:04x}:{product_id:04x} connected"),
+            }));
+        };
 
         self.open_path(device_info.path())
     }
@@ -53,45 +98,77 @@ impl HidApi {
         product_id: u16,
         serial: &str,
     ) -> HidResult<HidDevice> {
+        let filter = DeviceFilter::new()
+            .vendor_id(vendor_id)
+            .product_id(product_id);
         // Find the matching device with the specified serial
-        let device_info = self
-            .devices
-            .iter()
-            .find(|d| {
-                d.vendor_id() == vendor_id
-                    && d.product_id() == product_id
-                    && d.serial_number() == Some(serial)
-            })
-            .ok_or(HidError::OpenHidDeviceError)?;
+        let Some(device_info) = self
+            .enumerate_devices(&filter)?
+            .into_iter()
+            .find(|d| d.serial_number() == Some(serial))
+        else {
+            return self.last_error.track(Err(HidError::HidApiError {
+                message: format!(
+                    "No device {vendor_id:04x}:{product_id:04x} with serial '{serial}' connected"
+                ),
+            }));
+        };
 
         self.open_path(device_info.path())
     }
 
     /// Open a device by its path
     pub fn open_path(&self, path: &CStr) -> HidResult<HidDevice> {
-        let path_str = path.to_str().map_err(|_| HidError::HidApiError {
-            message: "Invalid device path".to_string(),
-        })?;
+        let path_str = self
+            .last_error
+            .track(path.to_str().map_err(|_| HidError::HidApiError {
+                message: "Invalid device path".to_string(),
+            }))?;
+
+        self.last_error.track_context(
+            hidraw_rs::HidDevice::open_path(path_str).and_then(HidDevice::new),
+            &format!("Failed to open a device with path '{path_str}'"),
+        )
+    }
 
-        let device =
-            hidraw_rs::HidDevice::open_path(path_str).map_err(|_| HidError::OpenHidDeviceError)?;
+    /// Get the message of the last failed operation on this context
+    pub fn get_last_error(&self) -> Option<String> {
+        self.last_error.get()
+    }
 
-        Ok(HidDevice::new(device))
+    /// Get the last error as a [`HidError`]
+    ///
+    /// Returns `Err(HidError::HidApiErrorEmpty)` when the last operation
+    /// succeeded.
+    pub fn check_error(&self) -> HidResult<HidError> {
+        self.last_error.check()
     }
 
-    /// Enumerate all HID devices
-    fn enumerate_devices() -> HidResult<Vec<DeviceInfo>> {
-        let hidraw_devices = hidraw_rs::enumerate().map_err(|_| HidError::InitializationError)?;
+    /// Enumerate the HID devices matching a filter
+    fn enumerate_devices(&self, filter: &DeviceFilter) -> HidResult<Vec<DeviceInfo>> {
+        let hidraw_devices = self.last_error.track_context(
+            Enumerator::new().enumerate_with(filter),
+            "Failed to enumerate devices",
+        )?;
 
-        let devices: Vec<DeviceInfo> = hidraw_devices
-            .into_iter()
-            .map(|d| DeviceInfo::from_hidraw(&d))
-            .collect();
+        let devices: Vec<DeviceInfo> = hidraw_devices.iter().map(DeviceInfo::from_hidraw).collect();
 
         Ok(devices)
     }
 }
 
+/// Filter on a vendor and product ID where 0 matches any value, as in hidapi
+fn id_filter(vendor_id: u16, product_id: u16) -> DeviceFilter {
+    let mut filter = DeviceFilter::new();
+    if vendor_id != 0 {
+        filter = filter.vendor_id(vendor_id);
+    }
+    if product_id != 0 {
+        filter = filter.product_id(product_id);
+    }
+    filter
+}
+
 // Implement AsRef for compatibility
 impl AsRef<HidApi> for HidApi {
     fn as_ref(&self) -> &HidApi {
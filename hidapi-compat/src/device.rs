@@ -1,141 +1,328 @@
 //! HID device implementation compatible with hidapi
 
-use crate::error::{HidError, HidResult};
+use crate::error::{HidError, HidResult, LastError};
+use crate::{DeviceInfo, usbfs};
+use std::io;
+use std::sync::{Mutex, MutexGuard, PoisonError};
 use std::time::Duration;
 
+/// How long each wait in an indefinitely blocking read lasts before waiting again
+const WAIT_FOREVER_STEP: Duration = Duration::from_secs(3600);
+
 /// HID device handle compatible with hidapi
+///
+/// All methods take `&self` and the handle is `Send + Sync`, so it can be
+/// shared between threads: reads go through their own duplicated file
+/// descriptor, letting one thread block in [`read`](Self::read) while
+/// another calls [`write`](Self::write).
 pub struct HidDevice {
-    inner: hidraw_rs::HidDevice,
-    blocking: bool,
+    /// Handle for reading input reports
+    reader: Mutex<hidraw_rs::HidDevice>,
+    /// Duplicate handle for writes, reports and queries
+    writer: Mutex<hidraw_rs::HidDevice>,
+    last_error: LastError,
 }
 
 impl HidDevice {
     /// Create a new HidDevice from a hidraw-rs device
-    pub(crate) fn new(device: hidraw_rs::HidDevice) -> Self {
-        Self {
-            inner: device,
-            blocking: true, // hidapi defaults to blocking mode
-        }
+    pub(crate) fn new(device: hidraw_rs::HidDevice) -> hidraw_rs::Result<Self> {
+        let writer = device.try_clone()?;
+        Ok(Self {
+            reader: Mutex::new(device),
+            writer: Mutex::new(writer),
+            last_error: LastError::default(),
+        })
+    }
+
+    /// Open another handle on the same device
+    ///
+    /// The handles share blocking mode but keep separate last errors.
+    pub fn try_clone(&self) -> HidResult<HidDevice> {
+        let result = lock(&self.writer).try_clone().and_then(Self::new);
+        self.last_error
+            .track_context(result, "Failed to duplicate device handle")
+    }
+
+    /// Record the outcome of an operation as the last error, as hidapi does
+    fn track<T>(&self, result: HidResult<T>) -> HidResult<T> {
+        self.last_error.track(result)
     }
 
     /// Write data to the device
     ///
     /// The first byte should be the report ID. For devices that don't use report IDs,
-    /// set the first byte to 0.
-    pub fn write(&mut self, data: &[u8]) -> HidResult<usize> {
+    /// set the first byte to 0. In non-blocking mode this fails instead of
+    /// waiting when the device cannot accept the report yet.
+    pub fn write(&self, data: &[u8]) -> HidResult<usize> {
         if data.is_empty() {
-            return Err(HidError::InvalidZeroSizeData);
+            return self.track(Err(HidError::InvalidZeroSizeData));
         }
 
-        match self.inner.write(data) {
-            Ok(n) => {
-                if n == data.len() {
-                    Ok(n)
-                } else {
-                    Err(HidError::IncompleteSendError {
-                        sent: n,
-                        all: data.len(),
-                    })
-                }
-            }
+        let result = match lock(&self.writer).write(data) {
+            Ok(n) if n == data.len() => Ok(n),
+            Ok(n) => Err(HidError::IncompleteSendError {
+                sent: n,
+                all: data.len(),
+            }),
             Err(e) => Err(e.into()),
-        }
+        };
+        self.track(result)
     }
 
     /// Read data from the device
-    pub fn read(&mut self, buf: &mut [u8]) -> HidResult<usize> {
-        if self.blocking {
-            // In blocking mode, wait indefinitely
-            self.inner.read(buf).map_err(Into::into)
-        } else {
-            // In non-blocking mode, return immediately
-            match self.inner.read_timeout(buf, Duration::from_millis(0)) {
-                Ok(n) => Ok(n),
-                Err(hidraw_rs::Error::Timeout) => {
-                    // hidapi returns 0 for non-blocking read with no data
-                    Ok(0)
-                }
-                Err(e) => Err(e.into()),
-            }
-        }
+    ///
+    /// In non-blocking mode this returns `Ok(0)` when no report is queued.
+    pub fn read(&self, buf: &mut [u8]) -> HidResult<usize> {
+        let result = lock(&self.reader).read(buf);
+        self.track(no_data_as_zero(result))
     }
 
     /// Read data from the device with a timeout
     ///
     /// `timeout` is in milliseconds. 0 means non-blocking, -1 means blocking indefinitely.
-    pub fn read_timeout(&mut self, buf: &mut [u8], timeout: i32) -> HidResult<usize> {
-        if timeout < 0 {
-            // Negative timeout means block indefinitely
-            self.inner.read(buf).map_err(Into::into)
-        } else {
-            let duration = Duration::from_millis(timeout as u64);
-            match self.inner.read_timeout(buf, duration) {
-                Ok(n) => Ok(n),
-                Err(hidraw_rs::Error::Timeout) => {
-                    // hidapi returns 0 when timeout occurs with no data
-                    // This is what rust-coldcard expects for resync operations
-                    Ok(0)
+    pub fn read_timeout(&self, buf: &mut [u8], timeout: i32) -> HidResult<usize> {
+        let result = if timeout < 0 {
+            // hidapi blocks here whatever the blocking mode, which only
+            // applies to plain reads; wait for a report instead of relying on
+            // a blocking read
+            let mut reader = lock(&self.reader);
+            loop {
+                match reader.read_timeout(buf, WAIT_FOREVER_STEP) {
+                    Err(hidraw_rs::Error::Timeout) => {}
+                    // Another handle took the report first
+                    Err(hidraw_rs::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    result => break result,
                 }
-                Err(e) => Err(e.into()),
             }
-        }
+        } else {
+            let duration = Duration::from_millis(timeout as u64);
+            // hidapi returns 0 when timeout occurs with no data
+            // This is what rust-coldcard expects for resync operations
+            lock(&self.reader).read_timeout(buf, duration)
+        };
+        self.track(no_data_as_zero(result))
     }
 
     /// Send a feature report to the device
-    pub fn send_feature_report(&mut self, data: &[u8]) -> HidResult<()> {
+    pub fn send_feature_report(&self, data: &[u8]) -> HidResult<()> {
         if data.is_empty() {
-            return Err(HidError::InvalidZeroSizeData);
+            return self.track(Err(HidError::InvalidZeroSizeData));
         }
 
-        self.inner
-            .send_feature_report(data)
-            .map_err(|_| HidError::SendFeatureReportError)?;
-        Ok(())
+        let result = lock(&self.writer).send_feature_report(data);
+        self.last_error
+            .track_context(result, "Failed to send feature report")
     }
 
     /// Get a feature report from the device
     ///
     /// The first byte should be the report ID.
-    pub fn get_feature_report(&mut self, buf: &mut [u8]) -> HidResult<usize> {
+    pub fn get_feature_report(&self, buf: &mut [u8]) -> HidResult<usize> {
+        if buf.is_empty() {
+            return self.track(Err(HidError::InvalidZeroSizeData));
+        }
+
+        let report_id = buf[0];
+        let result = lock(&self.writer).get_feature_report(report_id, buf);
+        self.last_error
+            .track_context(result, "Failed to get feature report")
+    }
+
+    /// Get an input report from the device
+    ///
+    /// The first byte should be the report ID. Requires Linux 5.11 or newer.
+    pub fn get_input_report(&self, buf: &mut [u8]) -> HidResult<usize> {
         if buf.is_empty() {
-            return Err(HidError::InvalidZeroSizeData);
+            return self.track(Err(HidError::InvalidZeroSizeData));
         }
 
         let report_id = buf[0];
-        self.inner
-            .get_feature_report(report_id, buf)
-            .map_err(|_| HidError::GetFeatureReportError)
+        let result = lock(&self.writer).get_input_report(report_id, buf);
+        self.track(result.map_err(Into::into))
+    }
+
+    /// Send an output report to the device
+    ///
+    /// The first byte should be the report ID. Requires Linux 5.11 or newer.
+    pub fn send_output_report(&self, data: &[u8]) -> HidResult<()> {
+        if data.is_empty() {
+            return self.track(Err(HidError::InvalidZeroSizeData));
+        }
+
+        let result = lock(&self.writer).send_output_report(data);
+        self.track(result.map_err(Into::into))
     }
 
     /// Set the device to blocking or non-blocking mode
-    pub fn set_blocking_mode(&mut self, blocking: bool) -> HidResult<()> {
-        self.blocking = blocking;
+    ///
+    /// Sets `O_NONBLOCK` on the file descriptor, so in non-blocking mode
+    /// neither `read` nor `write` wait for the device.
+    pub fn set_blocking_mode(&self, blocking: bool) -> HidResult<()> {
+        let result = lock(&self.writer).set_nonblocking(!blocking);
+        self.last_error
+            .track_context(result, "Failed to set blocking mode")?;
         Ok(())
     }
 
-    /// Get the last error string (not implemented in hidraw-rs)
+    /// Get the message of the last failed operation on this device
+    ///
+    /// Cleared by every successful operation, as in hidapi.
     pub fn get_last_error(&self) -> Option<String> {
-        None
+        self.last_error.get()
+    }
+
+    /// Get the last error as a [`HidError`]
+    ///
+    /// Returns `Err(HidError::HidApiErrorEmpty)` when the last operation
+    /// succeeded.
+    pub fn check_error(&self) -> HidResult<HidError> {
+        self.last_error.check()
     }
 
     /// Get manufacturer string
     pub fn get_manufacturer_string(&self) -> HidResult<Option<String>> {
-        Ok(self.inner.info().manufacturer.clone())
+        self.track(Ok(lock(&self.writer).info().manufacturer.clone()))
     }
 
     /// Get product string
     pub fn get_product_string(&self) -> HidResult<Option<String>> {
-        Ok(self.inner.info().product.clone())
+        self.track(Ok(lock(&self.writer).info().product.clone()))
     }
 
     /// Get serial number string
     pub fn get_serial_number_string(&self) -> HidResult<Option<String>> {
-        Ok(self.inner.info().serial_number.clone())
+        self.track(Ok(lock(&self.writer).info().serial_number.clone()))
+    }
+
+    /// Copy the report descriptor into `buf`
+    ///
+    /// Returns the number of bytes copied. A buffer of
+    /// [`MAX_REPORT_DESCRIPTOR_SIZE`](crate::MAX_REPORT_DESCRIPTOR_SIZE)
+    /// bytes always fits the whole descriptor.
+    pub fn get_report_descriptor(&self, buf: &mut [u8]) -> HidResult<usize> {
+        let result = lock(&self.writer)
+            .get_report_descriptor()
+            .map(|descriptor| {
+                let bytes = descriptor.as_bytes();
+                let n = bytes.len().min(buf.len());
+                buf[..n].copy_from_slice(&bytes[..n]);
+                n
+            });
+        self.track(result.map_err(Into::into))
+    }
+
+    /// Get the device information for this device
+    pub fn get_device_info(&self) -> HidResult<DeviceInfo> {
+        self.track(Ok(DeviceInfo::from_hidraw(lock(&self.writer).info())))
+    }
+
+    /// Get a USB string descriptor by index
+    ///
+    /// Fetched with a control transfer on the device's usbfs node, which
+    /// needs read access to `/dev/bus/usb/BBB/DDD`. Without it, the
+    /// manufacturer, product and serial number indexes still resolve to the
+    /// strings cached from sysfs, and other indexes fail with
+    /// [`HidError::HidApiError`]. A udev rule granting read access to the
+    /// usbfs node, e.g. one matching the device's vendor ID, enables them.
+    pub fn get_indexed_string(&self, index: i32) -> HidResult<Option<String>> {
+        let result = self.read_indexed_string(index);
+        self.track(result.map(Some))
+    }
+
+    fn read_indexed_string(&self, index: i32) -> HidResult<String> {
+        let invalid = |message: String| HidError::HidApiError { message };
+        let index = u8::try_from(index)
+            .ok()
+            .filter(|&index| index != 0)
+            .ok_or_else(|| invalid(format!("Invalid string index {index}")))?;
+
+        let writer = lock(&self.writer);
+        let details = writer.details()?;
+        let usb = details
+            .usb
+            .ok_or_else(|| invalid("Indexed strings are only available on USB".to_string()))?;
+        let (Some(bus_number), Some(device_number)) = (usb.bus_number, usb.device_number) else {
+            return Err(invalid("USB bus or device number unknown".to_string()));
+        };
+
+        let node = usbfs::device_node(bus_number, device_number);
+        let err = match usbfs::read_string(&node, index) {
+            Ok(string) => return Ok(string),
+            Err(err) => err,
+        };
+
+        // Layout: .../<usb device>/<usb interface>/<hid device>
+        let usb_dir = details.sysfs_path.parent().and_then(|dir| dir.parent());
+        let info = writer.info();
+        let cached = usb_dir.and_then(usbfs::standard_string_indexes).and_then(
+            |[manufacturer, product, serial]| match index {
+                i if i == manufacturer => info.manufacturer.clone(),
+                i if i == product => info.product.clone(),
+                i if i == serial => info.serial_number.clone(),
+                _ => None,
+            },
+        );
+
+        cached.ok_or_else(|| {
+            let hint = if err.kind() == io::ErrorKind::PermissionDenied {
+                " (indexed strings need read access to the usbfs node)"
+            } else {
+                ""
+            };
+            invalid(format!(
+                "Failed to read string descriptor {index} from {node}: {err}{hint}",
+                node = node.display()
+            ))
+        })
+    }
+}
+
+/// Lock a handle, ignoring poisoning since the handles hold no invariants
+fn lock(handle: &Mutex<hidraw_rs::HidDevice>) -> MutexGuard<'_, hidraw_rs::HidDevice> {
+    handle.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// hidapi reports "no data yet" as a successful read of 0 bytes
+fn no_data_as_zero(result: hidraw_rs::Result<usize>) -> HidResult<usize> {
+    match result {
+        Ok(n) => Ok(n),
+        Err(hidraw_rs::Error::Timeout) => Ok(0),
+        Err(hidraw_rs::Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => Ok(0),
+        Err(e) => Err(e.into()),
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::thread;
 
-    /// Get indexed string (not commonly used)
-    pub fn get_indexed_string(&self, _index: i32) -> HidResult<Option<String>> {
-        // hidraw-rs doesn't support indexed strings
-        Ok(None)
+    #[test]
+    fn test_negative_timeout_blocks_in_nonblocking_mode() -> HidResult<()> {
+        let info = hidraw_rs::DeviceInfo::new("/dev/hidraw-test0", 0xFFFF, 0xFFFE);
+        let (device, mut peer) = hidraw_rs::HidDevice::socket_pair(info)?;
+        let device = HidDevice::new(device)?;
+        device.set_blocking_mode(false)?;
+
+        let mut buf = [0u8; 16];
+        assert_eq!(device.read(&mut buf)?, 0);
+
+        let sender = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            peer.write_all(b"\x01report")
+        });
+        let n = device.read_timeout(&mut buf, -1)?;
+        assert_eq!(&buf[..n], b"\x01report");
+        sender
+            .join()
+            .map_err(|_| HidError::HidApiError {
+                message: "sender thread panicked".to_string(),
+            })?
+            .map_err(|e| HidError::HidApiError {
+                message: e.to_string(),
+            })?;
+
+        Ok(())
     }
 }
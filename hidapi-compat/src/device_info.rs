@@ -2,6 +2,28 @@
 
 use std::ffi::{CStr, CString};
 
+/// Bus the device is attached to, as reported by hidapi
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BusType {
+    Unknown = 0x00,
+    Usb = 0x01,
+    Bluetooth = 0x02,
+    I2c = 0x03,
+    Spi = 0x04,
+}
+
+impl From<hidraw_rs::BusType> for BusType {
+    fn from(bus: hidraw_rs::BusType) -> Self {
+        match bus {
+            hidraw_rs::BusType::Usb => BusType::Usb,
+            hidraw_rs::BusType::Bluetooth => BusType::Bluetooth,
+            hidraw_rs::BusType::I2c => BusType::I2c,
+            hidraw_rs::BusType::Spi => BusType::Spi,
+            _ => BusType::Unknown,
+        }
+    }
+}
+
 /// Device information structure that matches hidapi's API
 #[derive(Debug, Clone)]
 pub struct DeviceInfo {
@@ -15,6 +37,7 @@ pub struct DeviceInfo {
     usage_page: u16,
     usage: u16,
     interface_number: i32,
+    bus_type: BusType,
 }
 
 impl DeviceInfo {
@@ -25,14 +48,14 @@ impl DeviceInfo {
             vendor_id: info.vendor_id,
             product_id: info.product_id,
             serial_number: info.serial_number.clone(),
-            // hidraw-rs doesn't provide release_number, usage_page, or usage
-            // Set to defaults that match typical HID behavior
+            // hidraw-rs doesn't provide release_number
             release_number: 0,
             manufacturer_string: info.manufacturer.clone(),
             product_string: info.product.clone(),
-            usage_page: 0,
-            usage: 0,
+            usage_page: info.usage_page,
+            usage: info.usage,
             interface_number: info.interface_number,
+            bus_type: info.bus_type.into(),
         }
     }
 
@@ -86,6 +109,11 @@ impl DeviceInfo {
         self.interface_number
     }
 
+    /// Get the bus type
+    pub fn bus_type(&self) -> BusType {
+        self.bus_type
+    }
+
     /// Open this device
     pub fn open_device(&self, api: &crate::HidApi) -> crate::Result<crate::HidDevice> {
         api.open_path(&self.path)
@@ -1,11 +1,13 @@
 //! Error types compatible with hidapi
 
+use std::sync::{Mutex, PoisonError};
 use thiserror::Error;
 
 pub type HidResult<T> = std::result::Result<T, HidError>;
 
 #[derive(Error, Debug)]
 pub enum HidError {
+    /// Failure with a message describing the underlying cause
     #[error("hidapi error: {message}")]
     HidApiError { message: String },
 
@@ -50,39 +52,68 @@ pub enum HidError {
 }
 
 impl HidError {
-    /// Get the last error that happened, if any.
-    pub fn get_last_error(_device: &crate::HidDevice) -> Option<String> {
-        // hidraw-rs doesn't have a last error concept like hidapi
-        // Return None to indicate no error available
-        None
+    /// Get the last error that happened on `device`, if any.
+    pub fn get_last_error(device: &crate::HidDevice) -> Option<String> {
+        device.get_last_error()
     }
 }
 
-// Convert from hidraw-rs errors
+/// Message of the last failed operation
+///
+/// hidapi keeps one of these per device and one per context (`hid_error`);
+/// successful operations clear it.
+#[derive(Debug, Default)]
+pub(crate) struct LastError(Mutex<Option<String>>);
+
+impl LastError {
+    pub(crate) fn set(&self, message: Option<String>) {
+        *self.0.lock().unwrap_or_else(PoisonError::into_inner) = message;
+    }
+
+    pub(crate) fn get(&self) -> Option<String> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Record a result's error message (or clear it on success)
+    pub(crate) fn track<T>(&self, result: HidResult<T>) -> HidResult<T> {
+        self.set(result.as_ref().err().map(|e| match e {
+            HidError::HidApiError { message } => message.clone(),
+            e => e.to_string(),
+        }));
+        result
+    }
+
+    /// Like [`track`](Self::track) for a hidraw-rs result; the error
+    /// becomes a [`HidError::HidApiError`] prefixed with `context`
+    pub(crate) fn track_context<T>(
+        &self,
+        result: hidraw_rs::Result<T>,
+        context: &str,
+    ) -> HidResult<T> {
+        self.track(result.map_err(|e| HidError::HidApiError {
+            message: format!("{context}: {e}"),
+        }))
+    }
+
+    /// The last error as a [`HidError`], like hidapi's `check_error`
+    pub(crate) fn check(&self) -> HidResult<HidError> {
+        self.get()
+            .map(|message| HidError::HidApiError { message })
+            .ok_or(HidError::HidApiErrorEmpty)
+    }
+}
+
+// Convert from hidraw-rs errors, keeping the underlying cause
 impl From<hidraw_rs::Error> for HidError {
     fn from(err: hidraw_rs::Error) -> Self {
         match err {
             hidraw_rs::Error::Io(io_err) => HidError::IoError { error: io_err },
-            hidraw_rs::Error::DeviceNotFound => HidError::OpenHidDeviceError,
-            hidraw_rs::Error::PermissionDenied => HidError::HidApiError {
-                message: "Permission denied".to_string(),
-            },
-            hidraw_rs::Error::InvalidParameter(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::Timeout => HidError::HidApiError {
-                message: "Operation timed out".to_string(),
-            },
-            hidraw_rs::Error::NotSupported(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::BufferTooSmall { needed, got } => HidError::HidApiError {
-                message: format!("Buffer too small: needed {needed} bytes, got {got} bytes"),
-            },
-            hidraw_rs::Error::InvalidPath(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::InvalidData(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::Disconnected => HidError::HidApiError {
-                message: "Device disconnected".to_string(),
+            err => HidError::HidApiError {
+                message: err.to_string(),
             },
-            hidraw_rs::Error::Protocol(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::SystemCall(msg) => HidError::HidApiError { message: msg },
-            hidraw_rs::Error::Parse(msg) => HidError::HidApiError { message: msg },
         }
     }
 }
@@ -9,11 +9,15 @@ pub mod api;
 pub mod device;
 pub mod device_info;
 pub mod error;
+mod usbfs;
 
 pub use api::HidApi;
 pub use device::HidDevice;
-pub use device_info::{DeviceInfo, DeviceInfoList};
+pub use device_info::{BusType, DeviceInfo, DeviceInfoList};
 pub use error::{HidError, HidResult};
 
+/// Largest report descriptor the kernel will return, as in hidapi
+pub const MAX_REPORT_DESCRIPTOR_SIZE: usize = 4096;
+
 // Re-export as hidapi does
 pub type Result<T> = std::result::Result<T, HidError>;
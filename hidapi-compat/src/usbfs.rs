@@ -0,0 +1,151 @@
+//! USB string descriptors through usbfs
+//!
+//! hidraw only exposes the manufacturer, product and serial strings that
+//! sysfs caches. Other string descriptors are fetched with a
+//! `USBDEVFS_CONTROL` GET_DESCRIPTOR request on the device's
+//! `/dev/bus/usb/BBB/DDD` node, as libusb-based hidapi does.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+
+const USB_DIR_IN: u8 = 0x80;
+const USB_REQ_GET_DESCRIPTOR: u8 = 0x06;
+const USB_DT_STRING: u16 = 0x03;
+/// US English, used when the device lists no languages
+const DEFAULT_LANGID: u16 = 0x0409;
+const CONTROL_TIMEOUT_MS: u32 = 1000;
+
+/// `struct usbdevfs_ctrltransfer` from `linux/usbdevice_fs.h`
+#[repr(C)]
+struct CtrlTransfer {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+    timeout: u32,
+    data: *mut libc::c_void,
+}
+
+/// `_IOWR('U', 0, struct usbdevfs_ctrltransfer)`
+const USBDEVFS_CONTROL: u32 =
+    (3 << 30) | ((size_of::<CtrlTransfer>() as u32) << 16) | ((b'U' as u32) << 8);
+
+/// usbfs node of a USB device
+pub(crate) fn device_node(bus_number: u8, device_number: u8) -> PathBuf {
+    PathBuf::from(format!("/dev/bus/usb/{bus_number:03}/{device_number:03}"))
+}
+
+/// Indexes of the manufacturer, product and serial number strings
+///
+/// Read from the device descriptor at the start of the sysfs `descriptors`
+/// file of the USB device directory.
+pub(crate) fn standard_string_indexes(usb_dir: &Path) -> Option<[u8; 3]> {
+    let descriptors = std::fs::read(usb_dir.join("descriptors")).ok()?;
+    let device = descriptors.get(..18)?;
+    Some([device[14], device[15], device[16]])
+}
+
+/// Fetch string descriptor `index` in the device's first language
+pub(crate) fn read_string(node: &Path, index: u8) -> io::Result<String> {
+    let file = open(node)?;
+
+    let mut buf = [0u8; 255];
+    let n = get_string_descriptor(&file, 0, 0, &mut buf)?;
+    let langid = match buf.get(2..n.min(4)) {
+        Some([lo, hi]) => u16::from_le_bytes([*lo, *hi]),
+        _ => DEFAULT_LANGID,
+    };
+
+    let n = get_string_descriptor(&file, index, langid, &mut buf)?;
+    decode_string_descriptor(&buf[..n])
+}
+
+/// Open read-write like libusb, falling back to read-only which still
+/// allows GET_DESCRIPTOR on most kernels
+fn open(node: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(node)
+        .or_else(|_| File::open(node))
+}
+
+fn get_string_descriptor(file: &File, index: u8, langid: u16, buf: &mut [u8]) -> io::Result<usize> {
+    let mut transfer = CtrlTransfer {
+        request_type: USB_DIR_IN,
+        request: USB_REQ_GET_DESCRIPTOR,
+        value: (USB_DT_STRING << 8) | u16::from(index),
+        index: langid,
+        length: buf.len().min(u16::MAX as usize) as u16,
+        timeout: CONTROL_TIMEOUT_MS,
+        data: buf.as_mut_ptr().cast(),
+    };
+
+    // SAFETY: `transfer` matches the kernel's struct layout and `data`
+    // points to `length` writable bytes that outlive the call
+    #[cfg(target_env = "musl")]
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            USBDEVFS_CONTROL as libc::c_int,
+            &mut transfer,
+        )
+    };
+    #[cfg(not(target_env = "musl"))]
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            USBDEVFS_CONTROL as libc::c_ulong,
+            &mut transfer,
+        )
+    };
+
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+/// Decode a string descriptor (`bLength`, `bDescriptorType`, UTF-16LE text)
+fn decode_string_descriptor(descriptor: &[u8]) -> io::Result<String> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+    let (&[length, kind], text) = descriptor
+        .split_first_chunk::<2>()
+        .ok_or_else(|| invalid("String descriptor too short"))?;
+    if u16::from(kind) != USB_DT_STRING {
+        return Err(invalid("Not a string descriptor"));
+    }
+
+    let end = (usize::from(length).max(2) - 2).min(text.len());
+    let units: Vec<u16> = text[..end]
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16(&units).map_err(|_| invalid("Invalid UTF-16 in string descriptor"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_string_descriptor() -> io::Result<()> {
+        let descriptor = [10, 3, b'C', 0, b'a', 0, b'r', 0, b'd', 0, 0xAA];
+        assert_eq!(decode_string_descriptor(&descriptor)?, "Card");
+        assert!(decode_string_descriptor(&[4, 2, 0, 0]).is_err());
+        assert!(decode_string_descriptor(&[2]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_control_opcode() {
+        // 24-byte struct on 64-bit targets, matching the kernel headers
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(USBDEVFS_CONTROL, 0xC018_5500);
+        assert_eq!(device_node(1, 4), PathBuf::from("/dev/bus/usb/001/004"));
+    }
+}
@@ -29,6 +29,24 @@ fn test_error_types() {
     let _err3 = HidError::InvalidZeroSizeData;
 }
 
+#[test]
+fn test_error_keeps_cause() {
+    let err = HidError::from(hidraw_rs::Error::PermissionDenied);
+    assert!(
+        matches!(&err, HidError::HidApiError { message } if message.contains("Permission denied")),
+        "{err}"
+    );
+
+    let err = HidError::from(hidraw_rs::Error::InvalidPath("/dev/null".to_string()));
+    assert!(err.to_string().contains("/dev/null"), "{err}");
+}
+
+#[test]
+fn test_device_is_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<hidapi_compat::HidDevice>();
+}
+
 #[test]
 fn test_device_info() {
     // Test that we can create and use DeviceInfo
@@ -58,3 +76,65 @@ fn test_device_info() {
         }
     }
 }
+
+#[test]
+fn test_reset_and_add_devices() {
+    let Ok(mut api) = HidApi::new() else {
+        return;
+    };
+    let all = api.device_list().count();
+
+    assert!(api.reset_devices().is_ok());
+    assert_eq!(api.device_list().count(), 0);
+
+    // 0 matches any vendor and product, so this restores the full list
+    assert!(api.add_devices(0, 0).is_ok());
+    assert_eq!(api.device_list().count(), all);
+
+    assert!(api.reset_devices().is_ok());
+    assert!(api.add_devices(0xFFFF, 0xFFFF).is_ok());
+    assert!(
+        api.device_list()
+            .all(|d| d.vendor_id() == 0xFFFF && d.product_id() == 0xFFFF)
+    );
+}
+
+#[test]
+fn test_new_without_enumerate() {
+    let Ok(mut api) = HidApi::new_without_enumerate() else {
+        return;
+    };
+    assert_eq!(api.device_list().count(), 0);
+
+    assert!(api.add_devices(0xFFFF, 0xFFFF).is_ok());
+    assert!(
+        api.device_list()
+            .all(|d| d.vendor_id() == 0xFFFF && d.product_id() == 0xFFFF)
+    );
+
+    // Opening doesn't depend on the device list
+    assert!(api.open(0xFFFF, 0xFFFE).is_err());
+    let message = api.get_last_error().unwrap_or_default();
+    assert!(message.contains("ffff:fffe"), "{message}");
+}
+
+#[test]
+fn test_last_error_on_api() {
+    let Ok(api) = HidApi::new() else {
+        return;
+    };
+    assert!(matches!(api.check_error(), Err(HidError::HidApiErrorEmpty)));
+
+    let missing = c"/dev/hidraw-does-not-exist";
+    let err = api.open_path(missing).err();
+    assert!(
+        matches!(&err, Some(HidError::HidApiError { message }) if message.contains("/dev/hidraw-does-not-exist")),
+        "{err:?}"
+    );
+    let message = api.get_last_error().unwrap_or_default();
+    assert!(message.contains("/dev/hidraw-does-not-exist"), "{message}");
+    assert!(matches!(
+        api.check_error(),
+        Ok(HidError::HidApiError { .. })
+    ));
+}
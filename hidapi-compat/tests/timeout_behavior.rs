@@ -24,7 +24,7 @@ fn test_timeout_returns_zero() {
 
     // Try to open the first device
     let device_info = &devices[0];
-    let mut device = match device_info.open_device(&api) {
+    let device = match device_info.open_device(&api) {
         Ok(dev) => dev,
         Err(_) => {
             println!("Skipping test - couldn't open device");
@@ -2,10 +2,13 @@
 //!
 //! This module provides async versions of HID device operations using tokio.
 
-use crate::hidraw::{HidrawDevice, sys};
-use crate::{DeviceInfo, Error, Result};
+use crate::descriptor::ReportLayout;
+use crate::hidraw::{HidrawDevice, MAX_REPORT_LEN, sys};
+use crate::protocol::{HidReport, ReportType};
+use crate::{DeviceInfo, Error, ReportDescriptor, Result};
 use std::os::unix::io::AsRawFd;
 use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::Duration;
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
@@ -14,7 +17,7 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 pub struct AsyncHidrawDevice {
     file: File,
     path: PathBuf,
-    report_size: usize,
+    descriptor_size: usize,
 }
 
 impl AsyncHidrawDevice {
@@ -35,18 +38,49 @@ impl AsyncHidrawDevice {
         Ok(Self {
             file,
             path: path.to_owned(),
-            report_size: sync_device.report_size(),
+            descriptor_size: sync_device.descriptor_size(),
         })
     }
 
+    /// Open a second handle on the same device (duplicated descriptor)
+    pub fn try_clone(&self) -> Result<Self> {
+        let new_fd = rustix::io::dup(&self.file).map_err(|e| Error::Io(e.into()))?;
+
+        Ok(Self {
+            file: File::from_std(std::fs::File::from(new_fd)),
+            path: self.path.clone(),
+            descriptor_size: self.descriptor_size,
+        })
+    }
+
+    /// Duplicate the descriptor into a blocking [`HidrawDevice`]
+    ///
+    /// Both share one open file description, like [`try_clone`](Self::try_clone).
+    pub(crate) fn try_clone_sync(&self) -> Result<HidrawDevice> {
+        let new_fd = rustix::io::dup(&self.file).map_err(|e| Error::Io(e.into()))?;
+        Ok(HidrawDevice::from_parts(
+            std::fs::File::from(new_fd),
+            &self.path,
+            self.descriptor_size,
+        ))
+    }
+
     /// Get the device path
     pub fn path(&self) -> &Path {
         &self.path
     }
 
-    /// Get the report descriptor size
+    /// Get the report descriptor length in bytes
+    pub fn descriptor_size(&self) -> usize {
+        self.descriptor_size
+    }
+
+    /// Get the report descriptor length in bytes
+    #[deprecated(
+        note = "This is the descriptor length, not a report length; use descriptor_size()"
+    )]
     pub fn report_size(&self) -> usize {
-        self.report_size
+        self.descriptor_size
     }
 
     /// Read a HID report asynchronously
@@ -110,6 +144,25 @@ impl AsyncHidrawDevice {
         crate::hidraw::ioctl::ioctl_write_buf(&self.file, sys::hidiocsfeature(data.len()), data)?;
         Ok(())
     }
+
+    /// Get an input report (synchronous - ioctl doesn't have async variant)
+    pub fn get_input_report(&self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        crate::hidraw::ioctl::get_input_report(&self.file, report_id, buf)
+    }
+
+    /// Send an output report (synchronous - ioctl doesn't have async variant)
+    pub fn send_output_report(&self, data: &[u8]) -> Result<()> {
+        crate::hidraw::ioctl::send_output_report(&self.file, data)
+    }
+
+    /// Get the report descriptor (synchronous - ioctl doesn't have async variant)
+    pub fn get_report_descriptor(&self) -> Result<ReportDescriptor> {
+        let raw_desc = crate::hidraw::ioctl::get_report_descriptor(&self.file)?;
+        Ok(ReportDescriptor {
+            size: raw_desc.size as usize,
+            data: raw_desc.value.to_vec(),
+        })
+    }
 }
 
 impl AsRawFd for AsyncHidrawDevice {
@@ -122,16 +175,14 @@ impl AsRawFd for AsyncHidrawDevice {
 pub struct AsyncHidDevice {
     raw: AsyncHidrawDevice,
     info: DeviceInfo,
+    layout: OnceLock<ReportLayout>,
 }
 
 impl AsyncHidDevice {
     /// Open a HID device from DeviceInfo
     pub async fn open(info: &DeviceInfo) -> Result<Self> {
         let raw = AsyncHidrawDevice::open(&info.path).await?;
-        Ok(Self {
-            raw,
-            info: info.clone(),
-        })
+        Ok(Self::from_parts(raw, info.clone()))
     }
 
     /// Open a HID device by path
@@ -142,7 +193,7 @@ impl AsyncHidDevice {
         // Try to get device info from sysfs
         let info = crate::hidraw::get_device_info(&path)?;
 
-        Ok(Self { raw, info })
+        Ok(Self::from_parts(raw, info))
     }
 
     /// Open the first device matching vendor and product ID
@@ -153,6 +204,69 @@ impl AsyncHidDevice {
         Self::open(&device_info).await
     }
 
+    /// Open the device a [`crate::DeviceId`] currently refers to
+    pub async fn open_by_id(id: &crate::DeviceId) -> Result<Self> {
+        let path = id.resolve()?.ok_or(Error::DeviceNotFound)?;
+        let raw = AsyncHidrawDevice::open(&path).await?;
+        let info = crate::hidraw::get_device_info(&path)?;
+
+        Ok(Self::from_parts(raw, info))
+    }
+
+    /// Open the first device matching a filter
+    pub async fn open_matching(filter: &crate::DeviceFilter) -> Result<Self> {
+        let devices = crate::enumerate_with(filter)?;
+        let device_info = devices.into_iter().next().ok_or(Error::DeviceNotFound)?;
+
+        Self::open(&device_info).await
+    }
+
+    /// Open a second handle on the same device
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            raw: self.raw.try_clone()?,
+            info: self.info.clone(),
+            layout: self.layout.clone(),
+        })
+    }
+
+    /// Duplicate the descriptor into a blocking [`HidrawDevice`]
+    pub(crate) fn try_clone_sync(&self) -> Result<HidrawDevice> {
+        self.raw.try_clone_sync()
+    }
+
+    /// Wrap a blocking handle, e.g. one end of a socket pair in tests
+    #[cfg(test)]
+    pub(crate) fn from_raw(raw: HidrawDevice, info: DeviceInfo) -> Self {
+        let raw = AsyncHidrawDevice {
+            path: raw.path().to_owned(),
+            descriptor_size: raw.descriptor_size(),
+            file: File::from_std(raw.into_file()),
+        };
+        Self::from_parts(raw, info)
+    }
+
+    fn from_parts(raw: AsyncHidrawDevice, info: DeviceInfo) -> Self {
+        Self {
+            raw,
+            info,
+            layout: OnceLock::new(),
+        }
+    }
+
+    /// Split into halves that can be used from different tasks at once
+    ///
+    /// Works like `tokio::io::split`, but the writer gets its own
+    /// duplicated descriptor instead of sharing a lock with the reader, so a
+    /// task pending in [`AsyncHidReader::read`] never delays writes.
+    pub fn split(self) -> Result<(AsyncHidReader, AsyncHidWriter)> {
+        let writer = self.try_clone()?;
+        Ok((
+            AsyncHidReader { device: self },
+            AsyncHidWriter { device: writer },
+        ))
+    }
+
     /// Get device information
     pub fn info(&self) -> &DeviceInfo {
         &self.info
@@ -188,12 +302,162 @@ impl AsyncHidDevice {
         self.raw.send_feature_report(data)
     }
 
+    /// Get an input report
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.raw.get_input_report(report_id, buf)
+    }
+
+    /// Send an output report
+    pub fn send_output_report(&mut self, data: &[u8]) -> Result<()> {
+        self.raw.send_output_report(data)
+    }
+
+    /// Get the HID report descriptor
+    pub fn get_report_descriptor(&self) -> Result<ReportDescriptor> {
+        self.raw.get_report_descriptor()
+    }
+
+    /// Get the report layout parsed from the report descriptor
+    ///
+    /// The descriptor is fetched and parsed on first use and cached.
+    pub fn report_layout(&self) -> Result<&ReportLayout> {
+        if let Some(layout) = self.layout.get() {
+            return Ok(layout);
+        }
+
+        let layout = self.get_report_descriptor()?.layout()?;
+        Ok(self.layout.get_or_init(|| layout))
+    }
+
+    /// Maximum length of an input report as returned by `read`
+    ///
+    /// Includes the report ID prefix when the device uses numbered reports.
+    pub fn max_input_report_len(&self) -> Result<usize> {
+        Ok(self.report_layout()?.input_buffer_len())
+    }
+
+    /// Buffer length for feature report `report_id`, including the report ID byte
+    pub fn feature_report_len(&self, report_id: u8) -> Result<usize> {
+        self.report_layout()?.feature_buffer_len(report_id)
+    }
+
+    /// Read one input report with a timeout, sized from the report descriptor
+    ///
+    /// The report ID prefix is stripped for numbered reports; devices without
+    /// numbered reports yield report ID 0.
+    pub async fn read_report_timeout(&mut self, timeout: Duration) -> Result<HidReport> {
+        let uses_report_ids = self.report_layout()?.uses_report_ids();
+        let mut buf = vec![0u8; self.max_input_report_len()?.clamp(1, MAX_REPORT_LEN)];
+        let n = self.read_timeout(&mut buf, timeout).await?;
+        buf.truncate(n);
+
+        HidReport::from_report_bytes(&buf, ReportType::Input, uses_report_ids)
+    }
+
+    /// Get feature report `report_id`, sized from the report descriptor
+    ///
+    /// The returned report data excludes the report ID byte.
+    pub fn get_feature(&mut self, report_id: u8) -> Result<HidReport> {
+        let len = self.feature_report_len(report_id)?;
+        crate::device::get_feature_sized(report_id, len, |buf| {
+            self.get_feature_report(report_id, buf)
+        })
+    }
+
     /// Get the raw file descriptor (for advanced usage)
     pub fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
         self.raw.as_raw_fd()
     }
 }
 
+/// Read half of an [`AsyncHidDevice`], from [`AsyncHidDevice::split`]
+#[derive(Debug)]
+pub struct AsyncHidReader {
+    device: AsyncHidDevice,
+}
+
+impl AsyncHidReader {
+    /// Get device information
+    pub fn info(&self) -> &DeviceInfo {
+        self.device.info()
+    }
+
+    /// Read data from the device
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.device.read(buf).await
+    }
+
+    /// Read with timeout
+    pub async fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.device.read_timeout(buf, timeout).await
+    }
+
+    /// Put the halves back together, closing the writer's descriptor
+    ///
+    /// Halves of different devices, by path or [`DeviceId`](crate::DeviceId),
+    /// are handed back unchanged.
+    #[allow(
+        clippy::result_large_err,
+        reason = "the halves are handed back by value, as with tokio's ReuniteError"
+    )]
+    pub fn reunite(
+        self,
+        writer: AsyncHidWriter,
+    ) -> std::result::Result<AsyncHidDevice, (Self, AsyncHidWriter)> {
+        if !crate::split::same_device(self.info(), writer.info()) {
+            return Err((self, writer));
+        }
+        drop(writer);
+        Ok(self.device)
+    }
+}
+
+/// Write half of an [`AsyncHidDevice`], from [`AsyncHidDevice::split`]
+///
+/// Also carries the control-pipe operations (feature, input and output
+/// reports).
+#[derive(Debug)]
+pub struct AsyncHidWriter {
+    device: AsyncHidDevice,
+}
+
+impl AsyncHidWriter {
+    /// Get device information
+    pub fn info(&self) -> &DeviceInfo {
+        self.device.info()
+    }
+
+    /// Write data to the device
+    pub async fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.device.write(data).await
+    }
+
+    /// Write with timeout
+    pub async fn write_timeout(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        self.device.write_timeout(data, timeout).await
+    }
+
+    /// Get a feature report
+    pub fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.device.get_feature_report(report_id, buf)
+    }
+
+    /// Send a feature report
+    pub fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        self.device.send_feature_report(data)
+    }
+
+    /// Get an input report
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.device.get_input_report(report_id, buf)
+    }
+
+    /// Send an output report
+    pub fn send_output_report(&mut self, data: &[u8]) -> Result<()> {
+        self.device.send_output_report(data)
+    }
+}
+
 impl std::fmt::Debug for AsyncHidDevice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("AsyncHidDevice")
@@ -0,0 +1,96 @@
+//! Hex parsing and formatting for report data
+
+use hidraw_rs::{Error, Result};
+
+/// Parse bytes written as hex, e.g. `"01 02 ff"`, `"0102ff"` or `"0x01,0x02"`
+///
+/// Each separated group is decoded on its own: `"1 23"` is `01 23`, and
+/// `"123 456"` is rejected rather than read as `12 34 56`.
+pub fn parse(text: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    for group in text
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ':')
+        .filter(|group| !group.is_empty())
+    {
+        let digits = group
+            .strip_prefix("0x")
+            .or_else(|| group.strip_prefix("0X"))
+            .unwrap_or(group);
+        // Checked up front so slicing below stays on character boundaries
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::InvalidParameter(format!("Invalid hex in '{text}'")));
+        }
+        // Allow single-digit bytes when separated ("1 2 ff")
+        if digits.len() == 1 {
+            bytes.push(parse_byte(digits, text)?);
+            continue;
+        }
+        if !digits.len().is_multiple_of(2) {
+            return Err(Error::InvalidParameter(format!(
+                "Odd number of hex digits in '{group}' of '{text}'"
+            )));
+        }
+        for i in (0..digits.len()).step_by(2) {
+            bytes.push(parse_byte(&digits[i..i + 2], text)?);
+        }
+    }
+    Ok(bytes)
+}
+
+fn parse_byte(digits: &str, text: &str) -> Result<u8> {
+    u8::from_str_radix(digits, 16)
+        .map_err(|_| Error::InvalidParameter(format!("Invalid hex in '{text}'")))
+}
+
+/// Format bytes as space-separated hex
+pub fn format(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Print a classic hexdump with offsets and an ASCII column
+pub fn dump(data: &[u8]) {
+    print!("{dump}", dump = hidraw_rs::monitor::hexdump(data));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_forms() -> Result<()> {
+        assert_eq!(parse("01 02 ff")?, [0x01, 0x02, 0xff]);
+        assert_eq!(parse("0102FF")?, [0x01, 0x02, 0xff]);
+        assert_eq!(parse("0x01,0X02:ff")?, [0x01, 0x02, 0xff]);
+        assert_eq!(parse("1 2 f")?, [0x01, 0x02, 0x0f]);
+        assert_eq!(parse("1 23")?, [0x01, 0x23]);
+        assert!(parse("")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_input() -> Result<()> {
+        assert!(parse("012").is_err());
+        // Odd groups are not joined with their neighbours into "123456"
+        assert!(parse("123 456").is_err());
+        assert!(parse("0x123:4").is_err());
+        assert!(parse("0g").is_err());
+        // Only one prefix per group, and it needs digits after it
+        assert!(parse("0x0x01").is_err());
+        assert!(parse("0x").is_err());
+        assert!(parse("01 0X 02").is_err());
+        // Multi-byte characters must not split inside a char
+        assert!(parse("aé1").is_err());
+        assert!(parse("é").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format() -> Result<()> {
+        assert_eq!(format(&[0x00, 0xab, 0x10]), "00 ab 10");
+        assert_eq!(parse(&format(&[0xde, 0xad]))?, [0xde, 0xad]);
+        Ok(())
+    }
+}
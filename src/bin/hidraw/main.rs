@@ -0,0 +1,525 @@
+//! `hidraw` command-line tool
+//!
+//! Built with `cargo build --features cli`. Devices are selected by node
+//! path, `VID:PID`, filter string or serial number (see `select.rs`).
+
+mod hex;
+mod select;
+
+use clap::{Parser, Subcommand};
+use hidraw_rs::capture::{HidRecording, RecordedEvent};
+use hidraw_rs::coldcard::ColdcardDevice;
+use hidraw_rs::descriptor::{ItemType, main as main_tag};
+use hidraw_rs::monitor::ReportMonitor;
+use hidraw_rs::permissions::{UdevRule, diagnose};
+use hidraw_rs::uhid::UhidDevice;
+use hidraw_rs::{DeviceFilter, DeviceInfo, Error, HidDevice, Result, Transport};
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(
+    name = "hidraw",
+    version,
+    about = "Inspect and talk to Linux hidraw devices"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List HID devices
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+        /// Only list devices matching a filter (e.g. "vid=d13e,pid=cc10")
+        #[arg(long)]
+        filter: Option<DeviceFilter>,
+    },
+    /// Show everything known about a device, including permission problems
+    Info {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Dump the report descriptor
+    Descriptor {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Decode the items instead of printing a hexdump
+        #[arg(long)]
+        parsed: bool,
+    },
+    /// Read input reports
+    Read {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Timeout per report in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        timeout: u64,
+        /// Number of reports to read
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    /// Write an output report (first byte is the report ID, 00 if unnumbered)
+    Write {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Report bytes in hex, e.g. "00 01 02"
+        data: String,
+    },
+    /// Get a feature report
+    GetFeature {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Report ID (0 if the device does not use report IDs)
+        #[arg(value_parser = parse_u8)]
+        report_id: u8,
+        /// Buffer length including the report ID (default: from the descriptor)
+        #[arg(long)]
+        len: Option<usize>,
+    },
+    /// Send a feature report (first byte is the report ID)
+    SetFeature {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Report bytes in hex
+        data: String,
+    },
+    /// Print input reports from one or more devices as they arrive
+    Monitor {
+        /// Device paths, VID:PIDs, filters or serial numbers
+        #[arg(required = true)]
+        devices: Vec<String>,
+        /// Hide reports identical to the previous one with the same report ID
+        #[arg(long)]
+        suppress_unchanged: bool,
+        /// Don't decode fields with the report descriptor
+        #[arg(long)]
+        no_decode: bool,
+        /// Stop after this many reports
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Record input reports in the hid-tools format (hid-recorder)
+    Record {
+        /// Device path, VID:PID, filter or serial number
+        device: String,
+        /// Output file (default: stdout)
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Stop after this many reports
+        #[arg(long)]
+        count: Option<usize>,
+    },
+    /// Replay an hid-tools recording through virtual uhid devices (hid-replay)
+    Replay {
+        /// Recording file
+        file: PathBuf,
+        /// Send reports back to back instead of with the recorded timing
+        #[arg(long)]
+        fast: bool,
+    },
+    /// Coldcard hardware wallet commands
+    Coldcard {
+        /// Device path, VID:PID, filter or serial number (default: first Coldcard)
+        #[arg(long)]
+        device: Option<String>,
+        #[command(subcommand)]
+        command: ColdcardCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ColdcardCommand {
+    /// Send a ping and print the echoed message
+    Ping {
+        #[arg(default_value = "hello")]
+        message: String,
+    },
+    /// Print the firmware version
+    Version,
+}
+
+/// Parse a decimal or `0x`-prefixed hex byte
+fn parse_u8(text: &str) -> std::result::Result<u8, String> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => text.parse(),
+    }
+    .map_err(|e| format!("invalid report ID '{text}': {e}"))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::List { json, filter } => list(json, &filter.unwrap_or_default()),
+        Command::Info { device, json } => info(&select::find_device(&device)?, json),
+        Command::Descriptor { device, parsed } => descriptor(&open(&device)?, parsed),
+        Command::Read {
+            device,
+            timeout,
+            count,
+        } => read(&mut open(&device)?, Duration::from_millis(timeout), count),
+        Command::Write { device, data } => {
+            let written = open(&device)?.write(&hex::parse(&data)?)?;
+            println!("Wrote {written} bytes");
+            Ok(())
+        }
+        Command::GetFeature {
+            device,
+            report_id,
+            len,
+        } => {
+            let mut device = open(&device)?;
+            let len = match len {
+                Some(len) => len,
+                None => device.feature_report_len(report_id)?,
+            };
+            let mut buf = vec![0u8; len];
+            let n = device.get_feature_report(report_id, &mut buf)?;
+            println!("{hex}", hex = hex::format(&buf[..n.min(len)]));
+            Ok(())
+        }
+        Command::SetFeature { device, data } => {
+            open(&device)?.send_feature_report(&hex::parse(&data)?)?;
+            println!("Feature report sent");
+            Ok(())
+        }
+        Command::Monitor {
+            devices,
+            suppress_unchanged,
+            no_decode,
+            count,
+        } => {
+            let devices = devices
+                .iter()
+                .map(|spec| open(spec))
+                .collect::<Result<Vec<_>>>()?;
+            let monitor = ReportMonitor::new(devices).suppress_unchanged(suppress_unchanged);
+            run_monitor(monitor, !no_decode, count)
+        }
+        Command::Record {
+            device,
+            output,
+            count,
+        } => {
+            let device = open(&device)?;
+            match output {
+                Some(path) => record(device, File::create(path)?, count),
+                None => record(device, std::io::stdout().lock(), count),
+            }
+        }
+        Command::Replay { file, fast } => replay(&HidRecording::open(&file)?, !fast),
+        Command::Coldcard { device, command } => coldcard(device.as_deref(), command),
+    }
+}
+
+/// Open a device, explaining permission problems on failure
+fn open(spec: &str) -> Result<HidDevice> {
+    let info = select::find_device(spec)?;
+    HidDevice::open(&info).inspect_err(|e| {
+        if e.is_permission_denied() {
+            explain_permissions(&info);
+        }
+    })
+}
+
+fn explain_permissions(info: &DeviceInfo) {
+    if let Ok(report) = diagnose(&info.path) {
+        eprint!("{report}");
+    }
+    let rule = UdevRule::for_device(info);
+    eprintln!(
+        "\nSuggested rule for /etc/udev/rules.d/{file}:\n{rule}",
+        file = rule.file_name()
+    );
+}
+
+fn list(json: bool, filter: &DeviceFilter) -> Result<()> {
+    let mut devices = hidraw_rs::enumerate_with(filter)?;
+    devices.sort_by(|a, b| a.path.cmp(&b.path));
+
+    if json {
+        let text = serde_json::to_string_pretty(&devices)
+            .map_err(|e| Error::InvalidData(format!("JSON encoding failed: {e}")))?;
+        println!("{text}");
+        return Ok(());
+    }
+
+    println!(
+        "{path:<14} {ids:<9} {bus:<10} {usage:<9} {serial:<20} PRODUCT",
+        path = "PATH",
+        ids = "VID:PID",
+        bus = "BUS",
+        usage = "USAGE",
+        serial = "SERIAL"
+    );
+    for info in &devices {
+        println!(
+            "{path:<14} {vid:04x}:{pid:04x} {bus:<10} {page:04x}:{usage:04x} {serial:<20} {product}",
+            path = info.path.display(),
+            vid = info.vendor_id,
+            pid = info.product_id,
+            bus = format!("{bus:?}", bus = info.bus_type),
+            page = info.usage_page,
+            usage = info.usage,
+            serial = info.serial_number.as_deref().unwrap_or("-"),
+            product = info.product.as_deref().unwrap_or("-")
+        );
+    }
+    Ok(())
+}
+
+fn info(info: &DeviceInfo, json: bool) -> Result<()> {
+    let details = info.details().ok();
+
+    if json {
+        let value = serde_json::json!({ "device": info, "details": details });
+        let text = serde_json::to_string_pretty(&value)
+            .map_err(|e| Error::InvalidData(format!("JSON encoding failed: {e}")))?;
+        println!("{text}");
+        return Ok(());
+    }
+
+    println!("{name}", name = info.display_name());
+    println!("  Path: {path}", path = info.path.display());
+    println!("  Bus: {bus:?}", bus = info.bus_type);
+    println!("  Interface: {n}", n = info.interface_number);
+    println!(
+        "  Usage: {page:04x}:{usage:04x}",
+        page = info.usage_page,
+        usage = info.usage
+    );
+    for (label, value) in [
+        ("Manufacturer", &info.manufacturer),
+        ("Serial", &info.serial_number),
+        ("Physical path", &info.physical_path),
+    ] {
+        if let Some(value) = value {
+            println!("  {label}: {value}");
+        }
+    }
+    println!("  Stable ID: {id}", id = info.id());
+
+    if let Some(details) = details {
+        if let Some(driver) = &details.driver {
+            println!("  Driver: {driver}");
+        }
+        if let Some(usb) = &details.usb {
+            println!("  USB port: {port}", port = usb.port_path);
+            if let Some(speed) = &usb.speed {
+                println!("  Speed: {speed} Mbit/s");
+            }
+            let hubs: Vec<&str> = usb.hub_chain.iter().map(|hub| hub.name.as_str()).collect();
+            if !hubs.is_empty() {
+                println!("  Hubs: {chain}", chain = hubs.join(" <- "));
+            }
+        }
+    }
+
+    let report = diagnose(&info.path)?;
+    println!();
+    print!("{report}");
+    if !report.can_open() {
+        let rule = UdevRule::for_device(info);
+        println!(
+            "\nSuggested rule for /etc/udev/rules.d/{file}:\n{rule}",
+            file = rule.file_name()
+        );
+    }
+    Ok(())
+}
+
+fn descriptor(device: &HidDevice, parsed: bool) -> Result<()> {
+    let descriptor = device.get_report_descriptor()?;
+    if !parsed {
+        hex::dump(descriptor.as_bytes());
+        return Ok(());
+    }
+
+    let mut depth = 0usize;
+    for item in descriptor.items()? {
+        if item.item_type == ItemType::Main && item.tag == main_tag::END_COLLECTION {
+            depth = depth.saturating_sub(1);
+        }
+        println!(
+            "{raw:<15} {indent}{item}",
+            raw = hex::format(&item.to_bytes()),
+            indent = "  ".repeat(depth)
+        );
+        if item.item_type == ItemType::Main && item.tag == main_tag::COLLECTION {
+            depth += 1;
+        }
+    }
+    Ok(())
+}
+
+fn read(device: &mut HidDevice, timeout: Duration, count: usize) -> Result<()> {
+    let mut buf = vec![0u8; device.max_input_report_len().unwrap_or(64).max(1)];
+    for _ in 0..count {
+        let n = device.read_timeout(&mut buf, timeout)?;
+        println!("{hex}", hex = hex::format(&buf[..n]));
+    }
+    Ok(())
+}
+
+fn run_monitor(mut monitor: ReportMonitor, decode: bool, count: Option<usize>) -> Result<()> {
+    let names: Vec<String> = (0..monitor.active_devices())
+        .filter_map(|source| monitor.device(source))
+        .map(|device| device.info().path.display().to_string())
+        .collect();
+    eprintln!(
+        "Monitoring {devices}, press Ctrl-C to stop",
+        devices = names.join(", ")
+    );
+
+    let mut shown = 0;
+    while count.is_none_or(|count| shown < count) {
+        let report = match monitor.next() {
+            Some(Ok(report)) => report,
+            Some(Err(e)) => {
+                // Keep going with the remaining devices
+                eprintln!("error: {e}");
+                continue;
+            }
+            None => return Err(Error::Disconnected),
+        };
+        shown += 1;
+
+        let delta = report
+            .delta
+            .map(|delta| format!("+{secs:.6}", secs = delta.as_secs_f64()))
+            .unwrap_or_default();
+        let suppressed = match report.suppressed {
+            0 => String::new(),
+            n => format!(" ({n} unchanged hidden)"),
+        };
+        println!(
+            "{time:>12.6} {delta:>10} {name} report {id} ({len} bytes){suppressed}",
+            time = report.timestamp.as_secs_f64(),
+            name = names.get(report.source).map_or("?", String::as_str),
+            id = report.report_id,
+            len = report.data.len()
+        );
+        for line in report.hexdump().lines() {
+            println!("    {line}");
+        }
+        if decode && !report.fields.is_empty() {
+            let fields: Vec<String> = report.fields.iter().map(|f| f.to_string()).collect();
+            println!("    {fields}", fields = fields.join(" "));
+        }
+    }
+    Ok(())
+}
+
+fn record(device: HidDevice, sink: impl Write, count: Option<usize>) -> Result<()> {
+    let len = device.max_input_report_len().unwrap_or(64).max(1);
+    let mut recorder = device.with_hid_recorder(sink)?;
+    let mut buf = vec![0u8; len];
+    eprintln!("Recording, press Ctrl-C to stop");
+
+    let mut recorded = 0;
+    while count.is_none_or(|count| recorded < count) {
+        recorder.read(&mut buf)?;
+        recorded += 1;
+    }
+    Ok(())
+}
+
+fn replay(recording: &HidRecording, realtime: bool) -> Result<()> {
+    let mut devices = Vec::new();
+    for recorded in &recording.devices {
+        let uhid = UhidDevice::from_recording(recorded)?;
+        let info = uhid.find_hidraw(Duration::from_secs(2))?;
+        println!(
+            "{name} is {path}",
+            name = recorded.name,
+            path = info.path.display()
+        );
+        devices.push(uhid);
+    }
+
+    // hidraw drops input reports while nobody has the node open
+    for (uhid, recorded) in devices.iter_mut().zip(&recording.devices) {
+        eprintln!("Waiting for {name} to be opened", name = recorded.name);
+        loop {
+            match uhid.wait_open(Duration::from_secs(1)) {
+                Ok(()) => break,
+                Err(Error::Timeout) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // Interleave the events of all devices by timestamp
+    let mut events: Vec<(usize, &RecordedEvent)> = recording
+        .devices
+        .iter()
+        .enumerate()
+        .flat_map(|(index, recorded)| recorded.events.iter().map(move |event| (index, event)))
+        .collect();
+    events.sort_by_key(|(_, event)| event.timestamp);
+
+    loop {
+        eprintln!(
+            "Press Enter to replay {n} events, Ctrl-D to quit",
+            n = events.len()
+        );
+        if std::io::stdin().lines().next().is_none() {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        let first = events
+            .first()
+            .map(|(_, event)| event.timestamp)
+            .unwrap_or_default();
+        for (index, event) in &events {
+            if realtime {
+                let due = event.timestamp.saturating_sub(first);
+                std::thread::sleep(due.saturating_sub(start.elapsed()));
+            }
+            let uhid = &mut devices[*index];
+            uhid.answer_pending()?;
+            uhid.input(&event.data)?;
+        }
+    }
+}
+
+fn coldcard(spec: Option<&str>, command: ColdcardCommand) -> Result<()> {
+    let mut device = match spec {
+        Some(spec) => {
+            let info = select::find_device(spec)?;
+            ColdcardDevice::open_path(&info.path.to_string_lossy())?
+        }
+        None => ColdcardDevice::open()?,
+    };
+
+    match command {
+        ColdcardCommand::Ping { message } => {
+            let reply = device.ping(message.as_bytes())?;
+            println!("{reply}", reply = String::from_utf8_lossy(&reply));
+        }
+        ColdcardCommand::Version => println!("{version}", version = device.get_version()?),
+    }
+    Ok(())
+}
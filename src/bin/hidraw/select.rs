@@ -0,0 +1,90 @@
+//! Selecting a device from a command-line argument
+
+use hidraw_rs::{DeviceFilter, DeviceInfo, Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Resolve a device argument to a single device
+///
+/// Accepted forms:
+/// - a device node path (`/dev/hidraw3`) or name (`hidraw3`)
+/// - `VID:PID` in hex (`d13e:cc10`)
+/// - a filter string (`vid=d13e,serial=ABC*`, see [`DeviceFilter`])
+/// - anything else is taken as a serial number
+///
+/// When several devices match, the one with the lowest node path is used.
+pub fn find_device(spec: &str) -> Result<DeviceInfo> {
+    if spec.starts_with('/') || spec.starts_with("hidraw") {
+        let path = if spec.starts_with('/') {
+            PathBuf::from(spec)
+        } else {
+            Path::new("/dev").join(spec)
+        };
+        return hidraw_rs::hidraw::get_device_info(&path);
+    }
+
+    let filter = parse_spec(spec)?;
+    let mut devices = hidraw_rs::enumerate_with(&filter)?;
+    devices.sort_by_key(|info| node_number(&info.path));
+
+    if devices.len() > 1 {
+        eprintln!(
+            "note: {count} devices match '{spec}', using {path}",
+            count = devices.len(),
+            path = devices[0].path.display()
+        );
+    }
+    devices.into_iter().next().ok_or(Error::DeviceNotFound)
+}
+
+fn parse_spec(spec: &str) -> Result<DeviceFilter> {
+    if spec.contains('=') {
+        return spec.parse();
+    }
+    if let Some((vid, pid)) = spec.split_once(':')
+        && let (Ok(vid), Ok(pid)) = (u16::from_str_radix(vid, 16), u16::from_str_radix(pid, 16))
+    {
+        return Ok(DeviceFilter::new().vendor_id(vid).product_id(pid));
+    }
+    Ok(DeviceFilter::new().serial(spec))
+}
+
+/// Numeric suffix of a hidraw node, so hidraw10 sorts after hidraw2
+fn node_number(path: &Path) -> u32 {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.trim_start_matches("hidraw").parse().ok())
+        .unwrap_or(u32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec() -> Result<()> {
+        assert_eq!(
+            parse_spec("d13e:cc10")?,
+            DeviceFilter::new().vendor_id(0xd13e).product_id(0xcc10)
+        );
+        assert_eq!(parse_spec("vid=d13e")?, "vid=d13e".parse()?);
+        // Not hex, so a serial number containing a colon
+        assert_eq!(parse_spec("AB:XY")?, DeviceFilter::new().serial("AB:XY"));
+        assert_eq!(parse_spec("ABC123")?, DeviceFilter::new().serial("ABC123"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_node_number() -> Result<()> {
+        assert_eq!(node_number(Path::new("/dev/hidraw2")), 2);
+        assert_eq!(node_number(Path::new("/dev/hidraw10")), 10);
+        assert_eq!(node_number(Path::new("/dev/uhid")), u32::MAX);
+
+        let mut paths = ["/dev/hidraw10", "/dev/hidraw2", "/dev/hidraw0"].map(Path::new);
+        paths.sort_by_key(|path| node_number(path));
+        assert_eq!(
+            paths,
+            ["/dev/hidraw0", "/dev/hidraw2", "/dev/hidraw10"].map(Path::new)
+        );
+        Ok(())
+    }
+}
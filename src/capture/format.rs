@@ -0,0 +1,326 @@
+//! Compact binary capture format
+//!
+//! Layout (all integers little-endian):
+//!
+//! ```text
+//! header: "HIDCAP" | version u8 | vendor_id u16 | product_id u16
+//!         | descriptor_len u16 | descriptor bytes
+//! event:  kind u8 | timestamp_us u64 | report_id u8 | requested_len u32
+//!         | len u16 | data
+//! ```
+//!
+//! Timestamps are microseconds since the recording started, taken from a
+//! monotonic clock. `requested_len` is 0 when not recorded.
+
+use crate::{Error, Result};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+/// File magic
+const MAGIC: &[u8; 6] = b"HIDCAP";
+
+/// Current format version
+const VERSION: u8 = 1;
+
+/// Kind of a captured event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    /// Output report written to the device
+    Write,
+    /// Input report read from the device
+    Read,
+    /// A read timed out without data
+    Timeout,
+    /// Feature report fetched from the device
+    GetFeature,
+    /// Feature report sent to the device
+    SendFeature,
+    /// The device disconnected
+    Disconnected,
+    /// Any other error; the data holds the UTF-8 error message
+    Error,
+}
+
+impl EventKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            EventKind::Write => 1,
+            EventKind::Read => 2,
+            EventKind::Timeout => 3,
+            EventKind::GetFeature => 4,
+            EventKind::SendFeature => 5,
+            EventKind::Disconnected => 6,
+            EventKind::Error => 7,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self> {
+        Ok(match byte {
+            1 => EventKind::Write,
+            2 => EventKind::Read,
+            3 => EventKind::Timeout,
+            4 => EventKind::GetFeature,
+            5 => EventKind::SendFeature,
+            6 => EventKind::Disconnected,
+            7 => EventKind::Error,
+            other => {
+                return Err(Error::Parse(format!("Unknown capture event kind {other}")));
+            }
+        })
+    }
+}
+
+/// Capture file header describing the recorded device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CaptureHeader {
+    /// USB vendor ID
+    pub vendor_id: u16,
+    /// USB product ID
+    pub product_id: u16,
+    /// Raw report descriptor (empty if it could not be read)
+    pub descriptor: Vec<u8>,
+}
+
+/// A single captured event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureEvent {
+    /// Time since the start of the recording
+    pub timestamp: Duration,
+    /// Event kind
+    pub kind: EventKind,
+    /// Requested report ID (feature gets), otherwise 0
+    pub report_id: u8,
+    /// Report bytes as transferred, or the error message
+    pub data: Vec<u8>,
+    /// Bytes asked for: the write length or a feature get's buffer length
+    ///
+    /// `None` for events other than writes and feature gets.
+    pub requested_len: Option<usize>,
+}
+
+impl CaptureEvent {
+    /// Create an event
+    pub fn new(timestamp: Duration, kind: EventKind, report_id: u8, data: Vec<u8>) -> Self {
+        Self {
+            timestamp,
+            kind,
+            report_id,
+            data,
+            requested_len: None,
+        }
+    }
+
+    /// Set the buffer length the operation asked for
+    pub fn with_requested_len(mut self, len: usize) -> Self {
+        self.requested_len = Some(len);
+        self
+    }
+}
+
+/// Destination for captured events
+pub trait CaptureSink {
+    /// Record one event
+    fn write_event(&mut self, event: &CaptureEvent) -> Result<()>;
+}
+
+/// Writes capture files
+#[derive(Debug)]
+pub struct CaptureWriter<W: Write> {
+    sink: W,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    /// Create a writer and emit the header
+    pub fn new(mut sink: W, header: &CaptureHeader) -> Result<Self> {
+        let descriptor_len = u16::try_from(header.descriptor.len()).map_err(|_| {
+            Error::InvalidParameter(format!(
+                "Descriptor too large for capture: {len} bytes",
+                len = header.descriptor.len()
+            ))
+        })?;
+
+        sink.write_all(MAGIC)?;
+        sink.write_all(&[VERSION])?;
+        sink.write_all(&header.vendor_id.to_le_bytes())?;
+        sink.write_all(&header.product_id.to_le_bytes())?;
+        sink.write_all(&descriptor_len.to_le_bytes())?;
+        sink.write_all(&header.descriptor)?;
+        sink.flush()?;
+
+        Ok(Self { sink })
+    }
+
+    /// Consume the writer and return the sink
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+impl<W: Write> CaptureSink for CaptureWriter<W> {
+    /// Append an event and flush it to the sink
+    ///
+    /// Events are flushed one by one so a capture survives a crash of the
+    /// process being debugged.
+    fn write_event(&mut self, event: &CaptureEvent) -> Result<()> {
+        let len = u16::try_from(event.data.len()).map_err(|_| {
+            Error::InvalidParameter(format!(
+                "Event data too large for capture: {len} bytes",
+                len = event.data.len()
+            ))
+        })?;
+        let requested_len = u32::try_from(event.requested_len.unwrap_or(0)).map_err(|_| {
+            Error::InvalidParameter(format!(
+                "Requested length too large for capture: {requested:?} bytes",
+                requested = event.requested_len
+            ))
+        })?;
+        let timestamp_us = u64::try_from(event.timestamp.as_micros()).unwrap_or(u64::MAX);
+
+        self.sink.write_all(&[event.kind.to_byte()])?;
+        self.sink.write_all(&timestamp_us.to_le_bytes())?;
+        self.sink.write_all(&[event.report_id])?;
+        self.sink.write_all(&requested_len.to_le_bytes())?;
+        self.sink.write_all(&len.to_le_bytes())?;
+        self.sink.write_all(&event.data)?;
+        self.sink.flush()?;
+        Ok(())
+    }
+}
+
+/// Reads capture files
+#[derive(Debug)]
+pub struct CaptureReader<R: Read> {
+    source: R,
+    header: CaptureHeader,
+}
+
+impl<R: Read> CaptureReader<R> {
+    /// Create a reader and parse the header
+    pub fn new(mut source: R) -> Result<Self> {
+        let mut magic = [0u8; 6];
+        source.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::Parse("Not a hidraw-rs capture file".to_string()));
+        }
+
+        let version = read_u8(&mut source)?;
+        if version != VERSION {
+            return Err(Error::NotSupported(format!(
+                "Capture format version {version}"
+            )));
+        }
+
+        let vendor_id = read_u16(&mut source)?;
+        let product_id = read_u16(&mut source)?;
+        let descriptor_len = read_u16(&mut source)? as usize;
+        let mut descriptor = vec![0u8; descriptor_len];
+        source.read_exact(&mut descriptor)?;
+
+        Ok(Self {
+            source,
+            header: CaptureHeader {
+                vendor_id,
+                product_id,
+                descriptor,
+            },
+        })
+    }
+
+    /// Header of the capture
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Read the next event, or `None` at the end of the capture
+    pub fn next_event(&mut self) -> Result<Option<CaptureEvent>> {
+        let mut kind = [0u8; 1];
+        match self.source.read_exact(&mut kind) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        let kind = EventKind::from_byte(kind[0])?;
+        let mut timestamp = [0u8; 8];
+        self.source.read_exact(&mut timestamp)?;
+        let report_id = read_u8(&mut self.source)?;
+        let mut requested = [0u8; 4];
+        self.source.read_exact(&mut requested)?;
+        let requested_len = Some(u32::from_le_bytes(requested) as usize).filter(|&len| len > 0);
+        let len = read_u16(&mut self.source)? as usize;
+        let mut data = vec![0u8; len];
+        self.source.read_exact(&mut data)?;
+
+        Ok(Some(CaptureEvent {
+            timestamp: Duration::from_micros(u64::from_le_bytes(timestamp)),
+            kind,
+            report_id,
+            data,
+            requested_len,
+        }))
+    }
+
+    /// Read all remaining events
+    pub fn read_all(&mut self) -> Result<Vec<CaptureEvent>> {
+        let mut events = Vec::new();
+        while let Some(event) = self.next_event()? {
+            events.push(event);
+        }
+        Ok(events)
+    }
+}
+
+fn read_u8<R: Read>(source: &mut R) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    source.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_u16<R: Read>(source: &mut R) -> Result<u16> {
+    let mut buf = [0u8; 2];
+    source.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_round_trip() -> Result<()> {
+        let header = CaptureHeader {
+            vendor_id: 0xd13e,
+            product_id: 0xcc10,
+            descriptor: vec![0x06, 0x00, 0xFF],
+        };
+        let events = vec![
+            CaptureEvent::new(Duration::from_micros(10), EventKind::Write, 0, vec![1, 2])
+                .with_requested_len(4),
+            CaptureEvent::new(Duration::from_micros(250), EventKind::Timeout, 0, vec![]),
+            CaptureEvent::new(
+                Duration::from_millis(3),
+                EventKind::GetFeature,
+                5,
+                vec![5, 9],
+            ),
+        ];
+
+        let mut writer = CaptureWriter::new(Vec::new(), &header)?;
+        for event in &events {
+            writer.write_event(event)?;
+        }
+        let bytes = writer.into_inner();
+
+        let mut reader = CaptureReader::new(bytes.as_slice())?;
+        assert_eq!(reader.header(), &header);
+        assert_eq!(reader.read_all()?, events);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bad_magic() -> Result<()> {
+        assert!(CaptureReader::new(&b"NOTCAP\x01"[..]).is_err());
+        Ok(())
+    }
+}
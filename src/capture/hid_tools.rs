@@ -0,0 +1,408 @@
+//! hid-tools text recordings (`hid-recorder` / `hid-replay`)
+//!
+//! The format used by hid-tools, libinput and kernel bug reports:
+//!
+//! ```text
+//! # Comment lines start with '#'
+//! D: 0                                  select device 0 (multi-device files)
+//! R: 24 05 01 09 02 a1 01 ...           descriptor length (decimal), bytes
+//! N: Logitech USB Receiver              device name
+//! P: usb-0000:00:14.0-4/input0          physical path
+//! I: 3 046d c52b                        bus, vendor and product ID (hex)
+//! E: 000001.250000 4 01 00 ff 02        input report: seconds.micros, length, bytes
+//! ```
+//!
+//! [`HidToolsWriter`] records a live device in this format; [`HidRecording`]
+//! parses it back, and [`UhidDevice`](crate::uhid::UhidDevice) can replay a
+//! [`RecordedDevice`] as a virtual HID device.
+
+use super::format::{CaptureEvent, CaptureSink, EventKind};
+use crate::descriptor::parse_items;
+use crate::{BusType, Error, HidDevice, Result};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// One device from an hid-tools recording
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RecordedDevice {
+    /// Device name (`N:`)
+    pub name: String,
+    /// Physical path (`P:`)
+    pub phys: String,
+    /// Bus type (`I:`)
+    pub bus: BusType,
+    /// Vendor ID (`I:`)
+    pub vendor_id: u16,
+    /// Product ID (`I:`)
+    pub product_id: u16,
+    /// Raw report descriptor (`R:`)
+    pub descriptor: Vec<u8>,
+    /// Recorded input reports (`E:`)
+    pub events: Vec<RecordedEvent>,
+}
+
+/// One input report from an hid-tools recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    /// Time since the start of the recording
+    pub timestamp: Duration,
+    /// Report bytes, including any report ID prefix
+    pub data: Vec<u8>,
+}
+
+impl RecordedDevice {
+    /// Describe a live device, without any events
+    ///
+    /// Name and physical path come from the kernel when available, falling
+    /// back to the enumerated device information.
+    pub fn for_device(device: &HidDevice) -> Result<Self> {
+        let info = device.info();
+        Ok(Self {
+            name: device.get_name().unwrap_or_else(|_| info.display_name()),
+            phys: device
+                .get_physical_info()
+                .ok()
+                .or_else(|| info.physical_path.clone())
+                .unwrap_or_default(),
+            bus: info.bus_type,
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            descriptor: device.get_report_descriptor()?.as_bytes().to_vec(),
+            events: Vec::new(),
+        })
+    }
+
+    /// Write the device header and all its events
+    pub fn write_to<W: Write>(&self, sink: W) -> Result<W> {
+        let mut writer = HidToolsWriter::new(sink, self)?;
+        for event in &self.events {
+            writer.write_input(event.timestamp, &event.data)?;
+        }
+        Ok(writer.into_inner())
+    }
+}
+
+/// A parsed hid-tools recording, possibly holding several devices
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HidRecording {
+    /// Devices in the order of their `D:` index
+    pub devices: Vec<RecordedDevice>,
+}
+
+impl HidRecording {
+    /// Load a recording file
+    pub fn open(path: &Path) -> Result<Self> {
+        fs::read_to_string(path)?.parse()
+    }
+
+    /// Parse recording text
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut devices: Vec<RecordedDevice> = Vec::new();
+        let mut current = 0usize;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error =
+                |message: &str| Error::Parse(format!("line {n}: {message}", n = number + 1));
+            let (tag, value) = line
+                .split_once(':')
+                .ok_or_else(|| error("expected '<tag>: <value>'"))?;
+            let value = value.trim();
+
+            if tag == "D" {
+                current = value.parse().map_err(|_| error("invalid device index"))?;
+                // Indexes count up from 0, so a new one adds just the next device
+                if current > devices.len() {
+                    return Err(error("device index out of sequence"));
+                }
+                if current == devices.len() {
+                    devices.push(RecordedDevice::default());
+                }
+                continue;
+            }
+            // Single-device recordings may omit 'D: 0'
+            if devices.is_empty() {
+                devices.push(RecordedDevice::default());
+            }
+            let device = &mut devices[current];
+
+            match tag {
+                "N" => device.name = value.to_string(),
+                "P" => device.phys = value.to_string(),
+                "R" => device.descriptor = parse_sized_bytes(value).map_err(|e| error(&e))?,
+                "I" => {
+                    let ids: Vec<u16> = value
+                        .split_whitespace()
+                        .map(|id| u16::from_str_radix(id, 16))
+                        .collect::<std::result::Result<_, _>>()
+                        .map_err(|_| error("invalid or out-of-range hex in 'I:'"))?;
+                    let [bus, vid, pid] = ids[..] else {
+                        return Err(error("'I:' needs bus, vendor and product"));
+                    };
+                    device.bus = BusType::from_raw(bus);
+                    device.vendor_id = vid;
+                    device.product_id = pid;
+                }
+                "E" => {
+                    let (time, rest) = value
+                        .split_once(char::is_whitespace)
+                        .ok_or_else(|| error("'E:' needs a timestamp and data"))?;
+                    device.events.push(RecordedEvent {
+                        timestamp: parse_timestamp(time)
+                            .ok_or_else(|| error("invalid timestamp"))?,
+                        data: parse_sized_bytes(rest).map_err(|e| error(&e))?,
+                    });
+                }
+                // Other tags (e.g. the kernel version line) are informational
+                _ => {}
+            }
+        }
+
+        Ok(Self { devices })
+    }
+}
+
+impl FromStr for HidRecording {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self> {
+        Self::parse(text)
+    }
+}
+
+/// Parse `<decimal length> <hex bytes...>`, checking the length
+fn parse_sized_bytes(text: &str) -> std::result::Result<Vec<u8>, String> {
+    let mut parts = text.split_whitespace();
+    let len: usize = parts
+        .next()
+        .and_then(|len| len.parse().ok())
+        .ok_or("missing length")?;
+    let bytes = parts
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<std::result::Result<Vec<u8>, _>>()
+        .map_err(|_| "invalid hex byte")?;
+
+    if bytes.len() != len {
+        return Err(format!(
+            "length {len} does not match {count} bytes",
+            count = bytes.len()
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Parse `seconds.micros`
+fn parse_timestamp(text: &str) -> Option<Duration> {
+    let (secs, micros) = text.split_once('.')?;
+    let secs: u64 = secs.parse().ok()?;
+    // Normalize the fraction to microseconds regardless of its width
+    let digits = format!("{micros:0<6}");
+    let micros: u64 = digits.get(..6)?.parse().ok()?;
+    Some(Duration::from_secs(secs) + Duration::from_micros(micros))
+}
+
+fn hex_bytes(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Writes recordings in the hid-tools text format
+///
+/// As a [`CaptureSink`] only input reports are written, since the format
+/// has no representation for writes, feature reports or errors.
+#[derive(Debug)]
+pub struct HidToolsWriter<W: Write> {
+    sink: W,
+}
+
+impl<W: Write> HidToolsWriter<W> {
+    /// Create a writer and emit the device header
+    ///
+    /// The descriptor is also written as comments, one item per line, the
+    /// way `hid-recorder` does. Events in `device` are not written.
+    pub fn new(mut sink: W, device: &RecordedDevice) -> Result<Self> {
+        writeln!(sink, "# {name}", name = device.name)?;
+        if let Ok(items) = parse_items(&device.descriptor) {
+            for item in items {
+                let bytes: Vec<String> = item
+                    .to_bytes()
+                    .iter()
+                    .map(|b| format!("0x{b:02x},"))
+                    .collect();
+                writeln!(sink, "# {bytes:<30} // {item}", bytes = bytes.join(" "))?;
+            }
+        }
+        writeln!(
+            sink,
+            "R: {len} {bytes}",
+            len = device.descriptor.len(),
+            bytes = hex_bytes(&device.descriptor)
+        )?;
+        writeln!(sink, "N: {name}", name = device.name)?;
+        writeln!(sink, "P: {phys}", phys = device.phys)?;
+        writeln!(
+            sink,
+            "I: {bus:x} {vid:04x} {pid:04x}",
+            bus = device.bus.raw(),
+            vid = device.vendor_id,
+            pid = device.product_id
+        )?;
+        sink.flush()?;
+
+        Ok(Self { sink })
+    }
+
+    /// Append one input report
+    pub fn write_input(&mut self, timestamp: Duration, data: &[u8]) -> Result<()> {
+        writeln!(
+            self.sink,
+            "E: {secs:06}.{micros:06} {len} {bytes}",
+            secs = timestamp.as_secs(),
+            micros = timestamp.subsec_micros(),
+            len = data.len(),
+            bytes = hex_bytes(data)
+        )?;
+        self.sink.flush()?;
+        Ok(())
+    }
+
+    /// Consume the writer and return the sink
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+impl<W: Write> CaptureSink for HidToolsWriter<W> {
+    fn write_event(&mut self, event: &CaptureEvent) -> Result<()> {
+        match event.kind {
+            EventKind::Read => self.write_input(event.timestamp, &event.data),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_device() -> RecordedDevice {
+        RecordedDevice {
+            name: "Test Mouse".to_string(),
+            phys: "usb-0000:00:14.0-4/input0".to_string(),
+            bus: BusType::Usb,
+            vendor_id: 0x046d,
+            product_id: 0xc52b,
+            descriptor: vec![0x05, 0x01, 0x09, 0x02, 0xa1, 0x01, 0xc0],
+            events: vec![
+                RecordedEvent {
+                    timestamp: Duration::ZERO,
+                    data: vec![0x01, 0x00, 0xff],
+                },
+                RecordedEvent {
+                    timestamp: Duration::from_micros(1_250_017),
+                    data: vec![0x01, 0x02, 0x03],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let device = sample_device();
+        let text = String::from_utf8_lossy(&device.write_to(Vec::new())?).into_owned();
+
+        assert!(text.contains("R: 7 05 01 09 02 a1 01 c0\n"));
+        assert!(text.contains("I: 3 046d c52b\n"));
+        assert!(text.contains("E: 000001.250017 3 01 02 03\n"));
+        assert!(text.contains("// Usage Page (0x0001)"));
+
+        let recording = HidRecording::parse(&text)?;
+        assert_eq!(recording.devices, [device]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_multi_device() -> Result<()> {
+        let text = "\
+# hid-recorder output
+D: 0
+R: 2 05 01
+N: First
+I: 5 1 2
+D: 1
+R: 1 c0
+N: Second
+I: 18 04f3 0001
+D: 0
+E: 000000.500000 2 aa bb
+D: 1
+E: 000001.5 1 cc
+";
+        let recording: HidRecording = text.parse()?;
+
+        assert_eq!(recording.devices.len(), 2);
+        assert_eq!(recording.devices[0].bus, BusType::Bluetooth);
+        assert_eq!(recording.devices[0].vendor_id, 1);
+        assert_eq!(
+            recording.devices[0].events,
+            [RecordedEvent {
+                timestamp: Duration::from_millis(500),
+                data: vec![0xaa, 0xbb],
+            }]
+        );
+        assert_eq!(recording.devices[1].bus, BusType::I2c);
+        assert_eq!(
+            recording.devices[1].events[0].timestamp,
+            Duration::from_millis(1500)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() -> Result<()> {
+        // Length does not match the bytes
+        assert!(HidRecording::parse("R: 3 05 01").is_err());
+        assert!(HidRecording::parse("E: 000000.000000 2 aa").is_err());
+        assert!(HidRecording::parse("I: 3 046d").is_err());
+        assert!(HidRecording::parse("I: 3 1046d c52b").is_err());
+        assert!(HidRecording::parse("garbage").is_err());
+        // Device indexes that would overflow or allocate without bound
+        assert!(HidRecording::parse("D: 18446744073709551615\nN: x").is_err());
+        assert!(HidRecording::parse("D: 4000000000\nN: x").is_err());
+        assert!(HidRecording::parse("D: 0\nD: 2\nN: x").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sink_skips_non_input_events() -> Result<()> {
+        let mut writer = HidToolsWriter::new(Vec::new(), &RecordedDevice::default())?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::ZERO,
+            EventKind::Write,
+            0,
+            vec![1],
+        ))?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::ZERO,
+            EventKind::Read,
+            0,
+            vec![2],
+        ))?;
+
+        let text = String::from_utf8_lossy(&writer.into_inner()).into_owned();
+        assert_eq!(text.matches("E: ").count(), 1);
+        assert!(text.contains("E: 000000.000000 1 02"));
+
+        Ok(())
+    }
+}
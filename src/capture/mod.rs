@@ -0,0 +1,24 @@
+//! Capture and replay of HID traffic
+//!
+//! [`RecordingDevice`] logs every operation on a [`HidDevice`](crate::HidDevice)
+//! with monotonic timestamps to a [`CaptureSink`]: either the compact native
+//! format ([`CaptureWriter`]) or Wireshark-readable pcapng ([`PcapngWriter`]).
+//! [`ReplayDevice`] plays a native capture back as a
+//! [`Transport`](crate::Transport) for bug reports and tests.
+//! Recordings in the hid-tools text format are written by [`HidToolsWriter`]
+//! and parsed by [`HidRecording`].
+
+mod format;
+mod hid_tools;
+mod pcapng;
+mod recorder;
+mod replay;
+
+pub use format::{
+    CaptureEvent, CaptureHeader, CaptureReader, CaptureSink, CaptureWriter, EventKind,
+};
+pub use hid_tools::{HidRecording, HidToolsWriter, RecordedDevice, RecordedEvent};
+pub use pcapng::{LINKTYPE_USB_LINUX_MMAPPED, PcapngConfig, PcapngWriter};
+pub use recorder::RecordingDevice;
+pub(crate) use recorder::capture_header;
+pub use replay::ReplayDevice;
@@ -0,0 +1,526 @@
+//! pcapng export of HID transactions
+//!
+//! Writes captures readable by Wireshark's USB HID dissector without root
+//! access to `usbmon`. Each captured operation is synthesized into a usbmon
+//! submit/complete pair using the `LINKTYPE_USB_LINUX_MMAPPED` (220)
+//! pseudo-header:
+//!
+//! - writes become interrupt OUT transfers
+//! - reads become interrupt IN transfers
+//! - feature sets and gets become control SET_REPORT / GET_REPORT transfers
+//!
+//! Timeouts and errors produce no USB traffic and are not written.
+
+use super::format::{CaptureEvent, CaptureSink, EventKind};
+use crate::hidraw::DeviceDetails;
+use crate::{DeviceInfo, Error, Result};
+use std::io::Write;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Link type for usbmon packets with the 64-byte mmapped pseudo-header
+pub const LINKTYPE_USB_LINUX_MMAPPED: u16 = 220;
+
+/// Section Header Block type
+const BLOCK_SHB: u32 = 0x0A0D_0D0A;
+/// Interface Description Block type
+const BLOCK_IDB: u32 = 0x0000_0001;
+/// Enhanced Packet Block type
+const BLOCK_EPB: u32 = 0x0000_0006;
+/// Byte-order magic
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+/// `if_name` option code
+const OPT_IF_NAME: u16 = 2;
+
+/// URB status of a submission that hasn't completed (`-EINPROGRESS`)
+const STATUS_IN_PROGRESS: i32 = -115;
+
+/// usbmon transfer types
+const XFER_INTERRUPT: u8 = 1;
+const XFER_CONTROL: u8 = 2;
+
+/// HID class requests
+const HID_GET_REPORT: u8 = 0x01;
+const HID_SET_REPORT: u8 = 0x09;
+/// HID report type for feature reports in `wValue`
+const HID_REPORT_TYPE_FEATURE: u16 = 3;
+
+/// USB addressing used for the synthesized packets
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PcapngConfig {
+    /// USB bus number
+    pub bus: u16,
+    /// USB device address
+    pub device: u8,
+    /// HID interface number (used as `wIndex` for control requests)
+    pub interface: u16,
+    /// Interrupt IN endpoint address
+    pub in_endpoint: u8,
+    /// Interrupt OUT endpoint address
+    pub out_endpoint: u8,
+    /// Interface name shown by Wireshark
+    pub name: String,
+}
+
+impl Default for PcapngConfig {
+    fn default() -> Self {
+        Self {
+            bus: 1,
+            device: 1,
+            interface: 0,
+            in_endpoint: 0x81,
+            out_endpoint: 0x01,
+            name: "hidraw".to_string(),
+        }
+    }
+}
+
+impl PcapngConfig {
+    /// Configuration named after a device, with its USB bus and address
+    ///
+    /// The bus and device numbers are read from sysfs so the capture lines
+    /// up with a `usbmon` trace of the same device. Devices on other buses,
+    /// or whose sysfs entries can't be read, keep the defaults.
+    pub fn for_device(info: &DeviceInfo) -> Self {
+        match crate::hidraw::get_device_details(&info.path) {
+            Ok(details) => Self::with_details(info, &details),
+            Err(_e) => {
+                #[cfg(feature = "logging")]
+                tracing::debug!(
+                    "No USB bus and device numbers for {path}: {_e}",
+                    path = info.path.display()
+                );
+                Self::with_details(info, &DeviceDetails::default())
+            }
+        }
+    }
+
+    fn with_details(info: &DeviceInfo, details: &DeviceDetails) -> Self {
+        let defaults = Self::default();
+        let usb = details.usb.as_ref();
+        Self {
+            bus: usb
+                .and_then(|usb| usb.bus_number)
+                .map_or(defaults.bus, u16::from),
+            device: usb
+                .and_then(|usb| usb.device_number)
+                .unwrap_or(defaults.device),
+            interface: u16::try_from(info.interface_number).unwrap_or(0),
+            name: info.path.display().to_string(),
+            ..defaults
+        }
+    }
+}
+
+/// One usbmon record before serialization
+struct UsbmonPacket<'a> {
+    urb_id: u64,
+    event_type: u8,
+    xfer_type: u8,
+    endpoint: u8,
+    setup: Option<[u8; 8]>,
+    status: i32,
+    length: u32,
+    data: &'a [u8],
+}
+
+/// Writes captured events as pcapng
+#[derive(Debug)]
+pub struct PcapngWriter<W: Write> {
+    sink: W,
+    config: PcapngConfig,
+    start: SystemTime,
+    next_urb_id: u64,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Create a writer and emit the section and interface headers
+    ///
+    /// Event timestamps are taken relative to the wall-clock time of this call.
+    pub fn new(mut sink: W, config: PcapngConfig) -> Result<Self> {
+        write_block(&mut sink, BLOCK_SHB, |body| {
+            body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+            body.extend_from_slice(&1u16.to_le_bytes()); // major version
+            body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+            body.extend_from_slice(&(-1i64).to_le_bytes()); // unknown section length
+        })?;
+        write_block(&mut sink, BLOCK_IDB, |body| {
+            body.extend_from_slice(&LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+            body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+            body.extend_from_slice(&0u32.to_le_bytes()); // no snap length limit
+            push_option(body, OPT_IF_NAME, config.name.as_bytes());
+            push_option(body, 0, &[]); // opt_endofopt
+        })?;
+        sink.flush()?;
+
+        Ok(Self {
+            sink,
+            config,
+            start: SystemTime::now(),
+            next_urb_id: 1,
+        })
+    }
+
+    /// Consume the writer and return the sink
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+
+    fn write_packet(&mut self, timestamp: Duration, packet: &UsbmonPacket<'_>) -> Result<()> {
+        let time = self
+            .start
+            .checked_add(timestamp)
+            .unwrap_or(self.start)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let ts_us = u64::try_from(time.as_micros()).unwrap_or(u64::MAX);
+        let record = self.usbmon_record(time, packet);
+
+        write_block(&mut self.sink, BLOCK_EPB, |body| {
+            body.extend_from_slice(&0u32.to_le_bytes()); // interface ID
+            body.extend_from_slice(&((ts_us >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(ts_us as u32).to_le_bytes());
+            body.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            body.extend_from_slice(&(record.len() as u32).to_le_bytes());
+            body.extend_from_slice(&record);
+            pad_to_4(body);
+        })
+    }
+
+    /// Serialize the 64-byte mmapped usbmon header followed by the data
+    fn usbmon_record(&self, time: Duration, packet: &UsbmonPacket<'_>) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64 + packet.data.len());
+        out.extend_from_slice(&packet.urb_id.to_le_bytes());
+        out.push(packet.event_type);
+        out.push(packet.xfer_type);
+        out.push(packet.endpoint);
+        out.push(self.config.device);
+        out.extend_from_slice(&self.config.bus.to_le_bytes());
+        // flag_setup: 0 when a setup packet is present, '-' otherwise
+        out.push(if packet.setup.is_some() { 0 } else { b'-' });
+        // flag_data: 0 when data is present, '<' (IN) or '>' (OUT) otherwise
+        out.push(
+            match (packet.data.is_empty(), packet.endpoint & 0x80 != 0) {
+                (false, _) => 0,
+                (true, true) => b'<',
+                (true, false) => b'>',
+            },
+        );
+        out.extend_from_slice(&(time.as_secs() as i64).to_le_bytes());
+        out.extend_from_slice(&(time.subsec_micros() as i32).to_le_bytes());
+        out.extend_from_slice(&packet.status.to_le_bytes());
+        out.extend_from_slice(&packet.length.to_le_bytes());
+        out.extend_from_slice(&(packet.data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&packet.setup.unwrap_or_default());
+        out.extend_from_slice(&0i32.to_le_bytes()); // interval
+        out.extend_from_slice(&0i32.to_le_bytes()); // start_frame
+        out.extend_from_slice(&0u32.to_le_bytes()); // xfer_flags
+        out.extend_from_slice(&0u32.to_le_bytes()); // ndesc
+        out.extend_from_slice(packet.data);
+        out
+    }
+
+    /// Write a submit/complete pair for one transfer
+    ///
+    /// The submission carries the `requested` length and the completion the
+    /// length actually transferred.
+    fn write_transfer(
+        &mut self,
+        timestamp: Duration,
+        xfer_type: u8,
+        endpoint: u8,
+        setup: Option<[u8; 8]>,
+        requested: usize,
+        data: &[u8],
+    ) -> Result<()> {
+        let urb_id = self.next_urb_id;
+        self.next_urb_id += 1;
+        let is_in = endpoint & 0x80 != 0;
+
+        let submit = UsbmonPacket {
+            urb_id,
+            event_type: b'S',
+            xfer_type,
+            endpoint,
+            setup,
+            status: STATUS_IN_PROGRESS,
+            length: requested as u32,
+            data: if is_in { &[] } else { data },
+        };
+        let complete = UsbmonPacket {
+            urb_id,
+            event_type: b'C',
+            xfer_type,
+            endpoint,
+            setup: None,
+            status: 0,
+            length: data.len() as u32,
+            data: if is_in { data } else { &[] },
+        };
+
+        self.write_packet(timestamp, &submit)?;
+        self.write_packet(timestamp, &complete)?;
+        self.sink.flush()?;
+        Ok(())
+    }
+
+    fn hid_setup(&self, request_type: u8, request: u8, report_id: u8, length: usize) -> [u8; 8] {
+        let value = (HID_REPORT_TYPE_FEATURE << 8) | report_id as u16;
+        let mut setup = [0u8; 8];
+        setup[0] = request_type;
+        setup[1] = request;
+        setup[2..4].copy_from_slice(&value.to_le_bytes());
+        setup[4..6].copy_from_slice(&self.config.interface.to_le_bytes());
+        setup[6..8].copy_from_slice(&(length as u16).to_le_bytes());
+        setup
+    }
+}
+
+impl<W: Write> CaptureSink for PcapngWriter<W> {
+    fn write_event(&mut self, event: &CaptureEvent) -> Result<()> {
+        match event.kind {
+            EventKind::Write => {
+                // hidraw always takes a report ID byte; ID 0 is not sent on the wire
+                let data = strip_unnumbered(&event.data);
+                self.write_transfer(
+                    event.timestamp,
+                    XFER_INTERRUPT,
+                    self.config.out_endpoint,
+                    None,
+                    data.len(),
+                    data,
+                )
+            }
+            EventKind::Read => self.write_transfer(
+                event.timestamp,
+                XFER_INTERRUPT,
+                self.config.in_endpoint,
+                None,
+                event.data.len(),
+                &event.data,
+            ),
+            EventKind::SendFeature => {
+                let report_id = event.data.first().copied().ok_or_else(|| {
+                    Error::InvalidData("Feature report without report ID".to_string())
+                })?;
+                let data = strip_unnumbered(&event.data);
+                let setup = self.hid_setup(0x21, HID_SET_REPORT, report_id, data.len());
+                self.write_transfer(
+                    event.timestamp,
+                    XFER_CONTROL,
+                    0x00,
+                    Some(setup),
+                    data.len(),
+                    data,
+                )
+            }
+            EventKind::GetFeature => {
+                // As with the data, an unnumbered report's ID byte isn't
+                // part of wLength
+                let id_len = usize::from(event.report_id == 0);
+                let data = event.data.get(id_len..).unwrap_or_default();
+                let requested = event
+                    .requested_len
+                    .map_or(data.len(), |len| len.saturating_sub(id_len));
+                let setup = self.hid_setup(0xA1, HID_GET_REPORT, event.report_id, requested);
+                self.write_transfer(
+                    event.timestamp,
+                    XFER_CONTROL,
+                    0x80,
+                    Some(setup),
+                    requested,
+                    data,
+                )
+            }
+            EventKind::Timeout | EventKind::Disconnected | EventKind::Error => Ok(()),
+        }
+    }
+}
+
+/// Drop the leading report ID byte when it is 0 (unnumbered reports)
+fn strip_unnumbered(data: &[u8]) -> &[u8] {
+    match data.split_first() {
+        Some((0, rest)) => rest,
+        _ => data,
+    }
+}
+
+/// Pad a block body to a 32-bit boundary
+fn pad_to_4(body: &mut Vec<u8>) {
+    while !body.len().is_multiple_of(4) {
+        body.push(0);
+    }
+}
+
+/// Append a pcapng option (code, length, padded value)
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad_to_4(body);
+}
+
+/// Write a block with its type and leading/trailing total length
+fn write_block<W: Write>(
+    sink: &mut W,
+    block_type: u32,
+    fill: impl FnOnce(&mut Vec<u8>),
+) -> Result<()> {
+    let mut body = Vec::new();
+    fill(&mut body);
+    let total_len = (body.len() + 12) as u32;
+
+    sink.write_all(&block_type.to_le_bytes())?;
+    sink.write_all(&total_len.to_le_bytes())?;
+    sink.write_all(&body)?;
+    sink.write_all(&total_len.to_le_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes([
+            bytes[offset],
+            bytes[offset + 1],
+            bytes[offset + 2],
+            bytes[offset + 3],
+        ])
+    }
+
+    /// Split a pcapng stream into (block type, block body) pairs
+    fn blocks(bytes: &[u8]) -> Vec<(u32, &[u8])> {
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            let block_type = u32_at(bytes, offset);
+            let len = u32_at(bytes, offset + 4) as usize;
+            assert_eq!(u32_at(bytes, offset + len - 4) as usize, len);
+            blocks.push((block_type, &bytes[offset + 8..offset + len - 4]));
+            offset += len;
+        }
+        blocks
+    }
+
+    #[test]
+    fn test_interrupt_out_and_in() -> Result<()> {
+        let mut writer = PcapngWriter::new(Vec::new(), PcapngConfig::default())?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::ZERO,
+            EventKind::Write,
+            0,
+            vec![0x00, 0xAA, 0xBB],
+        ))?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::from_millis(1),
+            EventKind::Read,
+            0,
+            vec![0xCC],
+        ))?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::from_millis(2),
+            EventKind::Timeout,
+            0,
+            vec![],
+        ))?;
+        let bytes = writer.into_inner();
+        let blocks = blocks(&bytes);
+
+        assert_eq!(blocks.len(), 6);
+        assert_eq!(blocks[0].0, BLOCK_SHB);
+        assert_eq!(u32_at(blocks[0].1, 0), BYTE_ORDER_MAGIC);
+        assert_eq!(blocks[1].0, BLOCK_IDB);
+        assert_eq!(&blocks[1].1[..2], &LINKTYPE_USB_LINUX_MMAPPED.to_le_bytes());
+
+        // OUT submit carries the payload without the report ID 0 prefix
+        let out_submit = &blocks[2].1[20..];
+        assert_eq!(out_submit[8], b'S');
+        assert_eq!(out_submit[9], XFER_INTERRUPT);
+        assert_eq!(out_submit[10], 0x01);
+        assert_eq!(u32_at(out_submit, 28) as i32, STATUS_IN_PROGRESS);
+        assert_eq!(u32_at(out_submit, 32), 2); // urb length
+        assert_eq!(&out_submit[64..66], &[0xAA, 0xBB]);
+
+        // IN completion carries the data, sharing the URB ID with its submit
+        let in_submit = &blocks[4].1[20..];
+        let in_complete = &blocks[5].1[20..];
+        assert_eq!(in_submit[15], b'<');
+        assert_eq!(in_complete[8], b'C');
+        assert_eq!(in_complete[10], 0x81);
+        assert_eq!(u32_at(in_complete, 28), 0);
+        assert_eq!(&in_submit[..8], &in_complete[..8]);
+        assert_eq!(in_complete[64], 0xCC);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_feature_control_transfers() -> Result<()> {
+        let config = PcapngConfig {
+            interface: 2,
+            ..PcapngConfig::default()
+        };
+        let mut writer = PcapngWriter::new(Vec::new(), config)?;
+        writer.write_event(&CaptureEvent::new(
+            Duration::ZERO,
+            EventKind::SendFeature,
+            0,
+            vec![0x05, 0x01],
+        ))?;
+        writer.write_event(
+            &CaptureEvent::new(
+                Duration::ZERO,
+                EventKind::GetFeature,
+                0,
+                vec![0x00, 0x10, 0x20],
+            )
+            .with_requested_len(65),
+        )?;
+        let bytes = writer.into_inner();
+        let blocks = blocks(&bytes);
+
+        let set_submit = &blocks[2].1[20..];
+        assert_eq!(set_submit[9], XFER_CONTROL);
+        assert_eq!(set_submit[14], 0); // setup present
+        assert_eq!(
+            &set_submit[40..48],
+            &[0x21, HID_SET_REPORT, 0x05, 0x03, 0x02, 0x00, 0x02, 0x00]
+        );
+
+        // wLength and the URB length are what was asked for, not returned
+        let get_submit = &blocks[4].1[20..];
+        assert_eq!(&get_submit[46..48], &64u16.to_le_bytes());
+        assert_eq!(u32_at(get_submit, 32), 64);
+        let get_complete = &blocks[5].1[20..];
+        assert_eq!(get_complete[10], 0x80);
+        assert_eq!(u32_at(get_complete, 32), 2);
+        assert_eq!(&get_complete[64..66], &[0x10, 0x20]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_uses_sysfs_usb_address() -> Result<()> {
+        use crate::hidraw::{FixtureDevice, SysfsFixture};
+
+        let mut fixture = SysfsFixture::new()?;
+        let usb = fixture.add(&FixtureDevice::usb("hidraw0", 0xd13e, 0xcc10).port("3-2"))?;
+        let i2c = fixture.add(&FixtureDevice::i2c("hidraw1", 0x04f3, 0x3140))?;
+        let enumerator = fixture.enumerator();
+
+        let config = PcapngConfig::with_details(
+            &enumerator.device_info(&usb)?,
+            &enumerator.device_details(&usb)?,
+        );
+        assert_eq!((config.bus, config.device), (3, 11));
+
+        let config = PcapngConfig::with_details(
+            &enumerator.device_info(&i2c)?,
+            &enumerator.device_details(&i2c)?,
+        );
+        assert_eq!((config.bus, config.device), (1, 1));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+//! Recording wrapper around a live device
+
+use super::format::{CaptureEvent, CaptureHeader, CaptureSink, EventKind};
+use crate::transport::Transport;
+use crate::{DeviceInfo, Error, HidDevice, Result};
+use std::time::{Duration, Instant};
+
+/// A [`HidDevice`] that logs every operation to a [`CaptureSink`]
+///
+/// Each operation produces exactly one event: the transferred bytes on
+/// success, or a `Timeout`, `Disconnected` or `Error` event on failure.
+/// Create one with [`HidDevice::with_recorder`] or [`HidDevice::with_pcapng`].
+///
+/// Operations always return the device's own result. If the sink fails,
+/// recording stops and the error is kept for
+/// [`take_sink_error`](Self::take_sink_error), so a write the device took
+/// or a report already read is never reported as failed.
+pub struct RecordingDevice<S: CaptureSink> {
+    device: HidDevice,
+    sink: S,
+    start: Instant,
+    sink_error: Option<Error>,
+}
+
+impl<S: CaptureSink> RecordingDevice<S> {
+    /// Start recording `device` into `sink`
+    ///
+    /// Event timestamps are measured from this call.
+    pub fn new(device: HidDevice, sink: S) -> Self {
+        Self {
+            device,
+            sink,
+            start: Instant::now(),
+            sink_error: None,
+        }
+    }
+
+    /// The error that stopped recording, if the sink failed
+    ///
+    /// Taking it resumes recording with the next operation.
+    pub fn take_sink_error(&mut self) -> Option<Error> {
+        self.sink_error.take()
+    }
+
+    /// Get device information
+    pub fn info(&self) -> &DeviceInfo {
+        self.device.info()
+    }
+
+    /// Access the wrapped device
+    pub fn inner(&self) -> &HidDevice {
+        &self.device
+    }
+
+    /// Stop recording and return the device and the sink
+    pub fn into_parts(self) -> (HidDevice, S) {
+        (self.device, self.sink)
+    }
+
+    fn record(
+        &mut self,
+        kind: EventKind,
+        report_id: u8,
+        data: &[u8],
+        requested_len: Option<usize>,
+    ) {
+        if self.sink_error.is_some() {
+            return;
+        }
+        let mut event = CaptureEvent::new(self.start.elapsed(), kind, report_id, data.to_vec());
+        event.requested_len = requested_len;
+        if let Err(e) = self.sink.write_event(&event) {
+            self.sink_error = Some(e);
+        }
+    }
+
+    /// Record the outcome of an operation and pass it through
+    fn record_result<T>(
+        &mut self,
+        result: Result<T>,
+        kind: EventKind,
+        report_id: u8,
+        requested_len: Option<usize>,
+        data: impl FnOnce(&T) -> Vec<u8>,
+    ) -> Result<T> {
+        match result {
+            Ok(value) => {
+                self.record(kind, report_id, &data(&value), requested_len);
+                Ok(value)
+            }
+            Err(err) => {
+                let (kind, message) = match &err {
+                    Error::Timeout => (EventKind::Timeout, String::new()),
+                    e if e.is_disconnected() => (EventKind::Disconnected, String::new()),
+                    e => (EventKind::Error, e.to_string()),
+                };
+                self.record(kind, report_id, message.as_bytes(), requested_len);
+                Err(err)
+            }
+        }
+    }
+
+    fn record_read(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize> {
+        let result = match timeout {
+            Some(timeout) => self.device.read_timeout(buf, timeout),
+            None => self.device.read(buf),
+        };
+        let received = match &result {
+            Ok(n) => buf[..*n].to_vec(),
+            Err(_) => Vec::new(),
+        };
+        self.record_result(result, EventKind::Read, 0, None, |_| received)
+    }
+}
+
+impl<S: CaptureSink> Transport for RecordingDevice<S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.record_read(buf, None)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.record_read(buf, Some(timeout))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        let result = self.device.write(data);
+        self.record_result(result, EventKind::Write, 0, Some(data.len()), |n| {
+            data[..*n].to_vec()
+        })
+    }
+
+    fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        let requested_len = Some(buf.len());
+        let result = self.device.get_feature_report(report_id, buf);
+        let received = match &result {
+            Ok(n) => buf[..(*n).min(buf.len())].to_vec(),
+            Err(_) => Vec::new(),
+        };
+        self.record_result(
+            result,
+            EventKind::GetFeature,
+            report_id,
+            requested_len,
+            |_| received,
+        )
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        let result = self.device.send_feature_report(data);
+        self.record_result(result, EventKind::SendFeature, 0, None, |_| data.to_vec())
+    }
+
+    fn max_input_report_len(&self) -> Result<usize> {
+        self.device.max_input_report_len()
+    }
+}
+
+impl<S: CaptureSink> std::fmt::Debug for RecordingDevice<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RecordingDevice")
+            .field("info", self.device.info())
+            .finish()
+    }
+}
+
+/// Build the capture header for a device
+///
+/// The report descriptor is included when it can be read.
+pub(crate) fn capture_header(device: &HidDevice) -> CaptureHeader {
+    let descriptor = device
+        .get_report_descriptor()
+        .map(|desc| desc.as_bytes().to_vec())
+        .unwrap_or_default();
+
+    CaptureHeader {
+        vendor_id: device.info().vendor_id,
+        product_id: device.info().product_id,
+        descriptor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hidraw::HidrawDevice;
+    use std::io::Read;
+
+    /// A sink that fails every write, like a full disk
+    struct FailingSink;
+
+    impl CaptureSink for FailingSink {
+        fn write_event(&mut self, _event: &CaptureEvent) -> Result<()> {
+            Err(Error::io_error("disk full"))
+        }
+    }
+
+    #[test]
+    fn test_sink_error_keeps_device_result() -> Result<()> {
+        let path = "/dev/hidraw-test0";
+        let (raw, mut peer) = HidrawDevice::socket_pair(path)?;
+        let device = HidDevice::from_raw(raw, DeviceInfo::new(path, 0xFFFF, 0xFFFE));
+        let mut recorder = RecordingDevice::new(device, FailingSink);
+
+        assert_eq!(recorder.write(&[0, 1, 2])?, 3);
+        let mut sent = [0u8; 3];
+        peer.read_exact(&mut sent)?;
+        assert_eq!(sent, [0, 1, 2]);
+
+        assert!(recorder.take_sink_error().is_some());
+        assert!(recorder.take_sink_error().is_none());
+
+        Ok(())
+    }
+}
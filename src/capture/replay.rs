@@ -0,0 +1,246 @@
+//! Replaying captures as a transport
+
+use super::format::{CaptureEvent, CaptureHeader, CaptureReader, EventKind};
+use crate::descriptor::ReportLayout;
+use crate::transport::Transport;
+use crate::{Error, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::time::Duration;
+
+/// Plays a capture back in order as a [`Transport`]
+///
+/// Writes and feature reports sent by the caller must match the recording
+/// byte for byte. A write the device only partly accepted matches when the
+/// caller sends the same number of bytes, starting with the recorded ones;
+/// reads return the recorded input reports. Recorded
+/// timeouts, disconnects and errors are returned as errors at the same
+/// point in the sequence. Timing is not reproduced.
+#[derive(Debug)]
+pub struct ReplayDevice {
+    header: CaptureHeader,
+    events: VecDeque<CaptureEvent>,
+    layout: Option<ReportLayout>,
+}
+
+impl ReplayDevice {
+    /// Create a replay device from a header and its events
+    pub fn new(header: CaptureHeader, events: Vec<CaptureEvent>) -> Self {
+        let layout = if header.descriptor.is_empty() {
+            None
+        } else {
+            ReportLayout::parse(&header.descriptor).ok()
+        };
+
+        Self {
+            header,
+            events: events.into(),
+            layout,
+        }
+    }
+
+    /// Load a capture from a reader
+    pub fn from_reader<R: Read>(source: R) -> Result<Self> {
+        let mut reader = CaptureReader::new(source)?;
+        let events = reader.read_all()?;
+        Ok(Self::new(reader.header().clone(), events))
+    }
+
+    /// Load a capture file
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Header of the capture being replayed
+    pub fn header(&self) -> &CaptureHeader {
+        &self.header
+    }
+
+    /// Number of events not yet replayed
+    pub fn remaining(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Whether every recorded event has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Take the next event, which must be of kind `expected` or a failure
+    fn next_event(&mut self, expected: EventKind) -> Result<CaptureEvent> {
+        let event = self
+            .events
+            .pop_front()
+            .ok_or_else(|| Error::Protocol(format!("Capture exhausted, expected {expected:?}")))?;
+
+        match event.kind {
+            kind if kind == expected => Ok(event),
+            EventKind::Timeout => Err(Error::Timeout),
+            EventKind::Disconnected => Err(Error::Disconnected),
+            EventKind::Error => Err(Error::io_error(&String::from_utf8_lossy(&event.data))),
+            kind => Err(Error::Protocol(format!(
+                "Replay expected {expected:?} but capture has {kind:?} at {timestamp:?}",
+                timestamp = event.timestamp
+            ))),
+        }
+    }
+
+    /// Check sent bytes against the next event, returning the recorded length
+    fn check_sent(&mut self, expected: EventKind, data: &[u8]) -> Result<usize> {
+        let event = self.next_event(expected)?;
+        let matches = match event.requested_len {
+            // Partial write: the recording holds only what the device took
+            Some(requested) if requested > event.data.len() => {
+                data.len() == requested && data.starts_with(&event.data)
+            }
+            _ => event.data == data,
+        };
+        if !matches {
+            return Err(Error::Protocol(format!(
+                "Replay mismatch at {timestamp:?}: sent {data:02x?}, recorded {recorded:02x?}",
+                timestamp = event.timestamp,
+                recorded = event.data
+            )));
+        }
+        Ok(event.data.len())
+    }
+
+    fn replay_read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let event = self.next_event(EventKind::Read)?;
+        // Like hidraw, deliver as much of the report as fits
+        let n = event.data.len().min(buf.len());
+        buf[..n].copy_from_slice(&event.data[..n]);
+        Ok(n)
+    }
+}
+
+impl Transport for ReplayDevice {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.replay_read(buf)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], _timeout: Duration) -> Result<usize> {
+        self.replay_read(buf)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.check_sent(EventKind::Write, data)
+    }
+
+    fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        let event = self.next_event(EventKind::GetFeature)?;
+        if event.report_id != report_id {
+            return Err(Error::Protocol(format!(
+                "Replay expected feature report {recorded}, got request for {report_id}",
+                recorded = event.report_id
+            )));
+        }
+
+        let n = event.data.len().min(buf.len());
+        buf[..n].copy_from_slice(&event.data[..n]);
+        Ok(n)
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        self.check_sent(EventKind::SendFeature, data)?;
+        Ok(())
+    }
+
+    fn max_input_report_len(&self) -> Result<usize> {
+        let layout = self.layout.as_ref().ok_or_else(|| {
+            Error::NotSupported("Capture has no parseable report descriptor".to_string())
+        })?;
+        Ok(layout.input_buffer_len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coldcard::ColdcardProtocol;
+    use crate::protocol::frame_packets;
+
+    fn event(kind: EventKind, data: Vec<u8>) -> CaptureEvent {
+        CaptureEvent::new(Duration::ZERO, kind, 0, data)
+    }
+
+    fn ping_session(response: Vec<CaptureEvent>) -> ReplayDevice {
+        let mut request = b"ping".to_vec();
+        request.extend_from_slice(b"hello");
+
+        let mut events: Vec<_> = frame_packets(&request, 64)
+            .into_iter()
+            .map(|packet| event(EventKind::Write, packet))
+            .collect();
+        events.extend(response);
+        ReplayDevice::new(CaptureHeader::default(), events)
+    }
+
+    #[test]
+    fn test_replay_coldcard_ping() -> Result<()> {
+        let response = frame_packets(b"bihello", 64)
+            .into_iter()
+            .map(|packet| event(EventKind::Read, packet))
+            .collect();
+        let mut replay = ping_session(response);
+
+        let reply = ColdcardProtocol::new(&mut replay).send_command(b"ping", Some(b"hello"))?;
+        assert_eq!(reply, b"bihello");
+        assert!(replay.is_finished());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_recorded_timeout() -> Result<()> {
+        let mut replay = ping_session(vec![event(EventKind::Timeout, Vec::new())]);
+
+        let result = ColdcardProtocol::new(&mut replay).send_command(b"ping", Some(b"hello"));
+        assert!(matches!(result, Err(Error::Timeout)));
+        assert!(replay.is_finished());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_write_mismatch() -> Result<()> {
+        let recorded = || {
+            ReplayDevice::new(
+                CaptureHeader::default(),
+                vec![event(EventKind::Write, vec![0, 1, 2])],
+            )
+        };
+
+        assert!(matches!(
+            recorded().write(&[0, 1, 3]),
+            Err(Error::Protocol(_))
+        ));
+        assert!(matches!(recorded().write(&[0, 1]), Err(Error::Protocol(_))));
+        assert_eq!(recorded().write(&[0, 1, 2])?, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_partial_write() -> Result<()> {
+        let mut replay = ReplayDevice::new(
+            CaptureHeader::default(),
+            vec![
+                event(EventKind::Write, vec![0, 1]).with_requested_len(4),
+                event(EventKind::Write, vec![0, 1]).with_requested_len(4),
+                event(EventKind::Write, vec![0, 1]),
+            ],
+        );
+
+        assert_eq!(replay.write(&[0, 1, 2, 3])?, 2);
+        // The caller must still send as many bytes as were recorded
+        assert!(matches!(replay.write(&[0, 1, 2]), Err(Error::Protocol(_))));
+        // A write recorded in full doesn't accept trailing bytes
+        assert!(matches!(replay.write(&[0, 1, 2]), Err(Error::Protocol(_))));
+        assert!(replay.is_finished());
+
+        Ok(())
+    }
+}
@@ -3,5 +3,5 @@
 mod constants;
 mod protocol;
 
-pub use constants::{COINKITE_VID, COLDCARD_PID};
+pub use constants::{COINKITE_VID, COLDCARD_PID, PACKET_SIZE};
 pub use protocol::{ColdcardDevice, ColdcardProtocol};
@@ -2,7 +2,7 @@
 
 use super::constants::*;
 use crate::protocol::frame_packets;
-use crate::{Error, HidDevice, Result};
+use crate::{Error, HidDevice, Result, Transport};
 use std::time::Duration;
 
 /// Coldcard device handle
@@ -74,13 +74,17 @@ impl ColdcardDevice {
 }
 
 /// Low-level Coldcard protocol handler
-pub struct ColdcardProtocol<'a> {
-    device: &'a mut HidDevice,
+///
+/// Runs over any [`Transport`], so sessions can be recorded with
+/// [`HidDevice::with_recorder`] and replayed with
+/// [`ReplayDevice`](crate::capture::ReplayDevice).
+pub struct ColdcardProtocol<'a, T: Transport + ?Sized = HidDevice> {
+    device: &'a mut T,
 }
 
-impl<'a> ColdcardProtocol<'a> {
+impl<'a, T: Transport + ?Sized> ColdcardProtocol<'a, T> {
     /// Create a new protocol handler
-    pub fn new(device: &'a mut HidDevice) -> Self {
+    pub fn new(device: &'a mut T) -> Self {
         Self { device }
     }
 
@@ -114,8 +118,15 @@ impl<'a> ColdcardProtocol<'a> {
         // Set a reasonable timeout for reading
         let timeout = Duration::from_secs(5);
 
+        // Size reads from the report descriptor, falling back to the known packet size
+        let read_size = self
+            .device
+            .max_input_report_len()
+            .unwrap_or(PACKET_SIZE)
+            .max(PACKET_SIZE);
+
         while !response_complete {
-            let mut packet = vec![0u8; PACKET_SIZE];
+            let mut packet = vec![0u8; read_size];
             let n = self.device.read_timeout(&mut packet, timeout)?;
 
             if n == 0 {
@@ -0,0 +1,393 @@
+//! Report descriptor builder
+//!
+//! Produces report descriptors for virtual (uhid) devices and test fixtures.
+//! Every item is emitted as a short item with the smallest data size that
+//! holds its value, so descriptors written in the usual canonical form
+//! round-trip byte for byte through [`parse_items`](super::parse_items).
+
+use super::item::{Item, ItemType, global, local, main};
+use crate::ReportDescriptor;
+use std::ops::BitOr;
+
+/// Collection type for the Collection main item
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionType {
+    Physical,
+    Application,
+    Logical,
+    Report,
+    NamedArray,
+    UsageSwitch,
+    UsageModifier,
+    /// Reserved or vendor-defined collection type
+    Other(u8),
+}
+
+impl CollectionType {
+    /// Numeric value as stored in the descriptor
+    pub fn value(self) -> u8 {
+        match self {
+            CollectionType::Physical => 0x00,
+            CollectionType::Application => 0x01,
+            CollectionType::Logical => 0x02,
+            CollectionType::Report => 0x03,
+            CollectionType::NamedArray => 0x04,
+            CollectionType::UsageSwitch => 0x05,
+            CollectionType::UsageModifier => 0x06,
+            CollectionType::Other(value) => value,
+        }
+    }
+
+    /// Convert from the value stored in the descriptor
+    pub fn from_value(value: u8) -> Self {
+        match value {
+            0x00 => CollectionType::Physical,
+            0x01 => CollectionType::Application,
+            0x02 => CollectionType::Logical,
+            0x03 => CollectionType::Report,
+            0x04 => CollectionType::NamedArray,
+            0x05 => CollectionType::UsageSwitch,
+            0x06 => CollectionType::UsageModifier,
+            other => CollectionType::Other(other),
+        }
+    }
+}
+
+/// Data flags for Input, Output and Feature main items
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MainFlags(pub u32);
+
+impl MainFlags {
+    /// Data, Array, Absolute
+    pub const DATA: MainFlags = MainFlags(0x000);
+    /// Constant (padding) instead of Data
+    pub const CONSTANT: MainFlags = MainFlags(0x001);
+    /// Variable instead of Array
+    pub const VARIABLE: MainFlags = MainFlags(0x002);
+    /// Relative instead of Absolute
+    pub const RELATIVE: MainFlags = MainFlags(0x004);
+    /// Wrap
+    pub const WRAP: MainFlags = MainFlags(0x008);
+    /// Non Linear
+    pub const NON_LINEAR: MainFlags = MainFlags(0x010);
+    /// No Preferred State
+    pub const NO_PREFERRED: MainFlags = MainFlags(0x020);
+    /// Null State
+    pub const NULL_STATE: MainFlags = MainFlags(0x040);
+    /// Volatile (Output and Feature only)
+    pub const VOLATILE: MainFlags = MainFlags(0x080);
+    /// Buffered Bytes instead of Bit Field
+    pub const BUFFERED_BYTES: MainFlags = MainFlags(0x100);
+
+    /// Check whether all bits of `other` are set
+    pub fn contains(self, other: MainFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for MainFlags {
+    type Output = MainFlags;
+
+    fn bitor(self, rhs: MainFlags) -> MainFlags {
+        MainFlags(self.0 | rhs.0)
+    }
+}
+
+/// Builder for HID report descriptors
+///
+/// # Example
+/// ```
+/// use hidraw_rs::descriptor::{CollectionType, DescriptorBuilder, MainFlags};
+///
+/// let descriptor = DescriptorBuilder::new()
+///     .usage_page(0xFF00)
+///     .usage(0x01)
+///     .collection(CollectionType::Application, |c| {
+///         c.logical_minimum(0)
+///             .logical_maximum(255)
+///             .report_size(8)
+///             .report_count(64)
+///             .usage(0x01)
+///             .input(MainFlags::VARIABLE)
+///             .usage(0x02)
+///             .output(MainFlags::VARIABLE)
+///     })
+///     .build();
+///
+/// assert_eq!(descriptor.as_bytes()[..3], [0x06, 0x00, 0xFF]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorBuilder {
+    items: Vec<Item>,
+}
+
+impl DescriptorBuilder {
+    /// Create an empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a builder from already parsed items
+    ///
+    /// Each item is re-encoded with its minimal data size. Rebuilding the
+    /// items of a canonically encoded descriptor yields identical bytes.
+    pub fn from_items<'a>(items: impl IntoIterator<Item = &'a Item>) -> Self {
+        Self {
+            items: items.into_iter().map(Item::to_minimal).collect(),
+        }
+    }
+
+    /// Append an arbitrary item verbatim
+    pub fn item(mut self, item: Item) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    fn unsigned(self, item_type: ItemType, tag: u8, value: u32) -> Self {
+        // Tags used by the builder always fit in 4 bits and the data is at
+        // most 4 bytes, so construction cannot fail
+        match Item::unsigned(item_type, tag, value) {
+            Ok(item) => self.item(item),
+            Err(_) => self,
+        }
+    }
+
+    fn signed(self, item_type: ItemType, tag: u8, value: i32) -> Self {
+        match Item::signed(item_type, tag, value) {
+            Ok(item) => self.item(item),
+            Err(_) => self,
+        }
+    }
+
+    fn empty(self, item_type: ItemType, tag: u8) -> Self {
+        self.item(Item {
+            item_type,
+            tag,
+            data: Vec::new(),
+        })
+    }
+
+    /// Usage Page global item
+    pub fn usage_page(self, page: u16) -> Self {
+        self.unsigned(ItemType::Global, global::USAGE_PAGE, page as u32)
+    }
+
+    /// Logical Minimum global item
+    pub fn logical_minimum(self, value: i32) -> Self {
+        self.signed(ItemType::Global, global::LOGICAL_MINIMUM, value)
+    }
+
+    /// Logical Maximum global item
+    pub fn logical_maximum(self, value: i32) -> Self {
+        self.signed(ItemType::Global, global::LOGICAL_MAXIMUM, value)
+    }
+
+    /// Physical Minimum global item
+    pub fn physical_minimum(self, value: i32) -> Self {
+        self.signed(ItemType::Global, global::PHYSICAL_MINIMUM, value)
+    }
+
+    /// Physical Maximum global item
+    pub fn physical_maximum(self, value: i32) -> Self {
+        self.signed(ItemType::Global, global::PHYSICAL_MAXIMUM, value)
+    }
+
+    /// Unit Exponent global item
+    ///
+    /// Encoded as the 4-bit two's complement nibble used by most devices.
+    pub fn unit_exponent(self, exponent: i8) -> Self {
+        self.unsigned(
+            ItemType::Global,
+            global::UNIT_EXPONENT,
+            (exponent as u8 & 0x0F) as u32,
+        )
+    }
+
+    /// Unit global item
+    pub fn unit(self, unit: u32) -> Self {
+        self.unsigned(ItemType::Global, global::UNIT, unit)
+    }
+
+    /// Report Size global item (field size in bits)
+    pub fn report_size(self, bits: u32) -> Self {
+        self.unsigned(ItemType::Global, global::REPORT_SIZE, bits)
+    }
+
+    /// Report ID global item
+    pub fn report_id(self, id: u8) -> Self {
+        self.unsigned(ItemType::Global, global::REPORT_ID, id as u32)
+    }
+
+    /// Report Count global item
+    pub fn report_count(self, count: u32) -> Self {
+        self.unsigned(ItemType::Global, global::REPORT_COUNT, count)
+    }
+
+    /// Push global item
+    pub fn push(self) -> Self {
+        self.empty(ItemType::Global, global::PUSH)
+    }
+
+    /// Pop global item
+    pub fn pop(self) -> Self {
+        self.empty(ItemType::Global, global::POP)
+    }
+
+    /// Usage local item
+    ///
+    /// Values above `0xFFFF` are emitted as extended (page:usage) usages.
+    pub fn usage(self, usage: u32) -> Self {
+        self.unsigned(ItemType::Local, local::USAGE, usage)
+    }
+
+    /// Usage Minimum local item
+    pub fn usage_minimum(self, usage: u32) -> Self {
+        self.unsigned(ItemType::Local, local::USAGE_MINIMUM, usage)
+    }
+
+    /// Usage Maximum local item
+    pub fn usage_maximum(self, usage: u32) -> Self {
+        self.unsigned(ItemType::Local, local::USAGE_MAXIMUM, usage)
+    }
+
+    /// String Index local item
+    pub fn string_index(self, index: u32) -> Self {
+        self.unsigned(ItemType::Local, local::STRING_INDEX, index)
+    }
+
+    /// Input main item
+    pub fn input(self, flags: MainFlags) -> Self {
+        self.unsigned(ItemType::Main, main::INPUT, flags.0)
+    }
+
+    /// Output main item
+    pub fn output(self, flags: MainFlags) -> Self {
+        self.unsigned(ItemType::Main, main::OUTPUT, flags.0)
+    }
+
+    /// Feature main item
+    pub fn feature(self, flags: MainFlags) -> Self {
+        self.unsigned(ItemType::Main, main::FEATURE, flags.0)
+    }
+
+    /// Emit a Collection, the items added by `body`, and End Collection
+    pub fn collection(
+        self,
+        collection_type: CollectionType,
+        body: impl FnOnce(DescriptorBuilder) -> DescriptorBuilder,
+    ) -> Self {
+        let builder = self.unsigned(
+            ItemType::Main,
+            main::COLLECTION,
+            collection_type.value() as u32,
+        );
+        body(builder).empty(ItemType::Main, main::END_COLLECTION)
+    }
+
+    /// Items added so far
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Encode the descriptor to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.items.iter().map(Item::encoded_len).sum());
+        for item in &self.items {
+            item.encode_into(&mut out);
+        }
+        out
+    }
+
+    /// Build the report descriptor
+    pub fn build(&self) -> ReportDescriptor {
+        ReportDescriptor::from_bytes(self.to_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+    use crate::descriptor::parse_items;
+
+    /// Boot protocol mouse descriptor from the HID 1.11 specification, Appendix E.10
+    const BOOT_MOUSE: &[u8] = &[
+        0x05, 0x01, 0x09, 0x02, 0xA1, 0x01, 0x09, 0x01, 0xA1, 0x00, 0x05, 0x09, 0x19, 0x01, 0x29,
+        0x03, 0x15, 0x00, 0x25, 0x01, 0x95, 0x03, 0x75, 0x01, 0x81, 0x02, 0x95, 0x01, 0x75, 0x05,
+        0x81, 0x01, 0x05, 0x01, 0x09, 0x30, 0x09, 0x31, 0x15, 0x81, 0x25, 0x7F, 0x75, 0x08, 0x95,
+        0x02, 0x81, 0x06, 0xC0, 0xC0,
+    ];
+
+    #[test]
+    fn test_build_boot_mouse() -> Result<()> {
+        let built = DescriptorBuilder::new()
+            .usage_page(0x01)
+            .usage(0x02)
+            .collection(CollectionType::Application, |c| {
+                c.usage(0x01).collection(CollectionType::Physical, |c| {
+                    c.usage_page(0x09)
+                        .usage_minimum(1)
+                        .usage_maximum(3)
+                        .logical_minimum(0)
+                        .logical_maximum(1)
+                        .report_count(3)
+                        .report_size(1)
+                        .input(MainFlags::VARIABLE)
+                        .report_count(1)
+                        .report_size(5)
+                        .input(MainFlags::CONSTANT)
+                        .usage_page(0x01)
+                        .usage(0x30)
+                        .usage(0x31)
+                        .logical_minimum(-127)
+                        .logical_maximum(127)
+                        .report_size(8)
+                        .report_count(2)
+                        .input(MainFlags::VARIABLE | MainFlags::RELATIVE)
+                })
+            })
+            .build();
+
+        assert_eq!(built.as_bytes(), BOOT_MOUSE);
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip_through_parser() -> Result<()> {
+        let original = ReportDescriptor::from_bytes(BOOT_MOUSE.to_vec());
+        let rebuilt = original.rebuild()?;
+
+        assert_eq!(rebuilt.as_bytes(), original.as_bytes());
+        assert_eq!(parse_items(rebuilt.as_bytes())?, original.items()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wide_values() -> Result<()> {
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0xFF00)
+            .logical_maximum(255)
+            .logical_minimum(-32768)
+            .report_count(64)
+            .unit_exponent(-2)
+            .feature(MainFlags::VARIABLE | MainFlags::BUFFERED_BYTES)
+            .push()
+            .pop()
+            .to_bytes();
+
+        assert_eq!(
+            bytes,
+            [
+                0x06, 0x00, 0xFF, // Usage Page (Vendor 0xFF00)
+                0x26, 0xFF, 0x00, // Logical Maximum (255)
+                0x16, 0x00, 0x80, // Logical Minimum (-32768)
+                0x95, 0x40, // Report Count (64)
+                0x55, 0x0E, // Unit Exponent (-2)
+                0xB2, 0x02, 0x01, // Feature (Data, Var, Abs, Buffered Bytes)
+                0xA4, // Push
+                0xB4, // Pop
+            ]
+        );
+        Ok(())
+    }
+}
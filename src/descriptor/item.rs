@@ -0,0 +1,492 @@
+//! HID report descriptor items
+//!
+//! A report descriptor is a flat stream of items. Short items carry a
+//! one-byte prefix (tag, type and size) followed by 0, 1, 2 or 4 data bytes.
+//! Long items (prefix `0xFE`) are parsed and re-emitted verbatim but are not
+//! interpreted, since the HID specification does not define any.
+
+use crate::{Error, Result};
+
+/// Prefix byte that introduces a long item
+const LONG_ITEM_PREFIX: u8 = 0xFE;
+
+/// Main item tags
+pub mod main {
+    /// Input item
+    pub const INPUT: u8 = 0x8;
+    /// Output item
+    pub const OUTPUT: u8 = 0x9;
+    /// Collection item
+    pub const COLLECTION: u8 = 0xA;
+    /// Feature item
+    pub const FEATURE: u8 = 0xB;
+    /// End Collection item
+    pub const END_COLLECTION: u8 = 0xC;
+}
+
+/// Global item tags
+pub mod global {
+    /// Usage Page
+    pub const USAGE_PAGE: u8 = 0x0;
+    /// Logical Minimum
+    pub const LOGICAL_MINIMUM: u8 = 0x1;
+    /// Logical Maximum
+    pub const LOGICAL_MAXIMUM: u8 = 0x2;
+    /// Physical Minimum
+    pub const PHYSICAL_MINIMUM: u8 = 0x3;
+    /// Physical Maximum
+    pub const PHYSICAL_MAXIMUM: u8 = 0x4;
+    /// Unit Exponent
+    pub const UNIT_EXPONENT: u8 = 0x5;
+    /// Unit
+    pub const UNIT: u8 = 0x6;
+    /// Report Size (in bits)
+    pub const REPORT_SIZE: u8 = 0x7;
+    /// Report ID
+    pub const REPORT_ID: u8 = 0x8;
+    /// Report Count
+    pub const REPORT_COUNT: u8 = 0x9;
+    /// Push the global item state
+    pub const PUSH: u8 = 0xA;
+    /// Pop the global item state
+    pub const POP: u8 = 0xB;
+}
+
+/// Local item tags
+pub mod local {
+    /// Usage
+    pub const USAGE: u8 = 0x0;
+    /// Usage Minimum
+    pub const USAGE_MINIMUM: u8 = 0x1;
+    /// Usage Maximum
+    pub const USAGE_MAXIMUM: u8 = 0x2;
+    /// Designator Index
+    pub const DESIGNATOR_INDEX: u8 = 0x3;
+    /// Designator Minimum
+    pub const DESIGNATOR_MINIMUM: u8 = 0x4;
+    /// Designator Maximum
+    pub const DESIGNATOR_MAXIMUM: u8 = 0x5;
+    /// String Index
+    pub const STRING_INDEX: u8 = 0x7;
+    /// String Minimum
+    pub const STRING_MINIMUM: u8 = 0x8;
+    /// String Maximum
+    pub const STRING_MAXIMUM: u8 = 0x9;
+    /// Delimiter
+    pub const DELIMITER: u8 = 0xA;
+}
+
+/// Item type (bits 2-3 of the prefix byte)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemType {
+    Main,
+    Global,
+    Local,
+    Reserved,
+    /// Long item; the tag is the `bLongItemTag` byte
+    Long,
+}
+
+impl ItemType {
+    fn from_bits(bits: u8) -> Self {
+        match bits & 0x03 {
+            0 => ItemType::Main,
+            1 => ItemType::Global,
+            2 => ItemType::Local,
+            _ => ItemType::Reserved,
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            ItemType::Main => 0,
+            ItemType::Global => 1,
+            ItemType::Local => 2,
+            ItemType::Reserved | ItemType::Long => 3,
+        }
+    }
+}
+
+/// A single report descriptor item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    /// Item type
+    pub item_type: ItemType,
+    /// Item tag (4 bits for short items, 8 bits for long items)
+    pub tag: u8,
+    /// Raw little-endian item data
+    pub data: Vec<u8>,
+}
+
+impl Item {
+    /// Create a short item with the given raw data
+    ///
+    /// Short items can only hold 0, 1, 2 or 4 data bytes.
+    pub fn short(item_type: ItemType, tag: u8, data: Vec<u8>) -> Result<Self> {
+        if item_type == ItemType::Long {
+            return Err(Error::InvalidParameter(
+                "Use Item::long for long items".to_string(),
+            ));
+        }
+        if tag > 0x0F {
+            return Err(Error::InvalidParameter(format!(
+                "Short item tag {tag:#x} does not fit in 4 bits"
+            )));
+        }
+        if !matches!(data.len(), 0 | 1 | 2 | 4) {
+            return Err(Error::InvalidParameter(format!(
+                "Short item data must be 0, 1, 2 or 4 bytes, got {len}",
+                len = data.len()
+            )));
+        }
+
+        Ok(Self {
+            item_type,
+            tag,
+            data,
+        })
+    }
+
+    /// Create a long item
+    pub fn long(tag: u8, data: Vec<u8>) -> Result<Self> {
+        if data.len() > u8::MAX as usize {
+            return Err(Error::InvalidParameter(format!(
+                "Long item data too large: {len} bytes (max 255)",
+                len = data.len()
+            )));
+        }
+
+        Ok(Self {
+            item_type: ItemType::Long,
+            tag,
+            data,
+        })
+    }
+
+    /// Create a short item holding an unsigned value in as few bytes as possible
+    ///
+    /// At least one data byte is always emitted, matching the way descriptors
+    /// are conventionally written (e.g. `Logical Minimum (0)` is `15 00`).
+    pub fn unsigned(item_type: ItemType, tag: u8, value: u32) -> Result<Self> {
+        let data = if value <= 0xFF {
+            vec![value as u8]
+        } else if value <= 0xFFFF {
+            (value as u16).to_le_bytes().to_vec()
+        } else {
+            value.to_le_bytes().to_vec()
+        };
+        Self::short(item_type, tag, data)
+    }
+
+    /// Create a short item holding a signed value in as few bytes as possible
+    pub fn signed(item_type: ItemType, tag: u8, value: i32) -> Result<Self> {
+        let data = if i8::try_from(value).is_ok() {
+            vec![value as u8]
+        } else if i16::try_from(value).is_ok() {
+            (value as i16).to_le_bytes().to_vec()
+        } else {
+            value.to_le_bytes().to_vec()
+        };
+        Self::short(item_type, tag, data)
+    }
+
+    /// Interpret the item data as an unsigned little-endian value
+    pub fn unsigned_value(&self) -> u32 {
+        self.data
+            .iter()
+            .take(4)
+            .enumerate()
+            .fold(0, |acc, (i, &b)| acc | (b as u32) << (i * 8))
+    }
+
+    /// Interpret the item data as a signed little-endian value
+    pub fn signed_value(&self) -> i32 {
+        match self.data.len() {
+            0 => 0,
+            1 => self.data[0] as i8 as i32,
+            2 => i16::from_le_bytes([self.data[0], self.data[1]]) as i32,
+            _ => self.unsigned_value() as i32,
+        }
+    }
+
+    /// Whether this item's value is conventionally interpreted as signed
+    pub fn is_signed(&self) -> bool {
+        self.item_type == ItemType::Global
+            && matches!(
+                self.tag,
+                global::LOGICAL_MINIMUM
+                    | global::LOGICAL_MAXIMUM
+                    | global::PHYSICAL_MINIMUM
+                    | global::PHYSICAL_MAXIMUM
+            )
+    }
+
+    /// Re-encode this item with the smallest data size that holds its value
+    ///
+    /// Items without data and long items are returned unchanged, as are
+    /// 4-byte Usage, Usage Minimum and Usage Maximum items: their upper 16
+    /// bits are an explicit usage page, so a shorter encoding would change
+    /// their meaning.
+    pub fn to_minimal(&self) -> Item {
+        if self.data.is_empty() || self.item_type == ItemType::Long || self.is_extended_usage() {
+            return self.clone();
+        }
+
+        let minimal = if self.is_signed() {
+            Self::signed(self.item_type, self.tag, self.signed_value())
+        } else {
+            Self::unsigned(self.item_type, self.tag, self.unsigned_value())
+        };
+        minimal.unwrap_or_else(|_| self.clone())
+    }
+
+    /// Whether this is a local usage item carrying its own usage page
+    fn is_extended_usage(&self) -> bool {
+        self.item_type == ItemType::Local
+            && matches!(
+                self.tag,
+                local::USAGE | local::USAGE_MINIMUM | local::USAGE_MAXIMUM
+            )
+            && self.data.len() == 4
+    }
+
+    /// Number of bytes this item occupies in a descriptor
+    pub fn encoded_len(&self) -> usize {
+        match self.item_type {
+            ItemType::Long => 3 + self.data.len(),
+            _ => 1 + self.data.len(),
+        }
+    }
+
+    /// Append the encoded item to `out`
+    pub fn encode_into(&self, out: &mut Vec<u8>) {
+        if self.item_type == ItemType::Long {
+            out.push(LONG_ITEM_PREFIX);
+            out.push(self.data.len() as u8);
+            out.push(self.tag);
+        } else {
+            let size_bits = match self.data.len() {
+                0 => 0,
+                1 => 1,
+                2 => 2,
+                _ => 3,
+            };
+            out.push((self.tag << 4) | (self.item_type.bits() << 2) | size_bits);
+        }
+        out.extend_from_slice(&self.data);
+    }
+
+    /// Encode the item to bytes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.encoded_len());
+        self.encode_into(&mut out);
+        out
+    }
+
+    /// Name of the item as used in the HID specification
+    pub fn name(&self) -> &'static str {
+        match (self.item_type, self.tag) {
+            (ItemType::Main, main::INPUT) => "Input",
+            (ItemType::Main, main::OUTPUT) => "Output",
+            (ItemType::Main, main::COLLECTION) => "Collection",
+            (ItemType::Main, main::FEATURE) => "Feature",
+            (ItemType::Main, main::END_COLLECTION) => "End Collection",
+            (ItemType::Global, global::USAGE_PAGE) => "Usage Page",
+            (ItemType::Global, global::LOGICAL_MINIMUM) => "Logical Minimum",
+            (ItemType::Global, global::LOGICAL_MAXIMUM) => "Logical Maximum",
+            (ItemType::Global, global::PHYSICAL_MINIMUM) => "Physical Minimum",
+            (ItemType::Global, global::PHYSICAL_MAXIMUM) => "Physical Maximum",
+            (ItemType::Global, global::UNIT_EXPONENT) => "Unit Exponent",
+            (ItemType::Global, global::UNIT) => "Unit",
+            (ItemType::Global, global::REPORT_SIZE) => "Report Size",
+            (ItemType::Global, global::REPORT_ID) => "Report ID",
+            (ItemType::Global, global::REPORT_COUNT) => "Report Count",
+            (ItemType::Global, global::PUSH) => "Push",
+            (ItemType::Global, global::POP) => "Pop",
+            (ItemType::Local, local::USAGE) => "Usage",
+            (ItemType::Local, local::USAGE_MINIMUM) => "Usage Minimum",
+            (ItemType::Local, local::USAGE_MAXIMUM) => "Usage Maximum",
+            (ItemType::Local, local::DESIGNATOR_INDEX) => "Designator Index",
+            (ItemType::Local, local::DESIGNATOR_MINIMUM) => "Designator Minimum",
+            (ItemType::Local, local::DESIGNATOR_MAXIMUM) => "Designator Maximum",
+            (ItemType::Local, local::STRING_INDEX) => "String Index",
+            (ItemType::Local, local::STRING_MINIMUM) => "String Minimum",
+            (ItemType::Local, local::STRING_MAXIMUM) => "String Maximum",
+            (ItemType::Local, local::DELIMITER) => "Delimiter",
+            (ItemType::Long, _) => "Long Item",
+            (ItemType::Main, _) => "Main (reserved)",
+            (ItemType::Global, _) => "Global (reserved)",
+            (ItemType::Local, _) => "Local (reserved)",
+            (ItemType::Reserved, _) => "Reserved",
+        }
+    }
+}
+
+impl std::fmt::Display for Item {
+    /// Formats as e.g. `Usage Page (0xFF00)` or `Logical Minimum (-127)`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.name();
+        if self.data.is_empty() {
+            return write!(f, "{name}");
+        }
+        match (self.item_type, self.tag) {
+            (ItemType::Long, tag) => {
+                write!(f, "{name} ({tag:#04x}, {len} bytes)", len = self.data.len())
+            }
+            (ItemType::Global | ItemType::Local, _) if self.is_signed() => {
+                write!(f, "{name} ({value})", value = self.signed_value())
+            }
+            (ItemType::Global, global::USAGE_PAGE)
+            | (ItemType::Local, local::USAGE | local::USAGE_MINIMUM | local::USAGE_MAXIMUM)
+            | (ItemType::Main, _) => {
+                write!(f, "{name} ({value:#06x})", value = self.unsigned_value())
+            }
+            _ => write!(f, "{name} ({value})", value = self.unsigned_value()),
+        }
+    }
+}
+
+/// Parse a report descriptor into its items
+pub fn parse_items(data: &[u8]) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        let prefix = data[i];
+
+        if prefix == LONG_ITEM_PREFIX {
+            if i + 3 > data.len() {
+                return Err(Error::Parse(format!(
+                    "Truncated long item header at offset {i}"
+                )));
+            }
+            let size = data[i + 1] as usize;
+            let tag = data[i + 2];
+            let start = i + 3;
+            if start + size > data.len() {
+                return Err(Error::Parse(format!(
+                    "Long item at offset {i} needs {size} data bytes"
+                )));
+            }
+            items.push(Item {
+                item_type: ItemType::Long,
+                tag,
+                data: data[start..start + size].to_vec(),
+            });
+            i = start + size;
+            continue;
+        }
+
+        let size = match prefix & 0x03 {
+            3 => 4,
+            n => n as usize,
+        };
+        let start = i + 1;
+        if start + size > data.len() {
+            return Err(Error::Parse(format!(
+                "Item {prefix:#04x} at offset {i} needs {size} data bytes"
+            )));
+        }
+
+        items.push(Item {
+            item_type: ItemType::from_bits(prefix >> 2),
+            tag: prefix >> 4,
+            data: data[start..start + size].to_vec(),
+        });
+        i = start + size;
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_short_items() -> Result<()> {
+        // Usage Page (Generic Desktop), Logical Maximum (255), End Collection
+        let items = parse_items(&[0x05, 0x01, 0x26, 0xFF, 0x00, 0xC0])?;
+
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].item_type, ItemType::Global);
+        assert_eq!(items[0].tag, global::USAGE_PAGE);
+        assert_eq!(items[0].unsigned_value(), 1);
+        assert_eq!(items[1].signed_value(), 255);
+        assert_eq!(items[2].item_type, ItemType::Main);
+        assert_eq!(items[2].tag, main::END_COLLECTION);
+        assert!(items[2].data.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_truncated() -> Result<()> {
+        assert!(parse_items(&[0x06, 0x00]).is_err());
+        assert!(parse_items(&[0xFE, 0x04, 0x10, 0x00]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_item_round_trip() -> Result<()> {
+        let bytes = [0xFE, 0x02, 0x42, 0xAA, 0xBB];
+        let items = parse_items(&bytes)?;
+
+        assert_eq!(items, vec![Item::long(0x42, vec![0xAA, 0xBB])?]);
+        assert_eq!(items[0].to_bytes(), bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Result<()> {
+        let items = parse_items(&[0x06, 0x00, 0xFF, 0x15, 0x81, 0x95, 0x40, 0xA1, 0x01, 0xC0])?;
+        let text: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+
+        assert_eq!(
+            text,
+            [
+                "Usage Page (0xff00)",
+                "Logical Minimum (-127)",
+                "Report Count (64)",
+                "Collection (0x0001)",
+                "End Collection",
+            ]
+        );
+        assert_eq!(
+            Item::long(0x42, vec![0xAA, 0xBB])?.to_string(),
+            "Long Item (0x42, 2 bytes)"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_minimal_encoding() -> Result<()> {
+        let item = Item::signed(ItemType::Global, global::LOGICAL_MINIMUM, -127)?;
+        assert_eq!(item.to_bytes(), [0x15, 0x81]);
+
+        let item = Item::signed(ItemType::Global, global::LOGICAL_MAXIMUM, 255)?;
+        assert_eq!(item.to_bytes(), [0x26, 0xFF, 0x00]);
+
+        let item = Item::unsigned(ItemType::Global, global::USAGE_PAGE, 0xFF00)?;
+        assert_eq!(item.to_bytes(), [0x06, 0x00, 0xFF]);
+
+        let item = Item::unsigned(ItemType::Local, local::USAGE, 0x000D_0001)?;
+        assert_eq!(item.to_bytes(), [0x0B, 0x01, 0x00, 0x0D, 0x00]);
+
+        // A padded encoding collapses to the minimal one
+        let padded = Item::short(ItemType::Global, global::REPORT_COUNT, vec![8, 0, 0, 0])?;
+        assert_eq!(padded.to_minimal().to_bytes(), [0x95, 0x08]);
+
+        // A 4-byte usage names its page explicitly, even page 0
+        let extended = Item::short(ItemType::Local, local::USAGE, vec![5, 0, 0, 0])?;
+        assert_eq!(extended.to_minimal(), extended);
+        let extended = Item::short(ItemType::Local, local::USAGE_MAXIMUM, vec![5, 0, 7, 0])?;
+        assert_eq!(extended.to_minimal(), extended);
+        let padded = Item::short(ItemType::Local, local::USAGE, vec![5, 0])?;
+        assert_eq!(padded.to_minimal().to_bytes(), [0x09, 0x05]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,805 @@
+//! Report layout derived from a report descriptor
+//!
+//! Walks the descriptor items, tracking the global item state (including
+//! Push/Pop), and sums `Report Size * Report Count` for every Input, Output
+//! and Feature main item per report ID. Each main item is also kept as a
+//! [`ReportField`] so reports can be decoded into usages and values. The
+//! usage of each top-level collection is recorded as well.
+
+use super::builder::MainFlags;
+use super::item::{Item, ItemType, global, local, main, parse_items};
+use crate::hidraw::MAX_REPORT_LEN;
+use crate::protocol::ReportType;
+use crate::{Error, Result};
+
+/// Size information for one report declared in a descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportInfo {
+    /// Report type
+    pub report_type: ReportType,
+    /// Report ID (0 when the device does not use numbered reports)
+    pub report_id: u8,
+    /// Total size of the report fields in bits
+    pub bits: usize,
+}
+
+impl ReportInfo {
+    /// Report payload length in bytes, excluding any report ID prefix
+    pub fn payload_len(&self) -> usize {
+        self.bits.div_ceil(8)
+    }
+}
+
+/// One Input, Output or Feature main item within a report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportField {
+    /// Report type
+    pub report_type: ReportType,
+    /// Report ID (0 when the device does not use numbered reports)
+    pub report_id: u8,
+    /// Offset of the first value in bits, from the start of the payload
+    /// (after any report ID byte)
+    pub bit_offset: usize,
+    /// Size of each value in bits
+    pub report_size: u32,
+    /// Number of values
+    pub report_count: u32,
+    /// Main item flags
+    pub flags: MainFlags,
+    /// Usages declared for the field as `(page << 16) | id`
+    pub usages: Vec<u32>,
+    /// Logical minimum
+    pub logical_minimum: i32,
+    /// Logical maximum
+    pub logical_maximum: i32,
+}
+
+impl ReportField {
+    /// Whether the field is constant padding
+    pub fn is_constant(&self) -> bool {
+        self.flags.contains(MainFlags::CONSTANT)
+    }
+
+    /// Whether each value is a separate variable (otherwise an array of
+    /// usage selectors)
+    pub fn is_variable(&self) -> bool {
+        self.flags.contains(MainFlags::VARIABLE)
+    }
+
+    /// Extract value `index` from a report payload
+    ///
+    /// Values are sign-extended when the logical minimum is negative.
+    /// Returns `None` when the payload is too short.
+    pub fn value(&self, payload: &[u8], index: usize) -> Option<i32> {
+        let size = self.report_size as usize;
+        if index >= self.report_count as usize || size == 0 || size > 32 {
+            return None;
+        }
+        let start = self.bit_offset + index * size;
+        if (start + size).div_ceil(8) > payload.len() {
+            return None;
+        }
+
+        let mut raw = 0u64;
+        for bit in 0..size {
+            let pos = start + bit;
+            if payload[pos / 8] >> (pos % 8) & 1 != 0 {
+                raw |= 1 << bit;
+            }
+        }
+
+        if self.logical_minimum < 0 && size < 32 && raw & (1 << (size - 1)) != 0 {
+            raw |= u64::MAX << size;
+        }
+        Some(raw as u32 as i32)
+    }
+
+    /// Usage of value `index` of a variable field
+    ///
+    /// When fewer usages than values are declared, the last usage repeats.
+    pub fn usage(&self, index: usize) -> Option<u32> {
+        self.usages.get(index).or(self.usages.last()).copied()
+    }
+
+    /// Decode all values of this field from a report payload
+    ///
+    /// Variable fields yield one value per usage. Array fields yield the
+    /// usage selected by each non-empty slot, with a value of 1.
+    pub fn decode(&self, payload: &[u8]) -> Vec<FieldValue> {
+        let mut values = Vec::new();
+        for index in 0..self.report_count as usize {
+            let Some(value) = self.value(payload, index) else {
+                break;
+            };
+            if self.is_variable() {
+                values.push(FieldValue {
+                    usage: self.usage(index).unwrap_or(0),
+                    value,
+                });
+                continue;
+            }
+
+            // Array slots hold a usage index; out-of-range means "nothing"
+            if value < self.logical_minimum || value > self.logical_maximum {
+                continue;
+            }
+            // i64 so that a minimum of i32::MIN can't overflow
+            let selected = usize::try_from(i64::from(value) - i64::from(self.logical_minimum));
+            if let Some(&usage) = selected.ok().and_then(|selected| self.usages.get(selected))
+                && (value != 0 || self.logical_minimum != 0)
+            {
+                values.push(FieldValue { usage, value: 1 });
+            }
+        }
+        values
+    }
+}
+
+/// A decoded value from a report
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldValue {
+    /// Usage as `(page << 16) | id`
+    pub usage: u32,
+    /// Logical value
+    pub value: i32,
+}
+
+impl FieldValue {
+    /// Usage page
+    pub fn usage_page(&self) -> u16 {
+        (self.usage >> 16) as u16
+    }
+
+    /// Usage ID within the page
+    pub fn usage_id(&self) -> u16 {
+        self.usage as u16
+    }
+}
+
+impl std::fmt::Display for FieldValue {
+    /// Formats as `page:id=value`, e.g. `0009:0001=1`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{page:04x}:{id:04x}={value}",
+            page = self.usage_page(),
+            id = self.usage_id(),
+            value = self.value
+        )
+    }
+}
+
+/// Global item state relevant to report sizing
+#[derive(Debug, Clone, Copy, Default)]
+struct GlobalState {
+    usage_page: u16,
+    report_size: u32,
+    report_count: u32,
+    report_id: u8,
+    logical_minimum: i32,
+    logical_maximum: i32,
+}
+
+/// Upper bound on usages expanded from one Usage Minimum/Maximum pair
+const MAX_USAGE_RANGE: u32 = 0x1_0000;
+
+/// Largest Report Size the kernel's HID parser accepts, in bits
+const MAX_REPORT_SIZE_BITS: u32 = 256;
+
+/// Largest Report Count the kernel's HID parser accepts (`HID_MAX_USAGES`)
+const MAX_REPORT_COUNT: u32 = 12288;
+
+/// Largest report payload the kernel accepts, in bits; the report ID byte
+/// takes the rest of `MAX_REPORT_LEN`
+const MAX_REPORT_BITS: usize = (MAX_REPORT_LEN - 1) * 8;
+
+/// Reports declared by a report descriptor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReportLayout {
+    reports: Vec<ReportInfo>,
+    fields: Vec<ReportField>,
+    uses_report_ids: bool,
+    top_level_usages: Vec<(u16, u16)>,
+}
+
+impl ReportLayout {
+    /// Parse a raw report descriptor
+    pub fn parse(descriptor: &[u8]) -> Result<Self> {
+        Self::from_items(&parse_items(descriptor)?)
+    }
+
+    /// `(usage page, usage)` of each top-level collection of a raw report
+    /// descriptor
+    ///
+    /// Cheaper than [`parse`](Self::parse) followed by
+    /// [`top_level_usages`](Self::top_level_usages) since no fields are built
+    /// and no usage ranges expanded; enumeration only needs this.
+    pub fn parse_top_level_usages(descriptor: &[u8]) -> Result<Vec<(u16, u16)>> {
+        let mut usages = Vec::new();
+        let mut usage_page = 0u16;
+        let mut stack = Vec::new();
+        let mut first_usage = None;
+        let mut depth = 0usize;
+
+        for item in parse_items(descriptor)? {
+            match (item.item_type, item.tag) {
+                (ItemType::Global, global::USAGE_PAGE) => {
+                    usage_page = item.unsigned_value() as u16;
+                }
+                (ItemType::Global, global::PUSH) => stack.push(usage_page),
+                (ItemType::Global, global::POP) => {
+                    usage_page = stack
+                        .pop()
+                        .ok_or_else(|| Error::Parse("Pop without matching Push".to_string()))?;
+                }
+                (ItemType::Local, local::USAGE) => {
+                    first_usage.get_or_insert(item.unsigned_value());
+                }
+                (ItemType::Main, main::COLLECTION) => {
+                    if depth == 0 {
+                        usages.push(collection_usage(first_usage, usage_page));
+                    }
+                    depth += 1;
+                }
+                (ItemType::Main, main::END_COLLECTION) => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+
+            if item.item_type == ItemType::Main {
+                first_usage = None;
+            }
+        }
+
+        Ok(usages)
+    }
+
+    /// Build the layout from parsed descriptor items
+    pub fn from_items(items: &[Item]) -> Result<Self> {
+        let mut layout = ReportLayout::default();
+        let mut state = GlobalState::default();
+        let mut stack = Vec::new();
+        let mut first_usage = None;
+        let mut usages = Vec::new();
+        let mut usage_minimum = None;
+        let mut depth = 0usize;
+
+        for item in items {
+            // Usages shorter than 4 bytes take the current usage page
+            let extended_usage = || {
+                let usage = item.unsigned_value();
+                if item.data.len() == 4 {
+                    usage
+                } else {
+                    u32::from(state.usage_page) << 16 | usage
+                }
+            };
+
+            match (item.item_type, item.tag) {
+                (ItemType::Global, global::USAGE_PAGE) => {
+                    state.usage_page = item.unsigned_value() as u16;
+                }
+                (ItemType::Local, local::USAGE) => {
+                    first_usage.get_or_insert(item.unsigned_value());
+                    usages.push(extended_usage());
+                }
+                (ItemType::Local, local::USAGE_MINIMUM) => {
+                    usage_minimum = Some(extended_usage());
+                }
+                (ItemType::Local, local::USAGE_MAXIMUM) => {
+                    if let Some(minimum) = usage_minimum.take() {
+                        let maximum =
+                            extended_usage().min(minimum.saturating_add(MAX_USAGE_RANGE - 1));
+                        usages.extend(minimum..=maximum);
+                    }
+                }
+                (ItemType::Global, global::LOGICAL_MINIMUM) => {
+                    state.logical_minimum = item.signed_value();
+                }
+                (ItemType::Global, global::LOGICAL_MAXIMUM) => {
+                    // A short Logical Maximum such as 0xFF is commonly meant
+                    // as unsigned when the minimum is not negative
+                    state.logical_maximum = match item.signed_value() {
+                        value if value < 0 && state.logical_minimum >= 0 && item.data.len() < 4 => {
+                            item.unsigned_value() as i32
+                        }
+                        value => value,
+                    };
+                }
+                (ItemType::Global, global::REPORT_SIZE) => {
+                    let size = item.unsigned_value();
+                    if size > MAX_REPORT_SIZE_BITS {
+                        return Err(Error::Parse(format!(
+                            "Report Size {size} exceeds {MAX_REPORT_SIZE_BITS} bits"
+                        )));
+                    }
+                    state.report_size = size;
+                }
+                (ItemType::Global, global::REPORT_COUNT) => {
+                    let count = item.unsigned_value();
+                    if count > MAX_REPORT_COUNT {
+                        return Err(Error::Parse(format!(
+                            "Report Count {count} exceeds {MAX_REPORT_COUNT}"
+                        )));
+                    }
+                    state.report_count = count;
+                }
+                (ItemType::Global, global::REPORT_ID) => {
+                    let id = item.unsigned_value();
+                    state.report_id = u8::try_from(id)
+                        .map_err(|_| Error::Parse(format!("Report ID {id} out of range")))?;
+                    layout.uses_report_ids = true;
+                }
+                (ItemType::Global, global::PUSH) => stack.push(state),
+                (ItemType::Global, global::POP) => {
+                    state = stack
+                        .pop()
+                        .ok_or_else(|| Error::Parse("Pop without matching Push".to_string()))?;
+                }
+                (ItemType::Main, main::INPUT) => {
+                    layout.add_field(ReportType::Input, &state, item, &mut usages)?;
+                }
+                (ItemType::Main, main::OUTPUT) => {
+                    layout.add_field(ReportType::Output, &state, item, &mut usages)?;
+                }
+                (ItemType::Main, main::FEATURE) => {
+                    layout.add_field(ReportType::Feature, &state, item, &mut usages)?;
+                }
+                (ItemType::Main, main::COLLECTION) => {
+                    if depth == 0 {
+                        layout
+                            .top_level_usages
+                            .push(collection_usage(first_usage, state.usage_page));
+                    }
+                    depth += 1;
+                }
+                (ItemType::Main, main::END_COLLECTION) => {
+                    depth = depth.saturating_sub(1);
+                }
+                _ => {}
+            }
+
+            // Local items only apply up to the next main item
+            if item.item_type == ItemType::Main {
+                first_usage = None;
+                usages.clear();
+                usage_minimum = None;
+            }
+        }
+
+        Ok(layout)
+    }
+
+    fn add_field(
+        &mut self,
+        report_type: ReportType,
+        state: &GlobalState,
+        item: &Item,
+        usages: &mut Vec<u32>,
+    ) -> Result<()> {
+        let bits = (state.report_size as usize).saturating_mul(state.report_count as usize);
+        let index = match self
+            .reports
+            .iter()
+            .position(|r| r.report_type == report_type && r.report_id == state.report_id)
+        {
+            Some(index) => index,
+            None => {
+                self.reports.push(ReportInfo {
+                    report_type,
+                    report_id: state.report_id,
+                    bits: 0,
+                });
+                self.reports.len() - 1
+            }
+        };
+        let report = &mut self.reports[index];
+
+        let bit_offset = report.bits;
+        report.bits = bit_offset.saturating_add(bits);
+        if report.bits > MAX_REPORT_BITS {
+            return Err(Error::Parse(format!(
+                "{report_type:?} report {id} is longer than {MAX_REPORT_LEN} bytes",
+                id = state.report_id
+            )));
+        }
+
+        self.fields.push(ReportField {
+            report_type,
+            report_id: state.report_id,
+            bit_offset,
+            report_size: state.report_size,
+            report_count: state.report_count,
+            flags: MainFlags(item.unsigned_value()),
+            usages: std::mem::take(usages),
+            logical_minimum: state.logical_minimum,
+            logical_maximum: state.logical_maximum,
+        });
+        Ok(())
+    }
+
+    /// Whether the device uses numbered reports (any Report ID item present)
+    pub fn uses_report_ids(&self) -> bool {
+        self.uses_report_ids
+    }
+
+    /// `(usage page, usage)` of each top-level collection
+    pub fn top_level_usages(&self) -> &[(u16, u16)] {
+        &self.top_level_usages
+    }
+
+    /// All reports in descriptor order
+    pub fn reports(&self) -> &[ReportInfo] {
+        &self.reports
+    }
+
+    /// Look up a report by type and ID
+    pub fn report(&self, report_type: ReportType, report_id: u8) -> Option<&ReportInfo> {
+        self.reports
+            .iter()
+            .find(|r| r.report_type == report_type && r.report_id == report_id)
+    }
+
+    /// Fields of one report in the order they appear in the payload
+    pub fn fields(
+        &self,
+        report_type: ReportType,
+        report_id: u8,
+    ) -> impl Iterator<Item = &ReportField> {
+        self.fields
+            .iter()
+            .filter(move |f| f.report_type == report_type && f.report_id == report_id)
+    }
+
+    /// Decode a report payload (without report ID byte) into usage values
+    ///
+    /// Constant padding fields are skipped.
+    pub fn decode(
+        &self,
+        report_type: ReportType,
+        report_id: u8,
+        payload: &[u8],
+    ) -> Vec<FieldValue> {
+        self.fields(report_type, report_id)
+            .filter(|field| !field.is_constant())
+            .flat_map(|field| field.decode(payload))
+            .collect()
+    }
+
+    /// Largest payload length in bytes for the given report type
+    pub fn max_payload_len(&self, report_type: ReportType) -> usize {
+        self.reports
+            .iter()
+            .filter(|r| r.report_type == report_type)
+            .map(ReportInfo::payload_len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Buffer length for reading any input report from hidraw
+    ///
+    /// Includes the report ID prefix when the device uses numbered reports.
+    pub fn input_buffer_len(&self) -> usize {
+        self.max_payload_len(ReportType::Input) + usize::from(self.uses_report_ids)
+    }
+
+    /// Buffer length for writing any output report to hidraw
+    ///
+    /// hidraw always expects the report ID as the first byte of a write
+    /// (0 for devices without numbered reports), so this includes it.
+    pub fn output_buffer_len(&self) -> usize {
+        self.max_payload_len(ReportType::Output) + 1
+    }
+
+    /// Buffer length for feature report `report_id`, including the report ID byte
+    pub fn feature_buffer_len(&self, report_id: u8) -> Result<usize> {
+        self.report(ReportType::Feature, report_id)
+            .map(|report| report.payload_len() + 1)
+            .ok_or_else(|| {
+                Error::InvalidParameter(format!(
+                    "Feature report {report_id} is not declared in the report descriptor"
+                ))
+            })
+    }
+}
+
+/// `(usage page, usage)` of a collection from its first Usage item
+fn collection_usage(first_usage: Option<u32>, usage_page: u16) -> (u16, u16) {
+    let usage = first_usage.unwrap_or(0);
+    // Extended usages carry the page in the upper 16 bits
+    let page = if usage > 0xFFFF {
+        (usage >> 16) as u16
+    } else {
+        usage_page
+    };
+    (page, usage as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{CollectionType, DescriptorBuilder, MainFlags};
+
+    #[test]
+    fn test_unnumbered_layout() -> Result<()> {
+        // 64-byte vendor reports in both directions, as used by Coldcard
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0xFF00)
+            .usage(0x01)
+            .collection(CollectionType::Application, |c| {
+                c.logical_minimum(0)
+                    .logical_maximum(255)
+                    .report_size(8)
+                    .report_count(64)
+                    .usage(0x01)
+                    .input(MainFlags::VARIABLE)
+                    .usage(0x02)
+                    .output(MainFlags::VARIABLE)
+            })
+            .to_bytes();
+        let layout = ReportLayout::parse(&bytes)?;
+
+        assert!(!layout.uses_report_ids());
+        assert_eq!(layout.top_level_usages(), &[(0xFF00, 0x01)]);
+        assert_eq!(layout.max_payload_len(ReportType::Input), 64);
+        assert_eq!(layout.max_payload_len(ReportType::Output), 64);
+        assert_eq!(layout.max_payload_len(ReportType::Feature), 0);
+        assert_eq!(
+            layout
+                .report(ReportType::Input, 0)
+                .map(ReportInfo::payload_len),
+            Some(64)
+        );
+        assert_eq!(layout.input_buffer_len(), 64);
+        assert_eq!(layout.output_buffer_len(), 65);
+        assert!(matches!(
+            layout.feature_buffer_len(0),
+            Err(Error::InvalidParameter(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_numbered_layout_with_push_pop() -> Result<()> {
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0x01)
+            .collection(CollectionType::Application, |c| {
+                c.report_id(1)
+                    .report_size(1)
+                    .report_count(3)
+                    .input(MainFlags::VARIABLE)
+                    .report_count(5)
+                    .input(MainFlags::CONSTANT)
+                    .push()
+                    .report_size(8)
+                    .report_count(16)
+                    .report_id(2)
+                    .feature(MainFlags::VARIABLE)
+                    .pop()
+                    .report_count(4)
+                    .output(MainFlags::VARIABLE)
+            })
+            .to_bytes();
+        let layout = ReportLayout::parse(&bytes)?;
+
+        assert!(layout.uses_report_ids());
+        assert_eq!(
+            layout.report(ReportType::Input, 1),
+            Some(&ReportInfo {
+                report_type: ReportType::Input,
+                report_id: 1,
+                bits: 8,
+            })
+        );
+        assert_eq!(
+            layout
+                .report(ReportType::Feature, 2)
+                .map(ReportInfo::payload_len),
+            Some(16)
+        );
+        // Pop restored report ID 1 and a 1-bit report size
+        assert_eq!(
+            layout
+                .report(ReportType::Output, 1)
+                .map(ReportInfo::payload_len),
+            Some(1)
+        );
+        assert_eq!(layout.input_buffer_len(), 2);
+        assert_eq!(layout.output_buffer_len(), 2);
+        assert_eq!(layout.feature_buffer_len(2)?, 17);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_oversized_reports() -> Result<()> {
+        // Report Size and Report Count 0xFFFFFFFF, then three Input items
+        let bytes = [
+            0x77, 0xFF, 0xFF, 0xFF, 0xFF, 0x97, 0xFF, 0xFF, 0xFF, 0xFF, 0x81, 0x02, 0x81, 0x02,
+            0x81, 0x02,
+        ];
+        assert!(matches!(ReportLayout::parse(&bytes), Err(Error::Parse(_))));
+
+        let oversized = |size: u32, count: u32| {
+            DescriptorBuilder::new()
+                .report_size(size)
+                .report_count(count)
+                .input(MainFlags::VARIABLE)
+                .to_bytes()
+        };
+        assert!(matches!(
+            ReportLayout::parse(&oversized(255, 0xFFFF_FFFF)),
+            Err(Error::Parse(_))
+        ));
+        assert!(matches!(
+            ReportLayout::parse(&oversized(257, 1)),
+            Err(Error::Parse(_))
+        ));
+        // Each field is within the limits, but together they exceed 4096 bytes
+        assert!(matches!(
+            ReportLayout::parse(&oversized(8, 4096)),
+            Err(Error::Parse(_))
+        ));
+        assert_eq!(
+            ReportLayout::parse(&oversized(8, 4095))?.input_buffer_len(),
+            MAX_REPORT_LEN - 1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_mouse_report() -> Result<()> {
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0x01)
+            .usage(0x02)
+            .collection(CollectionType::Application, |c| {
+                c.usage_page(0x09)
+                    .usage_minimum(1)
+                    .usage_maximum(3)
+                    .logical_minimum(0)
+                    .logical_maximum(1)
+                    .report_size(1)
+                    .report_count(3)
+                    .input(MainFlags::VARIABLE)
+                    .report_count(5)
+                    .input(MainFlags::CONSTANT)
+                    .usage_page(0x01)
+                    .usage(0x30)
+                    .usage(0x31)
+                    .logical_minimum(-127)
+                    .logical_maximum(127)
+                    .report_size(8)
+                    .report_count(2)
+                    .input(MainFlags::VARIABLE | MainFlags::RELATIVE)
+            })
+            .to_bytes();
+        let layout = ReportLayout::parse(&bytes)?;
+
+        let fields: Vec<_> = layout.fields(ReportType::Input, 0).collect();
+        assert_eq!(fields.len(), 3);
+        assert_eq!(fields[2].bit_offset, 8);
+        assert_eq!(fields[0].usages, [0x0009_0001, 0x0009_0002, 0x0009_0003]);
+
+        let values = layout.decode(ReportType::Input, 0, &[0b0000_0101, 0xFF, 0x05]);
+        let text: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            text,
+            [
+                "0009:0001=1",
+                "0009:0002=0",
+                "0009:0003=1",
+                "0001:0030=-1",
+                "0001:0031=5"
+            ]
+        );
+
+        // Truncated payloads decode as far as they go
+        assert_eq!(layout.decode(ReportType::Input, 0, &[0x01]).len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_array_field() -> Result<()> {
+        // Keyboard-style key array; 1-byte Logical Maximum 0xFF means 255
+        let bytes = [
+            0x05, 0x07, // Usage Page (Keyboard)
+            0x19, 0x00, // Usage Minimum (0)
+            0x29, 0xFF, // Usage Maximum (255)
+            0x15, 0x00, // Logical Minimum (0)
+            0x25, 0xFF, // Logical Maximum (255)
+            0x75, 0x08, // Report Size (8)
+            0x95, 0x03, // Report Count (3)
+            0x81, 0x00, // Input (Data, Array)
+        ];
+        let layout = ReportLayout::parse(&bytes)?;
+        let field = layout
+            .fields(ReportType::Input, 0)
+            .next()
+            .ok_or_else(|| Error::Parse("missing field".to_string()))?;
+
+        assert_eq!(field.logical_maximum, 255);
+        assert!(!field.is_variable());
+        assert_eq!(
+            layout.decode(ReportType::Input, 0, &[0x04, 0x00, 0xE1]),
+            [
+                FieldValue {
+                    usage: 0x0007_0004,
+                    value: 1
+                },
+                FieldValue {
+                    usage: 0x0007_00E1,
+                    value: 1
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_extreme_ranges() -> Result<()> {
+        let bytes = [
+            0x0B, 0xFF, 0xFF, 0xFF, 0xFF, // Usage Minimum (0xFFFFFFFF)
+            0x2B, 0xFF, 0xFF, 0xFF, 0xFF, // Usage Maximum (0xFFFFFFFF)
+            0x17, 0x00, 0x00, 0x00, 0x80, // Logical Minimum (i32::MIN)
+            0x27, 0xFF, 0xFF, 0xFF, 0x7F, // Logical Maximum (i32::MAX)
+            0x75, 0x20, // Report Size (32)
+            0x95, 0x01, // Report Count (1)
+            0x81, 0x00, // Input (Data, Array)
+        ];
+        let layout = ReportLayout::parse(&bytes)?;
+        let field = layout
+            .fields(ReportType::Input, 0)
+            .next()
+            .ok_or_else(|| Error::Parse("missing field".to_string()))?;
+        assert_eq!(field.usages, [0xFFFF_FFFF]);
+
+        // i32::MAX - i32::MIN selects past the only usage
+        assert!(field.decode(&[0xFF, 0xFF, 0xFF, 0x7F]).is_empty());
+        assert_eq!(
+            field.decode(&[0x00, 0x00, 0x00, 0x80]),
+            [FieldValue {
+                usage: 0xFFFF_FFFF,
+                value: 1
+            }]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_top_level_usages() -> Result<()> {
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0x01)
+            .usage(0x06)
+            .collection(CollectionType::Application, |c| {
+                c.usage_page(0x07)
+                    .usage_minimum(0)
+                    .usage_maximum(0xFFFF)
+                    .report_size(8)
+                    .report_count(6)
+                    .input(MainFlags::DATA)
+            })
+            .push()
+            .usage_page(0xFF00)
+            .usage(0x01)
+            .collection(CollectionType::Application, |c| c)
+            .pop()
+            .to_bytes();
+
+        let usages = ReportLayout::parse_top_level_usages(&bytes)?;
+        assert_eq!(usages, [(0x01, 0x06), (0xFF00, 0x01)]);
+        assert_eq!(usages, ReportLayout::parse(&bytes)?.top_level_usages());
+        Ok(())
+    }
+
+    #[test]
+    fn test_pop_without_push() -> Result<()> {
+        let bytes = DescriptorBuilder::new().pop().to_bytes();
+        assert!(ReportLayout::parse(&bytes).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+//! HID report descriptor parsing and construction
+
+mod builder;
+mod item;
+mod layout;
+
+pub use builder::{CollectionType, DescriptorBuilder, MainFlags};
+pub use item::{Item, ItemType, global, local, main, parse_items};
+pub use layout::{FieldValue, ReportField, ReportInfo, ReportLayout};
@@ -1,16 +1,72 @@
 //! High-level HID device interface
 
-use crate::hidraw::HidrawDevice;
+use crate::capture::{
+    CaptureWriter, HidToolsWriter, PcapngConfig, PcapngWriter, RecordedDevice, RecordingDevice,
+    capture_header,
+};
+use crate::descriptor::{DescriptorBuilder, Item, ReportLayout, parse_items};
+use crate::hidraw::{HidrawDevice, MAX_REPORT_LEN};
+use crate::protocol::{HidReport, ReportType};
 use crate::{Error, Result};
 use std::path::PathBuf;
+use std::sync::OnceLock;
 use std::time::Duration;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
+/// Bus a HID device is attached to (`BUS_*` values from `<linux/input.h>`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BusType {
+    Usb,
+    Bluetooth,
+    I2c,
+    Spi,
+    Virtual,
+    /// Bus type not known (e.g. sysfs information unavailable)
+    #[default]
+    Unknown,
+    /// Any other bus, by raw value
+    Other(u16),
+}
+
+impl BusType {
+    /// Convert from the kernel's raw bus number
+    pub fn from_raw(raw: u16) -> Self {
+        match raw {
+            0x03 => BusType::Usb,
+            0x05 => BusType::Bluetooth,
+            0x06 => BusType::Virtual,
+            0x18 => BusType::I2c,
+            0x1C => BusType::Spi,
+            0x00 => BusType::Unknown,
+            other => BusType::Other(other),
+        }
+    }
+
+    /// The kernel's raw bus number
+    pub fn raw(self) -> u16 {
+        match self {
+            BusType::Usb => 0x03,
+            BusType::Bluetooth => 0x05,
+            BusType::Virtual => 0x06,
+            BusType::I2c => 0x18,
+            BusType::Spi => 0x1C,
+            BusType::Unknown => 0x00,
+            BusType::Other(other) => other,
+        }
+    }
+}
+
 /// Information about a HID device
+///
+/// Fields are added as sysfs exposes more about devices, so outside this
+/// crate it can't be built with a struct literal; start from
+/// [`DeviceInfo::new`] instead.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
 pub struct DeviceInfo {
     /// Device path (e.g., /dev/hidraw0)
     pub path: PathBuf,
@@ -26,14 +82,51 @@ pub struct DeviceInfo {
     pub product: Option<String>,
     /// Interface number
     pub interface_number: i32,
+    /// Bus the device is attached to
+    pub bus_type: BusType,
+    /// Usage page of the first top-level collection (0 if unknown)
+    pub usage_page: u16,
+    /// Usage of the first top-level collection (0 if unknown)
+    pub usage: u16,
+    /// Physical path reported by the kernel (e.g. "usb-0000:00:14.0-1/input0")
+    pub physical_path: Option<String>,
 }
 
 impl DeviceInfo {
+    /// Device information with only a path and IDs; everything else unknown
+    pub fn new(path: impl Into<PathBuf>, vendor_id: u16, product_id: u16) -> Self {
+        Self {
+            path: path.into(),
+            vendor_id,
+            product_id,
+            serial_number: None,
+            manufacturer: None,
+            product: None,
+            interface_number: 0,
+            bus_type: BusType::Unknown,
+            usage_page: 0,
+            usage: 0,
+            physical_path: None,
+        }
+    }
+
     /// Check if this device matches the given vendor and product IDs
     pub fn matches(&self, vendor_id: u16, product_id: u16) -> bool {
         self.vendor_id == vendor_id && self.product_id == product_id
     }
 
+    /// Stable identity of this device, see [`crate::DeviceId`]
+    pub fn id(&self) -> crate::DeviceId {
+        crate::DeviceId::from_info(self)
+    }
+
+    /// Read extended sysfs metadata (USB topology, speed, driver, power)
+    ///
+    /// Not collected by [`crate::enumerate`] to keep enumeration fast.
+    pub fn details(&self) -> Result<crate::DeviceDetails> {
+        crate::hidraw::get_device_details(&self.path)
+    }
+
     /// Get a display name for the device
     pub fn display_name(&self) -> String {
         if let Some(product) = &self.product {
@@ -52,6 +145,7 @@ pub struct HidDevice {
     raw: HidrawDevice,
     info: DeviceInfo,
     read_timeout: Option<Duration>,
+    layout: OnceLock<ReportLayout>,
 }
 
 impl HidDevice {
@@ -62,6 +156,7 @@ impl HidDevice {
             raw,
             info: info.clone(),
             read_timeout: None,
+            layout: OnceLock::new(),
         })
     }
 
@@ -77,6 +172,7 @@ impl HidDevice {
             raw,
             info,
             read_timeout: None,
+            layout: OnceLock::new(),
         })
     }
 
@@ -88,16 +184,131 @@ impl HidDevice {
         Self::open(&device_info)
     }
 
+    /// A device backed by one end of a socket pair, for testing code built
+    /// on `HidDevice` without hardware
+    ///
+    /// Bytes written to the returned peer arrive as input reports, writes to
+    /// the device can be read from the peer, and dropping the peer unplugs
+    /// the device. Ioctls such as feature reports fail.
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn socket_pair(info: DeviceInfo) -> Result<(Self, std::os::unix::net::UnixStream)> {
+        let (raw, peer) = HidrawDevice::socket_pair(&info.path)?;
+        Ok((Self::from_raw(raw, info), peer))
+    }
+
+    /// Assemble a device from an open handle, e.g. a socket pair in tests
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn from_raw(raw: HidrawDevice, info: DeviceInfo) -> Self {
+        Self {
+            raw,
+            info,
+            read_timeout: None,
+            layout: OnceLock::new(),
+        }
+    }
+
+    /// Open the device a [`DeviceId`] currently refers to
+    pub fn open_by_id(id: &crate::DeviceId) -> Result<Self> {
+        let path = id.resolve()?.ok_or(Error::DeviceNotFound)?;
+        let raw = HidrawDevice::open(&path)?;
+        let info = crate::hidraw::get_device_info(&path)?;
+
+        Ok(Self {
+            raw,
+            info,
+            read_timeout: None,
+            layout: OnceLock::new(),
+        })
+    }
+
+    /// Read extended sysfs metadata, see [`DeviceInfo::details`]
+    pub fn details(&self) -> Result<crate::DeviceDetails> {
+        self.info.details()
+    }
+
+    /// Stable identity of this device, for reopening it after re-enumeration
+    pub fn device_id(&self) -> crate::DeviceId {
+        crate::DeviceId::from_info(&self.info)
+    }
+
+    /// Open the first device matching a filter
+    pub fn open_matching(filter: &crate::DeviceFilter) -> Result<Self> {
+        let devices = crate::enumerate_with(filter)?;
+        let device_info = devices.into_iter().next().ok_or(Error::DeviceNotFound)?;
+
+        Self::open(&device_info)
+    }
+
     /// Get device information
     pub fn info(&self) -> &DeviceInfo {
         &self.info
     }
 
+    /// Wrap the device so every operation is logged to `sink`
+    ///
+    /// See [`crate::capture`] for the file format and replay support.
+    pub fn with_recorder<W: std::io::Write>(
+        self,
+        sink: W,
+    ) -> Result<RecordingDevice<CaptureWriter<W>>> {
+        let writer = CaptureWriter::new(sink, &capture_header(&self))?;
+        Ok(RecordingDevice::new(self, writer))
+    }
+
+    /// Wrap the device so every operation is written to `sink` as pcapng
+    ///
+    /// The capture uses synthesized usbmon records and opens in Wireshark.
+    pub fn with_pcapng<W: std::io::Write>(
+        self,
+        sink: W,
+    ) -> Result<RecordingDevice<PcapngWriter<W>>> {
+        let writer = PcapngWriter::new(sink, PcapngConfig::for_device(&self.info))?;
+        Ok(RecordingDevice::new(self, writer))
+    }
+
+    /// Wrap the device so input reports are written to `sink` in the
+    /// hid-tools text format read by `hid-replay`
+    pub fn with_hid_recorder<W: std::io::Write>(
+        self,
+        sink: W,
+    ) -> Result<RecordingDevice<HidToolsWriter<W>>> {
+        let writer = HidToolsWriter::new(sink, &RecordedDevice::for_device(&self)?)?;
+        Ok(RecordingDevice::new(self, writer))
+    }
+
+    /// Open a second handle on the same device, e.g. for a writer thread
+    ///
+    /// Shares the file descriptor's open file description (see
+    /// [`HidrawDevice::try_clone`]), so one thread can block in
+    /// [`read`](Self::read) while another calls [`write`](Self::write).
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            raw: self.raw.try_clone()?,
+            info: self.info.clone(),
+            read_timeout: self.read_timeout,
+            layout: self.layout.clone(),
+        })
+    }
+
     /// Set read timeout
     pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
         self.read_timeout = timeout;
     }
 
+    /// Timeout used by [`read`](Self::read), `None` when it blocks
+    pub fn get_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    /// Switch the file descriptor between blocking and non-blocking mode
+    ///
+    /// In non-blocking mode `read` and `write` return
+    /// `Error::Io` with `io::ErrorKind::WouldBlock` instead of waiting.
+    /// Timed reads and writes are unaffected since they poll first.
+    pub fn set_nonblocking(&mut self, nonblocking: bool) -> Result<()> {
+        self.raw.set_nonblocking(nonblocking)
+    }
+
     /// Read data from the device
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         if let Some(timeout) = self.read_timeout {
@@ -132,6 +343,20 @@ impl HidDevice {
         self.raw.send_feature_report(data)
     }
 
+    /// Get the current input report without waiting for the interrupt pipe
+    ///
+    /// Requires Linux 5.11 or newer; older kernels return `Error::NotSupported`.
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.raw.get_input_report(report_id, buf)
+    }
+
+    /// Send an output report over the control pipe
+    ///
+    /// Requires Linux 5.11 or newer; older kernels return `Error::NotSupported`.
+    pub fn send_output_report(&mut self, data: &[u8]) -> Result<()> {
+        self.raw.send_output_report(data)
+    }
+
     /// Get the raw file descriptor (for advanced usage)
     pub fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
         use std::os::unix::io::AsRawFd;
@@ -163,16 +388,19 @@ impl HidDevice {
             return Err(Error::Timeout);
         }
 
-        // Check for error conditions
+        // hidraw reports POLLHUP | POLLERR once the device is unplugged, but
+        // still hands out reports queued before that; only give up once the
+        // queue is drained
         let revents = fds[0].revents();
-        if revents.contains(PollFlags::ERR) {
-            return Err(Error::io_error("Poll error on device"));
-        }
-        if revents.contains(PollFlags::HUP) {
+        let hangup = revents.intersects(PollFlags::HUP | PollFlags::ERR);
+        if hangup && !revents.contains(PollFlags::IN) {
             return Err(Error::Disconnected);
         }
 
-        self.raw.read(buf)
+        match self.raw.read(buf)? {
+            0 if hangup => Err(Error::Disconnected),
+            n => Ok(n),
+        }
     }
 
     /// Internal implementation of write with timeout
@@ -194,18 +422,23 @@ impl HidDevice {
             return Err(Error::Timeout);
         }
 
-        // Check for error conditions
+        // hidraw reports POLLHUP | POLLERR once the device is unplugged
         let revents = fds[0].revents();
-        if revents.contains(PollFlags::ERR) {
-            return Err(Error::io_error("Poll error on device"));
-        }
-        if revents.contains(PollFlags::HUP) {
+        if revents.intersects(PollFlags::HUP | PollFlags::ERR) {
             return Err(Error::Disconnected);
         }
 
         self.raw.write(data)
     }
 
+    /// Get the device name as reported by the kernel
+    ///
+    /// Usually the manufacturer and product strings, e.g.
+    /// "Logitech USB Receiver".
+    pub fn get_name(&self) -> Result<String> {
+        self.raw.get_raw_name()
+    }
+
     /// Get the physical location of the device (e.g., USB port path)
     ///
     /// This returns a string describing the physical path to the device,
@@ -233,6 +466,105 @@ impl HidDevice {
             data: raw_desc.value.to_vec(),
         })
     }
+
+    /// Get the report layout parsed from the report descriptor
+    ///
+    /// The descriptor is fetched and parsed on first use and cached.
+    pub fn report_layout(&self) -> Result<&ReportLayout> {
+        if let Some(layout) = self.layout.get() {
+            return Ok(layout);
+        }
+
+        let layout = self.get_report_descriptor()?.layout()?;
+        Ok(self.layout.get_or_init(|| layout))
+    }
+
+    /// Maximum length of an input report as returned by `read`
+    ///
+    /// Includes the report ID prefix when the device uses numbered reports.
+    pub fn max_input_report_len(&self) -> Result<usize> {
+        Ok(self.report_layout()?.input_buffer_len())
+    }
+
+    /// Maximum length of an output report as passed to `write`
+    ///
+    /// hidraw always expects the report ID as the first byte of a write
+    /// (0 for devices without numbered reports), so this includes it.
+    pub fn max_output_report_len(&self) -> Result<usize> {
+        Ok(self.report_layout()?.output_buffer_len())
+    }
+
+    /// Buffer length for feature report `report_id`, including the report ID byte
+    pub fn feature_report_len(&self, report_id: u8) -> Result<usize> {
+        self.report_layout()?.feature_buffer_len(report_id)
+    }
+
+    /// Whether the device uses numbered reports, according to its descriptor
+    pub fn uses_report_ids(&self) -> Result<bool> {
+        Ok(self.report_layout()?.uses_report_ids())
+    }
+
+    /// Read one input report, sized from the report descriptor
+    ///
+    /// The report ID prefix is stripped for numbered reports; devices without
+    /// numbered reports yield report ID 0. Honors the configured read timeout.
+    pub fn read_report(&mut self) -> Result<HidReport> {
+        let uses_report_ids = self.uses_report_ids()?;
+        let mut buf = vec![0u8; self.max_input_report_len()?.clamp(1, MAX_REPORT_LEN)];
+        let n = self.read(&mut buf)?;
+        buf.truncate(n);
+
+        HidReport::from_report_bytes(&buf, ReportType::Input, uses_report_ids)
+    }
+
+    /// Send an output or feature report
+    ///
+    /// The report ID prefix is applied according to the device's report
+    /// numbering, so `report.data` holds only the payload. Output reports go
+    /// over the interrupt pipe (`write`), feature reports via SET_FEATURE.
+    /// Returns the number of bytes handed to the kernel, including the prefix.
+    pub fn write_report(&mut self, report: &HidReport) -> Result<usize> {
+        let bytes = report.to_report_bytes(self.uses_report_ids()?)?;
+
+        match report.report_type {
+            ReportType::Output => self.write(&bytes),
+            ReportType::Feature => {
+                self.send_feature_report(&bytes)?;
+                Ok(bytes.len())
+            }
+            ReportType::Input => Err(Error::InvalidParameter(
+                "Input reports cannot be written to the device".to_string(),
+            )),
+        }
+    }
+
+    /// Get feature report `report_id`, sized from the report descriptor
+    ///
+    /// The returned report data excludes the report ID byte. Devices without
+    /// numbered reports only accept report ID 0.
+    pub fn get_feature(&mut self, report_id: u8) -> Result<HidReport> {
+        let len = self.feature_report_len(report_id)?;
+        get_feature_sized(report_id, len, |buf| {
+            self.get_feature_report(report_id, buf)
+        })
+    }
+}
+
+/// Fetch feature report `report_id` into a `len`-byte buffer with `get`
+///
+/// The report ID byte the kernel leaves at the start is not part of the
+/// returned report data.
+pub(crate) fn get_feature_sized(
+    report_id: u8,
+    len: usize,
+    get: impl FnOnce(&mut [u8]) -> Result<usize>,
+) -> Result<HidReport> {
+    let mut buf = vec![0u8; len.min(MAX_REPORT_LEN)];
+    let n = get(&mut buf)?;
+    buf.truncate(n);
+
+    let data = buf.get(1..).unwrap_or_default().to_vec();
+    Ok(HidReport::feature(report_id, data))
 }
 
 /// HID Report Descriptor
@@ -249,6 +581,14 @@ pub struct ReportDescriptor {
 }
 
 impl ReportDescriptor {
+    /// Create a descriptor from raw bytes
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            size: data.len(),
+            data,
+        }
+    }
+
     /// Get a slice of the valid descriptor data
     pub fn as_bytes(&self) -> &[u8] {
         &self.data[..self.size.min(self.data.len())]
@@ -258,6 +598,30 @@ impl ReportDescriptor {
     pub fn is_empty(&self) -> bool {
         self.size == 0
     }
+
+    /// Parse the descriptor into its items
+    pub fn items(&self) -> Result<Vec<Item>> {
+        parse_items(self.as_bytes())
+    }
+
+    /// Parse the report layout (report IDs and sizes) from the descriptor
+    pub fn layout(&self) -> Result<ReportLayout> {
+        ReportLayout::parse(self.as_bytes())
+    }
+
+    /// Rebuild the descriptor from its parsed items using minimal encodings
+    ///
+    /// For canonically encoded descriptors the result is byte-for-byte
+    /// identical to the original.
+    pub fn rebuild(&self) -> Result<ReportDescriptor> {
+        Ok(DescriptorBuilder::from_items(&self.items()?).build())
+    }
+}
+
+impl rustix::fd::AsFd for HidDevice {
+    fn as_fd(&self) -> rustix::fd::BorrowedFd<'_> {
+        self.raw.as_fd()
+    }
 }
 
 impl std::fmt::Debug for HidDevice {
@@ -0,0 +1,186 @@
+//! Stable device identity across re-enumeration
+//!
+//! `/dev/hidrawN` numbers are handed out in probe order and change whenever a
+//! device is replugged or reset. A [`DeviceId`] instead records where the
+//! device sits in the USB topology (the sysfs interface name, such as
+//! `1-1.4:1.0`) together with its IDs and serial number, so the same physical
+//! device can be found again later, even among several identical devices.
+
+use crate::{DeviceInfo, Result};
+use std::fmt;
+use std::path::PathBuf;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Identity of a physical HID device that survives re-enumeration
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceId {
+    /// USB port path and interface (e.g. "1-1.4:1.0"), `None` for non-USB devices
+    pub port_path: Option<String>,
+    /// Vendor ID
+    pub vendor_id: u16,
+    /// Product ID
+    pub product_id: u16,
+    /// Serial number (if available)
+    pub serial_number: Option<String>,
+    /// Interface number
+    pub interface_number: i32,
+}
+
+impl DeviceId {
+    /// Build the identity of an enumerated device
+    ///
+    /// The port path is read from sysfs; it is `None` when the device is not
+    /// attached over USB or has already gone away.
+    pub fn from_info(info: &DeviceInfo) -> Self {
+        Self {
+            port_path: crate::hidraw::usb_port_path(&info.path),
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            serial_number: info.serial_number.clone(),
+            interface_number: info.interface_number,
+        }
+    }
+
+    /// Check whether `other` refers to the same physical device
+    ///
+    /// Vendor ID, product ID and interface must match. The port path and the
+    /// serial number are compared whenever both sides know them.
+    pub fn matches(&self, other: &DeviceId) -> bool {
+        self.same_model(other)
+            && both_equal(&self.port_path, &other.port_path)
+            && both_equal(&self.serial_number, &other.serial_number)
+    }
+
+    fn same_model(&self, other: &DeviceId) -> bool {
+        self.vendor_id == other.vendor_id
+            && self.product_id == other.product_id
+            && self.interface_number == other.interface_number
+    }
+
+    /// Find the device node this identity currently refers to
+    ///
+    /// A device on the recorded port is preferred. If there is none and the
+    /// identity has a serial number, a device with that serial on another
+    /// port is accepted, so moving the device to a different socket still
+    /// works. Returns `Ok(None)` when no device matches; enumeration errors
+    /// are passed on.
+    pub fn resolve(&self) -> Result<Option<PathBuf>> {
+        let candidates: Vec<(DeviceId, PathBuf)> = crate::enumerate()?
+            .into_iter()
+            .map(|info| (DeviceId::from_info(&info), info.path))
+            .collect();
+        Ok(self.select(&candidates).cloned())
+    }
+
+    /// Pick the best match among `(identity, path)` candidates
+    fn select<'a>(&self, candidates: &'a [(DeviceId, PathBuf)]) -> Option<&'a PathBuf> {
+        let exact = candidates.iter().find(|(id, _)| self.matches(id));
+        let moved = || {
+            let serial = self.serial_number.as_ref()?;
+            candidates
+                .iter()
+                .find(|(id, _)| self.same_model(id) && id.serial_number.as_ref() == Some(serial))
+        };
+
+        exact.or_else(moved).map(|(_, path)| path)
+    }
+}
+
+/// Equal when both are known, otherwise not a mismatch
+fn both_equal(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a == b,
+        _ => true,
+    }
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{vid:04x}:{pid:04x}",
+            vid = self.vendor_id,
+            pid = self.product_id
+        )?;
+        match &self.port_path {
+            Some(port) => write!(f, " at {port}")?,
+            None => write!(f, " interface {n}", n = self.interface_number)?,
+        }
+        if let Some(serial) = &self.serial_number {
+            write!(f, " serial {serial}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(port: Option<&str>, serial: Option<&str>) -> DeviceId {
+        DeviceId {
+            port_path: port.map(str::to_string),
+            vendor_id: 0xd13e,
+            product_id: 0xcc10,
+            serial_number: serial.map(str::to_string),
+            interface_number: 0,
+        }
+    }
+
+    #[test]
+    fn test_select_prefers_port() -> Result<()> {
+        // Two identical devices without serial numbers
+        let candidates = vec![
+            (id(Some("1-1.3:1.0"), None), PathBuf::from("/dev/hidraw2")),
+            (id(Some("1-1.4:1.0"), None), PathBuf::from("/dev/hidraw5")),
+        ];
+
+        let wanted = id(Some("1-1.4:1.0"), None);
+        assert_eq!(
+            wanted.select(&candidates),
+            Some(&PathBuf::from("/dev/hidraw5"))
+        );
+        assert_eq!(id(Some("2-1:1.0"), None).select(&candidates), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_select_follows_serial_to_new_port() -> Result<()> {
+        let candidates = vec![
+            (
+                id(Some("1-1.3:1.0"), Some("AAA")),
+                PathBuf::from("/dev/hidraw2"),
+            ),
+            (
+                id(Some("1-2:1.0"), Some("BBB")),
+                PathBuf::from("/dev/hidraw7"),
+            ),
+        ];
+
+        // BBB used to be on 1-1.4 and was moved
+        let wanted = id(Some("1-1.4:1.0"), Some("BBB"));
+        assert_eq!(
+            wanted.select(&candidates),
+            Some(&PathBuf::from("/dev/hidraw7"))
+        );
+
+        // Same port but a different serial is a different device
+        assert!(!id(Some("1-1.3:1.0"), Some("BBB")).matches(&candidates[0].0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display() -> Result<()> {
+        assert_eq!(
+            id(Some("1-1.4:1.0"), Some("ABC")).to_string(),
+            "d13e:cc10 at 1-1.4:1.0 serial ABC"
+        );
+        assert_eq!(id(None, None).to_string(), "d13e:cc10 interface 0");
+        Ok(())
+    }
+}
@@ -0,0 +1,495 @@
+//! Device filters for enumeration and opening
+//!
+//! A [`DeviceFilter`] combines any number of criteria; a device must satisfy
+//! all of them. Filters can be built in code or parsed from a compact string
+//! such as `vid=d13e,pid=cc10,serial=ABC*`, suitable for config files and
+//! command-line arguments.
+//!
+//! String keys (numbers are hexadecimal unless noted; list values use `|`):
+//!
+//! | key            | meaning                                   |
+//! |----------------|-------------------------------------------|
+//! | `vid`          | vendor ID list, e.g. `vid=d13e\|1209`      |
+//! | `pid`          | product ID list                           |
+//! | `serial`       | serial number, exact or glob (`*`, `?`)   |
+//! | `manufacturer` | manufacturer substring (case-insensitive) |
+//! | `product`      | product substring (case-insensitive)      |
+//! | `interface`    | interface number (decimal)                |
+//! | `usage_page`   | top-level usage page                      |
+//! | `usage`        | top-level usage                           |
+//! | `bus`          | `usb`, `bluetooth`, `i2c`, `spi`, `virtual` or a number |
+//! | `phys`         | physical path prefix                      |
+//!
+//! Values containing commas, double quotes or surrounding whitespace are
+//! written in double quotes, with `\"` and `\\` for a literal quote and
+//! backslash, e.g. `product="Keyboard, Mouse"`. `Display` quotes them the
+//! same way, so a printed filter parses back to itself.
+
+use crate::{BusType, DeviceInfo, Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Criteria for selecting HID devices
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceFilter {
+    vendor_ids: Vec<u16>,
+    product_ids: Vec<u16>,
+    serial: Option<String>,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    interface_number: Option<i32>,
+    usage_page: Option<u16>,
+    usage: Option<u16>,
+    bus_type: Option<BusType>,
+    physical_path_prefix: Option<String>,
+}
+
+impl DeviceFilter {
+    /// Create a filter that matches every device
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept this vendor ID (may be called repeatedly to accept several)
+    pub fn vendor_id(mut self, vendor_id: u16) -> Self {
+        self.vendor_ids.push(vendor_id);
+        self
+    }
+
+    /// Accept any of these vendor IDs
+    pub fn vendor_ids(mut self, vendor_ids: impl IntoIterator<Item = u16>) -> Self {
+        self.vendor_ids.extend(vendor_ids);
+        self
+    }
+
+    /// Accept this product ID (may be called repeatedly to accept several)
+    pub fn product_id(mut self, product_id: u16) -> Self {
+        self.product_ids.push(product_id);
+        self
+    }
+
+    /// Accept any of these product IDs
+    pub fn product_ids(mut self, product_ids: impl IntoIterator<Item = u16>) -> Self {
+        self.product_ids.extend(product_ids);
+        self
+    }
+
+    /// Match the serial number exactly, or as a glob if it contains `*` or `?`
+    pub fn serial(mut self, pattern: &str) -> Self {
+        self.serial = Some(pattern.to_string());
+        self
+    }
+
+    /// Require the manufacturer string to contain `text` (case-insensitive)
+    pub fn manufacturer_contains(mut self, text: &str) -> Self {
+        self.manufacturer = Some(text.to_string());
+        self
+    }
+
+    /// Require the product string to contain `text` (case-insensitive)
+    pub fn product_contains(mut self, text: &str) -> Self {
+        self.product = Some(text.to_string());
+        self
+    }
+
+    /// Require this interface number
+    pub fn interface_number(mut self, interface_number: i32) -> Self {
+        self.interface_number = Some(interface_number);
+        self
+    }
+
+    /// Require this top-level usage page
+    pub fn usage_page(mut self, usage_page: u16) -> Self {
+        self.usage_page = Some(usage_page);
+        self
+    }
+
+    /// Require this top-level usage
+    pub fn usage(mut self, usage: u16) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Require this bus type
+    pub fn bus_type(mut self, bus_type: BusType) -> Self {
+        self.bus_type = Some(bus_type);
+        self
+    }
+
+    /// Require the physical path to start with `prefix`
+    pub fn physical_path_prefix(mut self, prefix: &str) -> Self {
+        self.physical_path_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Check whether a device satisfies every criterion
+    pub fn matches(&self, info: &DeviceInfo) -> bool {
+        (self.vendor_ids.is_empty() || self.vendor_ids.contains(&info.vendor_id))
+            && (self.product_ids.is_empty() || self.product_ids.contains(&info.product_id))
+            && self.serial.as_deref().is_none_or(|pattern| {
+                info.serial_number
+                    .as_deref()
+                    .is_some_and(|serial| glob_match(pattern, serial))
+            })
+            && contains_ignore_case(info.manufacturer.as_deref(), self.manufacturer.as_deref())
+            && contains_ignore_case(info.product.as_deref(), self.product.as_deref())
+            && self
+                .interface_number
+                .is_none_or(|n| n == info.interface_number)
+            && self.usage_page.is_none_or(|page| page == info.usage_page)
+            && self.usage.is_none_or(|usage| usage == info.usage)
+            && self.bus_type.is_none_or(|bus| bus == info.bus_type)
+            && self.physical_path_prefix.as_deref().is_none_or(|prefix| {
+                info.physical_path
+                    .as_deref()
+                    .is_some_and(|phys| phys.starts_with(prefix))
+            })
+    }
+
+    /// Check the criteria known before reading a device's string attributes
+    ///
+    /// A `false` result means [`matches`](Self::matches) would reject the
+    /// device too, so enumeration can skip it early.
+    pub(crate) fn may_match(&self, bus_type: BusType, vendor_id: u16, product_id: u16) -> bool {
+        (self.vendor_ids.is_empty() || self.vendor_ids.contains(&vendor_id))
+            && (self.product_ids.is_empty() || self.product_ids.contains(&product_id))
+            && self.bus_type.is_none_or(|bus| bus == bus_type)
+    }
+}
+
+/// Check `haystack` contains `needle`, ignoring case; no needle always matches
+fn contains_ignore_case(haystack: Option<&str>, needle: Option<&str>) -> bool {
+    match needle {
+        None => true,
+        Some(needle) => haystack
+            .is_some_and(|haystack| haystack.to_lowercase().contains(&needle.to_lowercase())),
+    }
+}
+
+/// Match `text` against a glob supporting `*` (any run) and `?` (any char)
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text index it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            t = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+fn parse_hex_u16(key: &str, value: &str) -> Result<u16> {
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+    u16::from_str_radix(digits, 16)
+        .map_err(|_| Error::Parse(format!("Invalid hex value for {key}: {value}")))
+}
+
+fn parse_bus_type(value: &str) -> Result<BusType> {
+    Ok(match value.to_lowercase().as_str() {
+        "usb" => BusType::Usb,
+        "bluetooth" | "bt" => BusType::Bluetooth,
+        "i2c" => BusType::I2c,
+        "spi" => BusType::Spi,
+        "virtual" => BusType::Virtual,
+        other => BusType::from_raw(parse_hex_u16("bus", other)?),
+    })
+}
+
+fn bus_type_name(bus_type: BusType) -> String {
+    match bus_type {
+        BusType::Usb => "usb".to_string(),
+        BusType::Bluetooth => "bluetooth".to_string(),
+        BusType::I2c => "i2c".to_string(),
+        BusType::Spi => "spi".to_string(),
+        BusType::Virtual => "virtual".to_string(),
+        other => format!("{raw:x}", raw = other.raw()),
+    }
+}
+
+/// Split a filter string on the commas outside double quotes
+fn split_unquoted(s: &str) -> Result<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                parts.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quoted {
+        return Err(Error::Parse(format!("Unterminated quote in '{s}'")));
+    }
+
+    parts.push(&s[start..]);
+    Ok(parts)
+}
+
+/// Remove the double quotes and escapes from a filter value
+fn unquote(value: &str) -> Result<String> {
+    let mut out = String::with_capacity(value.len());
+    let mut quoted = false;
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => match chars.next() {
+                Some(escaped) => out.push(escaped),
+                None => break,
+            },
+            c => out.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::Parse(format!("Unterminated quote in '{value}'")));
+    }
+
+    Ok(out)
+}
+
+/// Quote a filter value if it would not parse back unchanged
+fn quote(value: &str) -> String {
+    if value.contains([',', '"']) || value.trim() != value {
+        format!(
+            "\"{escaped}\"",
+            escaped = value.replace('\\', "\\\\").replace('"', "\\\"")
+        )
+    } else {
+        value.to_string()
+    }
+}
+
+impl FromStr for DeviceFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut filter = DeviceFilter::new();
+
+        for part in split_unquoted(s)?
+            .into_iter()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| Error::Parse(format!("Expected key=value, got '{part}'")))?;
+            let key = key.trim();
+            let value = unquote(value.trim())?;
+            let value = value.as_str();
+
+            filter = match key {
+                "vid" => filter.vendor_ids(
+                    value
+                        .split('|')
+                        .map(|v| parse_hex_u16(key, v))
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+                "pid" => filter.product_ids(
+                    value
+                        .split('|')
+                        .map(|v| parse_hex_u16(key, v))
+                        .collect::<Result<Vec<_>>>()?,
+                ),
+                "serial" => filter.serial(value),
+                "manufacturer" => filter.manufacturer_contains(value),
+                "product" => filter.product_contains(value),
+                "interface" => filter.interface_number(
+                    value
+                        .parse()
+                        .map_err(|_| Error::Parse(format!("Invalid interface number: {value}")))?,
+                ),
+                "usage_page" => filter.usage_page(parse_hex_u16(key, value)?),
+                "usage" => filter.usage(parse_hex_u16(key, value)?),
+                "bus" => filter.bus_type(parse_bus_type(value)?),
+                "phys" => filter.physical_path_prefix(value),
+                other => {
+                    return Err(Error::Parse(format!("Unknown filter key '{other}'")));
+                }
+            };
+        }
+
+        Ok(filter)
+    }
+}
+
+impl fmt::Display for DeviceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join_hex = |ids: &[u16]| {
+            ids.iter()
+                .map(|id| format!("{id:04x}"))
+                .collect::<Vec<_>>()
+                .join("|")
+        };
+
+        let mut parts = Vec::new();
+        if !self.vendor_ids.is_empty() {
+            parts.push(format!("vid={ids}", ids = join_hex(&self.vendor_ids)));
+        }
+        if !self.product_ids.is_empty() {
+            parts.push(format!("pid={ids}", ids = join_hex(&self.product_ids)));
+        }
+        if let Some(serial) = &self.serial {
+            parts.push(format!("serial={value}", value = quote(serial)));
+        }
+        if let Some(manufacturer) = &self.manufacturer {
+            parts.push(format!("manufacturer={value}", value = quote(manufacturer)));
+        }
+        if let Some(product) = &self.product {
+            parts.push(format!("product={value}", value = quote(product)));
+        }
+        if let Some(interface) = self.interface_number {
+            parts.push(format!("interface={interface}"));
+        }
+        if let Some(usage_page) = self.usage_page {
+            parts.push(format!("usage_page={usage_page:04x}"));
+        }
+        if let Some(usage) = self.usage {
+            parts.push(format!("usage={usage:04x}"));
+        }
+        if let Some(bus_type) = self.bus_type {
+            parts.push(format!("bus={name}", name = bus_type_name(bus_type)));
+        }
+        if let Some(phys) = &self.physical_path_prefix {
+            parts.push(format!("phys={value}", value = quote(phys)));
+        }
+
+        write!(f, "{joined}", joined = parts.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn coldcard() -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from("/dev/hidraw3"),
+            vendor_id: 0xd13e,
+            product_id: 0xcc10,
+            serial_number: Some("ABC123".to_string()),
+            manufacturer: Some("Coinkite".to_string()),
+            product: Some("Coldcard Wallet".to_string()),
+            interface_number: 0,
+            bus_type: BusType::Usb,
+            usage_page: 0xFF00,
+            usage: 0x01,
+            physical_path: Some("usb-0000:00:14.0-1/input0".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_builder_matching() -> Result<()> {
+        let info = coldcard();
+
+        assert!(DeviceFilter::new().matches(&info));
+        assert!(
+            DeviceFilter::new()
+                .vendor_id(0xd13e)
+                .product_ids([0x0001, 0xcc10])
+                .manufacturer_contains("coinkite")
+                .product_contains("COLDCARD")
+                .usage_page(0xFF00)
+                .bus_type(BusType::Usb)
+                .physical_path_prefix("usb-0000:00:14.0")
+                .matches(&info)
+        );
+        assert!(!DeviceFilter::new().vendor_id(0x1209).matches(&info));
+        assert!(!DeviceFilter::new().interface_number(1).matches(&info));
+        assert!(
+            !DeviceFilter::new()
+                .bus_type(BusType::Bluetooth)
+                .matches(&info)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serial_glob() -> Result<()> {
+        let info = coldcard();
+
+        assert!(DeviceFilter::new().serial("ABC123").matches(&info));
+        assert!(DeviceFilter::new().serial("ABC*").matches(&info));
+        assert!(DeviceFilter::new().serial("*1?3").matches(&info));
+        assert!(!DeviceFilter::new().serial("ABC").matches(&info));
+        assert!(!DeviceFilter::new().serial("X*").matches(&info));
+
+        assert!(glob_match("a*b*c", "aXXbYYc"));
+        assert!(glob_match("**", ""));
+        assert!(!glob_match("a*b", "aXXc"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_and_display() -> Result<()> {
+        let filter: DeviceFilter = "vid=d13e, pid=cc10|0x0001, serial=ABC*, bus=usb".parse()?;
+
+        assert_eq!(
+            filter,
+            DeviceFilter::new()
+                .vendor_id(0xd13e)
+                .product_ids([0xcc10, 0x0001])
+                .serial("ABC*")
+                .bus_type(BusType::Usb)
+        );
+        assert!(filter.matches(&coldcard()));
+
+        let text = filter.to_string();
+        assert_eq!(text, "vid=d13e,pid=cc10|0001,serial=ABC*,bus=usb");
+        assert_eq!(text.parse::<DeviceFilter>()?, filter);
+
+        assert!("vid=zzzz".parse::<DeviceFilter>().is_err());
+        assert!("color=red".parse::<DeviceFilter>().is_err());
+        assert!("vid".parse::<DeviceFilter>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quoted_values_round_trip() -> Result<()> {
+        let filter = DeviceFilter::new()
+            .product_contains("Keyboard, Mouse")
+            .manufacturer_contains(" \"Acme\" \\ Co ")
+            .serial("A=B");
+
+        let text = filter.to_string();
+        assert_eq!(
+            text,
+            r#"serial=A=B,manufacturer=" \"Acme\" \\ Co ",product="Keyboard, Mouse""#
+        );
+        assert_eq!(text.parse::<DeviceFilter>()?, filter);
+
+        assert!(r#"product="Keyboard"#.parse::<DeviceFilter>().is_err());
+
+        Ok(())
+    }
+}
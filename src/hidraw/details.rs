@@ -0,0 +1,183 @@
+//! Extended sysfs metadata for diagnostics
+//!
+//! Everything here is read on demand by
+//! [`get_device_details`](super::get_device_details) rather than
+//! during enumeration, which only needs the handful of attributes in
+//! [`DeviceInfo`](crate::DeviceInfo).
+
+use super::enumerate::{read_optional, usb_interface_name};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Detailed sysfs information about a hidraw device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DeviceDetails {
+    /// Resolved sysfs directory of the HID device
+    pub sysfs_path: PathBuf,
+    /// Driver bound to the HID device (e.g. "hid-generic")
+    pub driver: Option<String>,
+    /// USB device information, `None` for devices on other buses
+    pub usb: Option<UsbDetails>,
+}
+
+/// USB-level attributes of a HID device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UsbDetails {
+    /// USB bus number (`busnum`)
+    pub bus_number: Option<u8>,
+    /// Device address on the bus (`devnum`)
+    pub device_number: Option<u8>,
+    /// Port path of the USB device (e.g. "1-1.4")
+    pub port_path: String,
+    /// Negotiated speed in Mbit/s as reported by sysfs (e.g. "12", "480", "1.5")
+    pub speed: Option<String>,
+    /// Maximum packet size of endpoint 0 (`bMaxPacketSize0`)
+    pub max_packet_size0: Option<u8>,
+    /// Interface class (`bInterfaceClass`, 0x03 for HID)
+    pub interface_class: Option<u8>,
+    /// Interface subclass (`bInterfaceSubClass`, 0x01 for boot devices)
+    pub interface_subclass: Option<u8>,
+    /// Interface protocol (`bInterfaceProtocol`, 1 keyboard, 2 mouse)
+    pub interface_protocol: Option<u8>,
+    /// Runtime power management mode (`power/control`: "on" or "auto")
+    pub power_control: Option<String>,
+    /// Autosuspend delay in seconds (`power/autosuspend`, negative disables)
+    pub autosuspend: Option<i32>,
+    /// Hubs between the device and the host controller, nearest first
+    pub hub_chain: Vec<UsbHub>,
+}
+
+/// A USB hub on the path to a device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UsbHub {
+    /// sysfs name of the hub ("1-1" for an external hub, "usb1" for a root hub)
+    pub name: String,
+    /// Hub vendor ID
+    pub vendor_id: u16,
+    /// Hub product ID
+    pub product_id: u16,
+    /// Hub product string (if available)
+    pub product: Option<String>,
+    /// Negotiated speed in Mbit/s
+    pub speed: Option<String>,
+}
+
+/// Collect details from a resolved HID device directory
+pub(crate) fn read_details(hid_dir: &Path) -> DeviceDetails {
+    let driver = fs::read_link(hid_dir.join("driver"))
+        .ok()
+        .and_then(|target| target.file_name()?.to_str().map(str::to_string));
+
+    DeviceDetails {
+        sysfs_path: hid_dir.to_owned(),
+        driver,
+        usb: read_usb_details(hid_dir),
+    }
+}
+
+fn read_usb_details(hid_dir: &Path) -> Option<UsbDetails> {
+    // Layout: .../<usb device>/<usb interface>/<hid device>
+    usb_interface_name(hid_dir)?;
+    let interface_dir = hid_dir.parent()?;
+    let usb_dir = interface_dir.parent()?;
+
+    Some(UsbDetails {
+        bus_number: read_decimal(&usb_dir.join("busnum")),
+        device_number: read_decimal(&usb_dir.join("devnum")),
+        port_path: usb_dir.file_name()?.to_str()?.to_string(),
+        speed: read_optional(&usb_dir.join("speed")),
+        max_packet_size0: read_decimal(&usb_dir.join("bMaxPacketSize0")),
+        interface_class: read_hex_u8(&interface_dir.join("bInterfaceClass")),
+        interface_subclass: read_hex_u8(&interface_dir.join("bInterfaceSubClass")),
+        interface_protocol: read_hex_u8(&interface_dir.join("bInterfaceProtocol")),
+        power_control: read_optional(&usb_dir.join("power/control")),
+        autosuspend: read_decimal(&usb_dir.join("power/autosuspend")),
+        hub_chain: read_hub_chain(usb_dir),
+    })
+}
+
+/// Walk up from a USB device, collecting every ancestor USB device (hubs)
+fn read_hub_chain(usb_dir: &Path) -> Vec<UsbHub> {
+    usb_dir
+        .ancestors()
+        .skip(1)
+        .map_while(|dir| {
+            let vendor_id = read_hex_u16(&dir.join("idVendor"))?;
+            Some(UsbHub {
+                name: dir.file_name()?.to_str()?.to_string(),
+                vendor_id,
+                product_id: read_hex_u16(&dir.join("idProduct")).unwrap_or(0),
+                product: read_optional(&dir.join("product")),
+                speed: read_optional(&dir.join("speed")),
+            })
+        })
+        .collect()
+}
+
+fn read_decimal<T: std::str::FromStr>(path: &Path) -> Option<T> {
+    read_optional(path)?.parse().ok()
+}
+
+fn read_hex_u8(path: &Path) -> Option<u8> {
+    u8::from_str_radix(&read_optional(path)?, 16).ok()
+}
+
+fn read_hex_u16(path: &Path) -> Option<u16> {
+    u16::from_str_radix(&read_optional(path)?, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Result;
+    use crate::hidraw::{FixtureDevice, SysfsFixture};
+
+    #[test]
+    fn test_usb_details() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        let node = fixture.add(&FixtureDevice::usb("hidraw3", 0xd13e, 0xcc10).port("1-1.4"))?;
+
+        let details = fixture.enumerator().device_details(&node)?;
+
+        assert_eq!(details.driver.as_deref(), Some("hid-generic"));
+        let usb = details
+            .usb
+            .ok_or_else(|| crate::Error::Parse("missing USB details".to_string()))?;
+        assert_eq!(usb.port_path, "1-1.4");
+        assert_eq!(usb.bus_number, Some(1));
+        assert_eq!(usb.speed.as_deref(), Some("12"));
+        assert_eq!(usb.max_packet_size0, Some(64));
+        assert_eq!(usb.interface_class, Some(0x03));
+        assert_eq!(usb.power_control.as_deref(), Some("auto"));
+        assert_eq!(usb.autosuspend, Some(2));
+        assert_eq!(
+            usb.hub_chain
+                .iter()
+                .map(|hub| hub.name.as_str())
+                .collect::<Vec<_>>(),
+            ["1-1", "usb1"]
+        );
+        assert_eq!(usb.hub_chain[0].product.as_deref(), Some("USB2.1 Hub"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_i2c_has_no_usb_details() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        let node =
+            fixture.add(&FixtureDevice::i2c("hidraw0", 0x04f3, 0x3140).driver("hid-multitouch"))?;
+
+        let details = fixture.enumerator().device_details(&node)?;
+
+        assert_eq!(details.driver.as_deref(), Some("hid-multitouch"));
+        assert_eq!(details.usb, None);
+
+        Ok(())
+    }
+}
@@ -1,19 +1,21 @@
 //! Low-level hidraw device operations
 
-use crate::hidraw::{ioctl, sys};
+use crate::hidraw::{MAX_REPORT_LEN, ioctl, sys};
 use crate::{Error, Result};
 use rustix::fd::AsFd;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::os::unix::fs::FileTypeExt;
 use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(any(test, feature = "test-util"))]
+use std::os::unix::net::UnixStream;
 use std::path::{Path, PathBuf};
 
 /// Low-level hidraw device handle
 pub struct HidrawDevice {
     file: File,
     path: PathBuf,
-    report_size: usize,
+    descriptor_size: usize,
 }
 
 impl HidrawDevice {
@@ -45,12 +47,56 @@ impl HidrawDevice {
             })?;
 
         // Get report descriptor size via ioctl (using rustix)
-        let report_size = ioctl::get_report_descriptor_size(&file)? as usize;
+        let descriptor_size = ioctl::get_report_descriptor_size(&file)? as usize;
 
         Ok(Self {
             file,
             path: path.to_owned(),
-            report_size,
+            descriptor_size,
+        })
+    }
+
+    /// A device backed by one end of a socket pair, for tests
+    ///
+    /// Bytes written to the returned peer arrive as input reports, and
+    /// dropping the peer unplugs the device.
+    #[cfg(any(test, feature = "test-util"))]
+    pub(crate) fn socket_pair(path: impl AsRef<Path>) -> Result<(Self, UnixStream)> {
+        let (ours, peer) = UnixStream::pair()?;
+        let device = Self {
+            file: File::from(std::os::fd::OwnedFd::from(ours)),
+            path: path.as_ref().to_owned(),
+            descriptor_size: 0,
+        };
+        Ok((device, peer))
+    }
+
+    /// Wrap a descriptor duplicated from another handle on `path`
+    #[cfg(feature = "async")]
+    pub(crate) fn from_parts(file: File, path: &Path, descriptor_size: usize) -> Self {
+        Self {
+            file,
+            path: path.to_owned(),
+            descriptor_size,
+        }
+    }
+
+    /// Consume the handle, returning the open file
+    #[cfg(all(test, feature = "async"))]
+    pub(crate) fn into_file(self) -> File {
+        self.file
+    }
+
+    /// Open a second handle on the same device
+    ///
+    /// The file descriptor is duplicated, so both handles share one open
+    /// file description: reports read through one are not seen by the
+    /// other, and `O_NONBLOCK` set on either applies to both.
+    pub fn try_clone(&self) -> Result<Self> {
+        Ok(Self {
+            file: self.file.try_clone()?,
+            path: self.path.clone(),
+            descriptor_size: self.descriptor_size,
         })
     }
 
@@ -59,15 +105,24 @@ impl HidrawDevice {
         &self.path
     }
 
-    /// Get the report descriptor size
+    /// Get the report descriptor length in bytes
+    pub fn descriptor_size(&self) -> usize {
+        self.descriptor_size
+    }
+
+    /// Get the report descriptor length in bytes
+    #[deprecated(
+        note = "This is the descriptor length, not a report length; use descriptor_size()"
+    )]
     pub fn report_size(&self) -> usize {
-        self.report_size
+        self.descriptor_size
     }
 
     /// Read a HID report (blocking)
     pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
         self.file.read(buf).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            // hidraw fails reads with EIO once the device is unplugged
+            if e.kind() == std::io::ErrorKind::UnexpectedEof || is_unplugged(&e) {
                 Error::Disconnected
             } else {
                 Error::Io(e)
@@ -84,14 +139,15 @@ impl HidrawDevice {
         }
 
         // Check if data exceeds typical HID report size
-        if data.len() > 4096 {
+        if data.len() > MAX_REPORT_LEN {
             return Err(Error::InvalidParameter(format!(
-                "Data too large: {} bytes (max 4096)",
+                "Data too large: {} bytes (max {MAX_REPORT_LEN})",
                 data.len()
             )));
         }
 
         self.file.write(data).map_err(|e| match e.kind() {
+            _ if is_unplugged(&e) => Error::Disconnected,
             std::io::ErrorKind::BrokenPipe => Error::Disconnected,
             std::io::ErrorKind::PermissionDenied => Error::PermissionDenied,
             std::io::ErrorKind::NotConnected => Error::Disconnected,
@@ -124,6 +180,33 @@ impl HidrawDevice {
         Ok(())
     }
 
+    /// Get the current input report via the control pipe (HIDIOCGINPUT)
+    ///
+    /// The first byte of `buf` is set to `report_id`. Returns
+    /// `Error::NotSupported` on kernels older than 5.11.
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        ioctl::get_input_report(&self.file, report_id, buf)
+    }
+
+    /// Send an output report via the control pipe (HIDIOCSOUTPUT)
+    ///
+    /// Returns `Error::NotSupported` on kernels older than 5.11.
+    pub fn send_output_report(&mut self, data: &[u8]) -> Result<()> {
+        ioctl::send_output_report(&self.file, data)
+    }
+
+    /// Set or clear `O_NONBLOCK` on the file descriptor
+    ///
+    /// In non-blocking mode `read` and `write` fail with
+    /// `io::ErrorKind::WouldBlock` instead of waiting.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        use rustix::fs::{OFlags, fcntl_getfl, fcntl_setfl};
+
+        let mut flags = fcntl_getfl(&self.file).map_err(|e| Error::Io(e.into()))?;
+        flags.set(OFlags::NONBLOCK, nonblocking);
+        fcntl_setfl(&self.file, flags).map_err(|e| Error::Io(e.into()))
+    }
+
     /// Get device info via ioctl
     pub fn get_raw_info(&self) -> Result<sys::HidrawDevInfo> {
         // Using rustix for fixed-size struct
@@ -168,7 +251,15 @@ impl std::fmt::Debug for HidrawDevice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("HidrawDevice")
             .field("path", &self.path)
-            .field("report_size", &self.report_size)
+            .field("descriptor_size", &self.descriptor_size)
             .finish()
     }
 }
+
+/// Whether an I/O error means the device was unplugged
+///
+/// hidraw returns EIO from `read` and ENODEV from `write` and ioctls once
+/// the underlying HID device is gone.
+pub(crate) fn is_unplugged(error: &std::io::Error) -> bool {
+    matches!(error.raw_os_error(), Some(libc::EIO | libc::ENODEV))
+}
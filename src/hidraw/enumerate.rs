@@ -1,91 +1,290 @@
 //! Device enumeration and discovery functionality
 
-use crate::{DeviceInfo, Error, Result};
+use super::details::{DeviceDetails, read_details};
+use crate::descriptor::ReportLayout;
+use crate::{BusType, DeviceFilter, DeviceInfo, Error, Result};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 /// Enumerate all HID devices on the system
 pub fn enumerate() -> Result<Vec<DeviceInfo>> {
-    let mut devices = Vec::new();
+    Enumerator::new().enumerate()
+}
 
-    // Check if /sys/class/hidraw exists
-    let hidraw_class = Path::new("/sys/class/hidraw");
-    if !hidraw_class.exists() {
-        return Err(Error::NotSupported(
-            "hidraw not available on this system".to_string(),
-        ));
+/// Get device information from a hidraw device path
+pub fn get_device_info(device_path: &Path) -> Result<DeviceInfo> {
+    Enumerator::new().device_info(device_path)
+}
+
+/// USB port path and interface of a hidraw device (e.g. "1-1.4:1.0")
+///
+/// This is the name of the USB interface directory the HID device hangs off
+/// in sysfs. Returns `None` for non-USB devices.
+pub fn usb_port_path(device_path: &Path) -> Option<String> {
+    Enumerator::new().usb_port_path(device_path)
+}
+
+/// Read detailed sysfs information for a hidraw device node
+pub fn get_device_details(device_path: &Path) -> Result<DeviceDetails> {
+    Enumerator::new().device_details(device_path)
+}
+
+/// Device discovery against a sysfs and devfs tree
+///
+/// The free functions in this module use the system roots (`/sys` and
+/// `/dev`). Pointing an `Enumerator` elsewhere allows running enumeration
+/// against a `SysfsFixture` (feature `test-util`) in tests, or against a
+/// container's bind-mounted `/sys`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enumerator {
+    sysfs_root: PathBuf,
+    dev_root: PathBuf,
+}
+
+impl Default for Enumerator {
+    fn default() -> Self {
+        Self {
+            sysfs_root: PathBuf::from("/sys"),
+            dev_root: PathBuf::from("/dev"),
+        }
+    }
+}
+
+impl Enumerator {
+    /// Enumerator using `/sys` and `/dev`
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    // Read /sys/class/hidraw/ directory
-    for entry in fs::read_dir(hidraw_class)? {
-        let entry = entry?;
-        let name = entry.file_name();
-        let device_path = PathBuf::from("/dev").join(&name);
+    /// Use a different sysfs root (the directory containing `class/hidraw`)
+    pub fn sysfs_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.sysfs_root = root.into();
+        self
+    }
 
-        // Skip if the device file doesn't exist
-        if !device_path.exists() {
-            continue;
+    /// Use a different devfs root (the directory containing the `hidrawN` nodes)
+    pub fn dev_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.dev_root = root.into();
+        self
+    }
+
+    fn hidraw_class(&self) -> PathBuf {
+        self.sysfs_root.join("class/hidraw")
+    }
+
+    /// Whether the hidraw class exists under the sysfs root
+    pub fn is_available(&self) -> bool {
+        self.hidraw_class().exists()
+    }
+
+    /// Enumerate all HID devices
+    pub fn enumerate(&self) -> Result<Vec<DeviceInfo>> {
+        self.enumerate_with(&DeviceFilter::new())
+    }
+
+    /// Enumerate HID devices matching a filter
+    ///
+    /// The bus and IDs in each device's HID uevent are checked first, so the
+    /// USB string attributes and report descriptors of non-matching devices
+    /// are never read.
+    pub fn enumerate_with(&self, filter: &DeviceFilter) -> Result<Vec<DeviceInfo>> {
+        let mut devices = Vec::new();
+
+        // Check if /sys/class/hidraw exists
+        let hidraw_class = self.hidraw_class();
+        if !hidraw_class.exists() {
+            return Err(Error::NotSupported(
+                "hidraw not available on this system".to_string(),
+            ));
         }
 
-        // Try to get device info
-        match get_device_info(&device_path) {
-            Ok(info) => devices.push(info),
-            Err(_) => {
-                // Skip devices we can't read info from
+        // Read /sys/class/hidraw/ directory
+        for entry in fs::read_dir(hidraw_class)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let device_path = self.dev_root.join(&name);
+
+            // Skip if the device file doesn't exist
+            if !device_path.exists() {
                 continue;
             }
+
+            let Ok(hid_dir) = self.hid_sysfs_dir(&device_path) else {
+                continue;
+            };
+            let uevent = read_hid_uevent(&hid_dir.join("uevent")).unwrap_or_default();
+            if let Some((vendor_id, product_id)) = uevent.ids
+                && !filter.may_match(uevent.bus_type, vendor_id, product_id)
+            {
+                continue;
+            }
+
+            // Skip devices we can't read info from
+            if let Ok(info) = build_device_info(&device_path, &hid_dir, uevent)
+                && filter.matches(&info)
+            {
+                devices.push(info);
+            }
         }
+
+        Ok(devices)
     }
 
-    Ok(devices)
-}
+    /// Resolve the sysfs directory of the HID device behind a hidraw node
+    ///
+    /// `class/hidraw/hidrawN/device` is a relative symlink; it is
+    /// canonicalized so walking up the hierarchy sees the real topology.
+    fn hid_sysfs_dir(&self, device_path: &Path) -> Result<PathBuf> {
+        // Extract device name from path (e.g., "hidraw0" from "/dev/hidraw0")
+        let device_name = device_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| Error::InvalidPath("Invalid device path".to_string()))?;
 
-/// Get device information from a hidraw device path
-pub fn get_device_info(device_path: &Path) -> Result<DeviceInfo> {
-    // Extract device name from path (e.g., "hidraw0" from "/dev/hidraw0")
-    let device_name = device_path
-        .file_name()
-        .and_then(|n| n.to_str())
-        .ok_or_else(|| Error::InvalidPath("Invalid device path".to_string()))?;
+        let sysfs_base = self.hidraw_class().join(device_name);
+        if !sysfs_base.exists() {
+            return Err(Error::InvalidPath(format!(
+                "sysfs path {path} does not exist",
+                path = sysfs_base.display()
+            )));
+        }
 
-    // Construct sysfs path
-    let sysfs_base = PathBuf::from("/sys/class/hidraw").join(device_name);
-    if !sysfs_base.exists() {
-        return Err(Error::InvalidPath(format!(
-            "sysfs path {} does not exist",
-            sysfs_base.display()
-        )));
+        fs::canonicalize(sysfs_base.join("device"))
+            .map_err(|e| Error::Parse(format!("Failed to canonicalize path: {e}")))
     }
 
-    // Follow the device symlink
-    let device_sysfs = sysfs_base.join("device");
+    /// Get device information for a hidraw device path
+    pub fn device_info(&self, device_path: &Path) -> Result<DeviceInfo> {
+        let hid_dir = self.hid_sysfs_dir(device_path)?;
 
-    // Find the USB device directory by walking up the hierarchy
-    let usb_device_path = find_usb_device_path(&device_sysfs)?;
+        // Bus type, IDs and physical path come from the HID device's uevent
+        let uevent = read_hid_uevent(&hid_dir.join("uevent")).unwrap_or_default();
 
-    // Read USB device attributes
-    let vendor_id = read_hex_attr(&usb_device_path.join("idVendor"))?;
-    let product_id = read_hex_attr(&usb_device_path.join("idProduct"))?;
+        build_device_info(device_path, &hid_dir, uevent)
+    }
 
-    // Read string descriptors (may not exist)
-    let manufacturer = read_string_attr(&usb_device_path.join("manufacturer")).ok();
-    let product = read_string_attr(&usb_device_path.join("product")).ok();
-    let serial = read_string_attr(&usb_device_path.join("serial")).ok();
+    /// USB port path and interface of a hidraw device (e.g. "1-1.4:1.0")
+    pub fn usb_port_path(&self, device_path: &Path) -> Option<String> {
+        let hid_dir = self.hid_sysfs_dir(device_path).ok()?;
+        usb_interface_name(&hid_dir).map(str::to_string)
+    }
 
-    // Try to get interface number
-    let interface_number = get_interface_number(&device_sysfs).unwrap_or(0);
+    /// Read detailed sysfs information for a hidraw device node
+    pub fn device_details(&self, device_path: &Path) -> Result<DeviceDetails> {
+        Ok(read_details(&self.hid_sysfs_dir(device_path)?))
+    }
+}
+
+/// Assemble device information from the HID device's sysfs directory
+fn build_device_info(device_path: &Path, hid_dir: &Path, uevent: HidUevent) -> Result<DeviceInfo> {
+    // Only USB devices take their IDs and strings from a USB ancestor;
+    // Bluetooth HID devices often sit below a USB Bluetooth adapter whose
+    // IDs must not be reported as the device's own.
+    let usb_device_path = match uevent.bus_type {
+        BusType::Usb | BusType::Unknown => find_usb_device_path(hid_dir).ok(),
+        _ => None,
+    };
+
+    let info = match usb_device_path {
+        Some(usb_device_path) => DeviceInfo {
+            path: device_path.to_owned(),
+            vendor_id: read_hex_attr(&usb_device_path.join("idVendor"))?,
+            product_id: read_hex_attr(&usb_device_path.join("idProduct"))?,
+            // Read string descriptors (may not exist)
+            serial_number: read_string_attr(&usb_device_path.join("serial")).ok(),
+            manufacturer: read_string_attr(&usb_device_path.join("manufacturer")).ok(),
+            product: read_string_attr(&usb_device_path.join("product")).ok(),
+            interface_number: get_interface_number(hid_dir).unwrap_or(0),
+            bus_type: uevent.bus_type,
+            usage_page: 0,
+            usage: 0,
+            physical_path: uevent.phys,
+        },
+        None => {
+            let (vendor_id, product_id) = uevent.ids.ok_or_else(|| {
+                Error::Parse("Could not determine vendor and product ID".to_string())
+            })?;
+            DeviceInfo {
+                path: device_path.to_owned(),
+                vendor_id,
+                product_id,
+                serial_number: uevent.uniq,
+                manufacturer: None,
+                product: uevent.name,
+                interface_number: 0,
+                bus_type: uevent.bus_type,
+                usage_page: 0,
+                usage: 0,
+                physical_path: uevent.phys,
+            }
+        }
+    };
+
+    // Top-level usage from the sysfs copy of the report descriptor
+    let (usage_page, usage) =
+        read_top_level_usage(&hid_dir.join("report_descriptor")).unwrap_or((0, 0));
 
     Ok(DeviceInfo {
-        path: device_path.to_owned(),
-        vendor_id,
-        product_id,
-        serial_number: serial,
-        manufacturer,
-        product,
-        interface_number,
+        usage_page,
+        usage,
+        ..info
     })
 }
 
+/// HID attributes from a HID device's uevent file
+#[derive(Debug, Default)]
+struct HidUevent {
+    bus_type: BusType,
+    ids: Option<(u16, u16)>,
+    name: Option<String>,
+    phys: Option<String>,
+    uniq: Option<String>,
+}
+
+/// Parse the `HID_*` variables from a uevent file
+fn read_hid_uevent(path: &Path) -> Result<HidUevent> {
+    let content = fs::read_to_string(path)?;
+    let mut uevent = HidUevent::default();
+    let non_empty = |value: &str| (!value.is_empty()).then(|| value.to_string());
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        match key {
+            "HID_ID" => {
+                // Format: BUS:VENDOR:PRODUCT, e.g. 0003:0000D13E:0000CC10
+                let invalid = || Error::Parse(format!("Invalid HID_ID: {value}"));
+                let mut fields = value
+                    .split(':')
+                    .map(|field| u32::from_str_radix(field, 16).map_err(|_| invalid()));
+                let (Some(bus), Some(vendor), Some(product)) =
+                    (fields.next(), fields.next(), fields.next())
+                else {
+                    return Err(invalid());
+                };
+                uevent.bus_type = BusType::from_raw(bus? as u16);
+                uevent.ids = Some((vendor? as u16, product? as u16));
+            }
+            "HID_NAME" => uevent.name = non_empty(value),
+            "HID_PHYS" => uevent.phys = non_empty(value),
+            "HID_UNIQ" => uevent.uniq = non_empty(value),
+            _ => {}
+        }
+    }
+
+    Ok(uevent)
+}
+
+/// Read the usage page and usage of the first top-level collection
+fn read_top_level_usage(path: &Path) -> Result<(u16, u16)> {
+    let descriptor = fs::read(path)?;
+    ReportLayout::parse_top_level_usages(&descriptor)?
+        .first()
+        .copied()
+        .ok_or_else(|| Error::Parse("Report descriptor has no collections".to_string()))
+}
+
 /// Find the USB device path by walking up the sysfs hierarchy
 fn find_usb_device_path(start_path: &Path) -> Result<PathBuf> {
     // Canonicalize the path to resolve symlinks and .. components
@@ -113,28 +312,42 @@ fn find_usb_device_path(start_path: &Path) -> Result<PathBuf> {
     ))
 }
 
-/// Get the interface number from the device path
-fn get_interface_number(device_path: &Path) -> Result<i32> {
-    // The interface number is often in the path like: .../1-1.4:1.0/...
-    // Where the last number after the colon is the interface number
-
-    if let Some(path_str) = device_path.to_str() {
-        // Look for pattern like ":1.0" in the path
-        for component in path_str.split('/') {
-            if let Some(colon_pos) = component.rfind(':') {
-                if let Some(dot_pos) = component[colon_pos + 1..].find('.') {
-                    let interface_str = &component[colon_pos + 1..colon_pos + 1 + dot_pos];
-                    if let Ok(num) = interface_str.parse::<i32>() {
-                        return Ok(num);
-                    }
-                }
-            }
-        }
-    }
+/// Name of the USB interface a HID device directory belongs to
+///
+/// usbhid creates HID devices directly below the interface
+/// (`<bus>-<ports>:<config>.<interface>`), so only the parent is considered.
+pub(super) fn usb_interface_name(hid_dir: &Path) -> Option<&str> {
+    hid_dir
+        .parent()?
+        .file_name()?
+        .to_str()
+        .filter(|name| is_usb_interface_name(name))
+}
 
-    Err(Error::Parse(
-        "Could not determine interface number".to_string(),
-    ))
+fn is_usb_interface_name(name: &str) -> bool {
+    let Some((port, interface)) = name.split_once(':') else {
+        return false;
+    };
+    let Some((bus, ports)) = port.split_once('-') else {
+        return false;
+    };
+    let numeric = |s: &str, sep: char| {
+        !s.is_empty()
+            && s.split(sep)
+                .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit()))
+    };
+
+    numeric(bus, '.') && numeric(ports, '.') && numeric(interface, '.')
+}
+
+/// Get the interface number from a resolved HID device directory
+fn get_interface_number(hid_dir: &Path) -> Result<i32> {
+    // The interface directory is named like "1-1.4:1.0", where the number
+    // after the dot is the interface number
+    usb_interface_name(hid_dir)
+        .and_then(|name| name.rsplit('.').next())
+        .and_then(|interface| interface.parse().ok())
+        .ok_or_else(|| Error::Parse("Could not determine interface number".to_string()))
 }
 
 /// Read a hexadecimal value from a sysfs attribute file
@@ -147,8 +360,189 @@ fn read_hex_attr(path: &Path) -> Result<u16> {
         .map_err(|_| Error::Parse(format!("Invalid hex value: {trimmed}")))
 }
 
+/// Read a trimmed sysfs attribute, `None` if missing or empty
+pub(super) fn read_optional(path: &Path) -> Option<String> {
+    read_string_attr(path)
+        .ok()
+        .filter(|value| !value.is_empty())
+}
+
 /// Read a string value from a sysfs attribute file
 fn read_string_attr(path: &Path) -> Result<String> {
     let content = fs::read_to_string(path)?;
     Ok(content.trim().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{CollectionType, DescriptorBuilder, MainFlags};
+    use crate::hidraw::{FixtureDevice, SysfsFixture};
+
+    #[test]
+    fn test_usb_interface_name() -> Result<()> {
+        let usb = Path::new(
+            "/sys/devices/pci0000:00/0000:00:14.0/usb1/1-1/1-1.4/1-1.4:1.0/0003:D13E:CC10.0005",
+        );
+        assert_eq!(usb_interface_name(usb), Some("1-1.4:1.0"));
+
+        // A Bluetooth device below a USB adapter is not on that USB interface
+        let bluetooth = Path::new(
+            "/sys/devices/pci0000:00/0000:00:14.0/usb1/1-10/1-10:1.0/bluetooth/hci0/hci0:256/0005:046D:B01A.0006",
+        );
+        assert_eq!(usb_interface_name(bluetooth), None);
+
+        let i2c = Path::new(
+            "/sys/devices/platform/AMDI0010:00/i2c-0/i2c-ELAN0001:00/0018:04F3:3140.0001",
+        );
+        assert_eq!(usb_interface_name(i2c), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_usb_device_info() -> Result<()> {
+        let descriptor = DescriptorBuilder::new()
+            .usage_page(0xFF00)
+            .usage(0x01)
+            .collection(CollectionType::Application, |c| {
+                c.report_size(8).report_count(64).input(MainFlags::VARIABLE)
+            })
+            .to_bytes();
+        let mut fixture = SysfsFixture::new()?;
+        let node = fixture.add(
+            &FixtureDevice::usb("hidraw12", 0xd13e, 0xcc10)
+                .port("1-1.4")
+                .interface(2)
+                .manufacturer("Coinkite")
+                .product("Coldcard")
+                .serial("ABC123")
+                .report_descriptor(&descriptor),
+        )?;
+
+        let info = fixture.enumerator().device_info(&node)?;
+
+        assert_eq!((info.vendor_id, info.product_id), (0xd13e, 0xcc10));
+        assert_eq!(info.manufacturer.as_deref(), Some("Coinkite"));
+        assert_eq!(info.product.as_deref(), Some("Coldcard"));
+        assert_eq!(info.serial_number.as_deref(), Some("ABC123"));
+        // Used to be read from the uncanonicalized class path and come out as 0
+        assert_eq!(info.interface_number, 2);
+        assert_eq!(info.bus_type, BusType::Usb);
+        assert_eq!((info.usage_page, info.usage), (0xFF00, 0x01));
+        assert_eq!(
+            info.physical_path.as_deref(),
+            Some("usb-0000:00:14.0-1.4/input2")
+        );
+        assert_eq!(
+            fixture.enumerator().usb_port_path(&node).as_deref(),
+            Some("1-1.4:1.2")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bluetooth_uses_hid_ids_not_adapter() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        let node = fixture.add(
+            &FixtureDevice::bluetooth("hidraw1", 0x046d, 0xb01a)
+                .product("MX Anywhere 2S")
+                .serial("d2:4c:6b:01:02:03"),
+        )?;
+
+        let info = fixture.enumerator().device_info(&node)?;
+
+        assert_eq!((info.vendor_id, info.product_id), (0x046d, 0xb01a));
+        assert_eq!(info.bus_type, BusType::Bluetooth);
+        assert_eq!(info.product.as_deref(), Some("MX Anywhere 2S"));
+        assert_eq!(info.serial_number.as_deref(), Some("d2:4c:6b:01:02:03"));
+        assert_eq!(info.manufacturer, None);
+        assert_eq!(fixture.enumerator().usb_port_path(&node), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_i2c_device_info() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        let node = fixture.add(&FixtureDevice::i2c("hidraw0", 0x04f3, 0x3140))?;
+
+        let info = fixture.enumerator().device_info(&node)?;
+
+        assert_eq!((info.vendor_id, info.product_id), (0x04f3, 0x3140));
+        assert_eq!(info.bus_type, BusType::I2c);
+        // The HID directory name "0018:04F3:3140.0001" is not an interface
+        assert_eq!(info.interface_number, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enumerate_skips_missing_nodes() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        fixture.add(&FixtureDevice::usb("hidraw0", 0x1234, 0x0001).port("1-1"))?;
+        fixture.add(&FixtureDevice::usb("hidraw1", 0x1234, 0x0002).port("1-2"))?;
+        fixture.add(&FixtureDevice::i2c("hidraw2", 0x04f3, 0x3140))?;
+        fs::remove_file(fixture.dev_root().join("hidraw1"))?;
+
+        let mut found: Vec<u16> = fixture
+            .enumerator()
+            .enumerate()?
+            .iter()
+            .map(|info| info.product_id)
+            .collect();
+        found.sort_unstable();
+        assert_eq!(found, [0x0001, 0x3140]);
+
+        fixture.remove("hidraw0")?;
+        assert_eq!(fixture.enumerator().enumerate()?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_enumerate_with_filter() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        fixture.add(&FixtureDevice::usb("hidraw0", 0xd13e, 0xcc10).port("1-1"))?;
+        fixture.add(
+            &FixtureDevice::usb("hidraw1", 0xd13e, 0xcc10)
+                .port("1-2")
+                .serial("ABC123"),
+        )?;
+        fixture.add(&FixtureDevice::usb("hidraw2", 0x046d, 0xc52b).port("1-3"))?;
+        fixture.add(&FixtureDevice::bluetooth("hidraw3", 0xd13e, 0xcc10))?;
+
+        let enumerator = fixture.enumerator();
+        let usb = DeviceFilter::new()
+            .vendor_id(0xd13e)
+            .product_id(0xcc10)
+            .bus_type(BusType::Usb);
+        assert_eq!(enumerator.enumerate_with(&usb)?.len(), 2);
+
+        let serial = enumerator.enumerate_with(&usb.serial("ABC123"))?;
+        assert_eq!(serial.len(), 1);
+        assert_eq!(serial[0].path, fixture.dev_root().join("hidraw1"));
+
+        let any_bus = DeviceFilter::new().vendor_id(0xd13e);
+        assert_eq!(enumerator.enumerate_with(&any_bus)?.len(), 3);
+        assert_eq!(enumerator.enumerate()?.len(), 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_hidraw_class() -> Result<()> {
+        let enumerator = Enumerator::new().sysfs_root("/nonexistent/sys");
+        assert!(!enumerator.is_available());
+        assert!(matches!(
+            enumerator.enumerate(),
+            Err(Error::NotSupported(_))
+        ));
+        assert!(matches!(
+            enumerator.device_info(Path::new("/dev/hidraw0")),
+            Err(Error::InvalidPath(_))
+        ));
+        Ok(())
+    }
+}
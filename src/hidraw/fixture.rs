@@ -0,0 +1,462 @@
+//! Fake sysfs and devfs trees for testing enumeration
+//!
+//! A [`SysfsFixture`] lays out the same directory structure and relative
+//! symlinks the kernel creates for hidraw devices on USB, Bluetooth and I2C
+//! buses, under a temporary directory that is removed on drop. Point an
+//! [`Enumerator`] at it with [`SysfsFixture::enumerator`].
+//!
+//! Only built with the `test-util` feature.
+//!
+//! ```
+//! use hidraw_rs::hidraw::{FixtureDevice, SysfsFixture};
+//!
+//! # fn main() -> hidraw_rs::Result<()> {
+//! let mut fixture = SysfsFixture::new()?;
+//! fixture.add(
+//!     &FixtureDevice::usb("hidraw12", 0xd13e, 0xcc10)
+//!         .port("1-1.4")
+//!         .serial("ABC123"),
+//! )?;
+//!
+//! let devices = fixture.enumerator().enumerate()?;
+//! assert_eq!(devices[0].serial_number.as_deref(), Some("ABC123"));
+//! # Ok(())
+//! # }
+//! ```
+
+use super::Enumerator;
+use crate::{BusType, Result};
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, symlink};
+use std::path::{Component, Path, PathBuf};
+
+/// Description of one fake hidraw device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixtureDevice {
+    hidraw: String,
+    bus_type: BusType,
+    vendor_id: u16,
+    product_id: u16,
+    port: String,
+    interface: u8,
+    manufacturer: Option<String>,
+    product: Option<String>,
+    serial: Option<String>,
+    report_descriptor: Vec<u8>,
+    driver: String,
+}
+
+impl FixtureDevice {
+    fn new(hidraw: &str, bus_type: BusType, vendor_id: u16, product_id: u16) -> Self {
+        Self {
+            hidraw: hidraw.to_string(),
+            bus_type,
+            vendor_id,
+            product_id,
+            port: "1-1".to_string(),
+            interface: 0,
+            manufacturer: None,
+            product: None,
+            serial: None,
+            report_descriptor: Vec::new(),
+            driver: "hid-generic".to_string(),
+        }
+    }
+
+    /// A USB HID interface (port "1-1", interface 0 unless changed)
+    pub fn usb(hidraw: &str, vendor_id: u16, product_id: u16) -> Self {
+        Self::new(hidraw, BusType::Usb, vendor_id, product_id)
+    }
+
+    /// A Bluetooth HID device behind a USB Bluetooth adapter
+    pub fn bluetooth(hidraw: &str, vendor_id: u16, product_id: u16) -> Self {
+        Self::new(hidraw, BusType::Bluetooth, vendor_id, product_id)
+    }
+
+    /// An I2C HID device on a platform I2C controller
+    pub fn i2c(hidraw: &str, vendor_id: u16, product_id: u16) -> Self {
+        Self::new(hidraw, BusType::I2c, vendor_id, product_id)
+    }
+
+    /// USB port path of the device (e.g. "1-1.4" for port 4 of a hub on port 1)
+    pub fn port(mut self, port: &str) -> Self {
+        self.port = port.to_string();
+        self
+    }
+
+    /// USB interface number
+    pub fn interface(mut self, interface: u8) -> Self {
+        self.interface = interface;
+        self
+    }
+
+    /// Manufacturer string (USB only)
+    pub fn manufacturer(mut self, manufacturer: &str) -> Self {
+        self.manufacturer = Some(manufacturer.to_string());
+        self
+    }
+
+    /// Product string, also used as the HID name
+    pub fn product(mut self, product: &str) -> Self {
+        self.product = Some(product.to_string());
+        self
+    }
+
+    /// Serial number (the `serial` attribute on USB, `HID_UNIQ` otherwise)
+    pub fn serial(mut self, serial: &str) -> Self {
+        self.serial = Some(serial.to_string());
+        self
+    }
+
+    /// Contents of the sysfs `report_descriptor` attribute
+    pub fn report_descriptor(mut self, descriptor: &[u8]) -> Self {
+        self.report_descriptor = descriptor.to_vec();
+        self
+    }
+
+    /// Name of the bound HID driver
+    pub fn driver(mut self, driver: &str) -> Self {
+        self.driver = driver.to_string();
+        self
+    }
+}
+
+/// A temporary sysfs/devfs tree populated with fake hidraw devices
+#[derive(Debug)]
+pub struct SysfsFixture {
+    root: PathBuf,
+    next_sequence: u16,
+}
+
+impl SysfsFixture {
+    /// Create an empty tree with `sys/class/hidraw` and `dev`
+    ///
+    /// The tree lives in a fresh, randomly named directory under the system
+    /// temporary directory that only the current user can access.
+    pub fn new() -> Result<Self> {
+        let fixture = Self {
+            root: create_private_dir()?,
+            next_sequence: 1,
+        };
+        fs::create_dir_all(fixture.sysfs_root().join("class/hidraw"))?;
+        fs::create_dir_all(fixture.dev_root())?;
+        Ok(fixture)
+    }
+
+    /// Root of the whole fixture
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The fake `/sys`
+    pub fn sysfs_root(&self) -> PathBuf {
+        self.root.join("sys")
+    }
+
+    /// The fake `/dev`
+    pub fn dev_root(&self) -> PathBuf {
+        self.root.join("dev")
+    }
+
+    /// An enumerator reading this tree
+    pub fn enumerator(&self) -> Enumerator {
+        Enumerator::new()
+            .sysfs_root(self.sysfs_root())
+            .dev_root(self.dev_root())
+    }
+
+    /// Add a device, returning the path of its fake device node
+    pub fn add(&mut self, device: &FixtureDevice) -> Result<PathBuf> {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+
+        let hid_name = format!(
+            "{bus:04X}:{vid:04X}:{pid:04X}.{sequence:04X}",
+            bus = device.bus_type.raw(),
+            vid = device.vendor_id,
+            pid = device.product_id
+        );
+        let (parent, phys) = match device.bus_type {
+            BusType::Usb => self.add_usb_interface(device, sequence)?,
+            BusType::Bluetooth => self.add_bluetooth_link(sequence)?,
+            _ => self.add_i2c_client(device)?,
+        };
+
+        let hid_dir = parent.join(&hid_name);
+        fs::create_dir_all(&hid_dir)?;
+        write_attr(&hid_dir, "uevent", &hid_uevent(device, &phys))?;
+        fs::write(hid_dir.join("report_descriptor"), &device.report_descriptor)?;
+
+        let driver = self
+            .sysfs_root()
+            .join("bus/hid/drivers")
+            .join(&device.driver);
+        fs::create_dir_all(&driver)?;
+        link(&driver, &hid_dir.join("driver"))?;
+
+        // hidrawN class device below the HID device
+        let minor: u32 = device
+            .hidraw
+            .trim_start_matches(|c: char| !c.is_ascii_digit())
+            .parse()
+            .unwrap_or(0);
+        let class_dir = hid_dir.join("hidraw").join(&device.hidraw);
+        fs::create_dir_all(&class_dir)?;
+        write_attr(&class_dir, "dev", &format!("247:{minor}"))?;
+        write_attr(
+            &class_dir,
+            "uevent",
+            &format!(
+                "MAJOR=247\nMINOR={minor}\nDEVNAME={name}",
+                name = device.hidraw
+            ),
+        )?;
+        link(&hid_dir, &class_dir.join("device"))?;
+        link(
+            &class_dir,
+            &self.sysfs_root().join("class/hidraw").join(&device.hidraw),
+        )?;
+
+        let node = self.dev_root().join(&device.hidraw);
+        fs::write(&node, b"")?;
+        Ok(node)
+    }
+
+    /// Remove a device as if it had been unplugged
+    pub fn remove(&mut self, hidraw: &str) -> Result<()> {
+        let class_link = self.sysfs_root().join("class/hidraw").join(hidraw);
+        let class_dir = fs::canonicalize(&class_link)?;
+        fs::remove_file(class_link)?;
+        fs::remove_file(self.dev_root().join(hidraw))?;
+        // Remove the HID device directory (two levels above hidrawN)
+        if let Some(hid_dir) = class_dir.parent().and_then(Path::parent) {
+            fs::remove_dir_all(hid_dir)?;
+        }
+        Ok(())
+    }
+
+    fn pci_controller(&self) -> PathBuf {
+        self.sysfs_root().join("devices/pci0000:00/0000:00:14.0")
+    }
+
+    /// Create the hub chain, USB device and interface for a USB HID device
+    fn add_usb_interface(
+        &self,
+        device: &FixtureDevice,
+        sequence: u16,
+    ) -> Result<(PathBuf, String)> {
+        let (bus, ports) = device.port.split_once('-').unwrap_or(("1", "1"));
+
+        let mut dir = self.pci_controller().join(format!("usb{bus}"));
+        write_usb_device(&dir, bus, 1, 0x1d6b, 0x0002, "xHCI Host Controller", "480")?;
+
+        // One directory per hub level: 1-1, 1-1.4, ...
+        let levels: Vec<&str> = ports.split('.').collect();
+        let mut port_path = format!("{bus}-");
+        for (i, port) in levels.iter().enumerate() {
+            if i > 0 {
+                port_path.push('.');
+            }
+            port_path.push_str(port);
+            dir = dir.join(&port_path);
+            if i + 1 < levels.len() && !dir.join("idVendor").exists() {
+                write_usb_device(&dir, bus, 2 + i as u16, 0x05e3, 0x0610, "USB2.1 Hub", "480")?;
+            }
+        }
+
+        // The innermost level is the device itself
+        write_usb_device(
+            &dir,
+            bus,
+            10 + sequence,
+            device.vendor_id,
+            device.product_id,
+            device.product.as_deref().unwrap_or(""),
+            "12",
+        )?;
+        write_attr(&dir, "bMaxPacketSize0", "64")?;
+        write_attr(&dir, "power/control", "auto")?;
+        write_attr(&dir, "power/autosuspend", "2")?;
+        if let Some(manufacturer) = &device.manufacturer {
+            write_attr(&dir, "manufacturer", manufacturer)?;
+        }
+        if let Some(serial) = &device.serial {
+            write_attr(&dir, "serial", serial)?;
+        }
+
+        let interface = dir.join(format!("{port_path}:1.{n}", n = device.interface));
+        write_attr(
+            &interface,
+            "bInterfaceNumber",
+            &format!("{n:02x}", n = device.interface),
+        )?;
+        write_attr(&interface, "bInterfaceClass", "03")?;
+        write_attr(&interface, "bInterfaceSubClass", "00")?;
+        write_attr(&interface, "bInterfaceProtocol", "00")?;
+
+        let phys = format!("usb-0000:00:14.0-{ports}/input{n}", n = device.interface);
+        Ok((interface, phys))
+    }
+
+    /// Create a USB Bluetooth adapter with an HCI connection below it
+    fn add_bluetooth_link(&self, sequence: u16) -> Result<(PathBuf, String)> {
+        let usb = self.pci_controller().join("usb1");
+        write_usb_device(&usb, "1", 1, 0x1d6b, 0x0002, "xHCI Host Controller", "480")?;
+        let adapter = usb.join("1-10");
+        write_usb_device(&adapter, "1", 9, 0x8087, 0x0026, "AX201 Bluetooth", "12")?;
+        let interface = adapter.join("1-10:1.0");
+        write_attr(&interface, "bInterfaceClass", "e0")?;
+
+        let connection = interface
+            .join("bluetooth/hci0")
+            .join(format!("hci0:{handle}", handle = 256 + sequence));
+        fs::create_dir_all(&connection)?;
+        Ok((connection, "00:1a:7d:da:71:13".to_string()))
+    }
+
+    /// Create an I2C controller and client for an I2C HID device
+    fn add_i2c_client(&self, device: &FixtureDevice) -> Result<(PathBuf, String)> {
+        let client_name = format!("i2c-HID{pid:04X}:00", pid = device.product_id);
+        let client = self
+            .sysfs_root()
+            .join("devices/platform/AMDI0010:00/i2c-0")
+            .join(&client_name);
+        fs::create_dir_all(&client)?;
+        Ok((client, client_name))
+    }
+}
+
+impl Drop for SysfsFixture {
+    fn drop(&mut self) {
+        // Errors cannot be reported from drop
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// Create a new mode 0700 directory with an unpredictable name
+///
+/// `mkdir` fails on any existing entry, symlinks included, so a name
+/// planted by another user is skipped rather than reused.
+fn create_private_dir() -> Result<PathBuf> {
+    const ATTEMPTS: usize = 16;
+
+    let temp = std::env::temp_dir();
+    let mut builder = fs::DirBuilder::new();
+    builder.mode(0o700);
+    for _ in 0..ATTEMPTS {
+        // RandomState is seeded from the OS random source
+        let suffix = RandomState::new().build_hasher().finish();
+        let root = temp.join(format!("hidraw-fixture-{suffix:016x}"));
+        match builder.create(&root) {
+            Ok(()) => return Ok(root),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "no unused fixture directory name found",
+    )
+    .into())
+}
+
+/// uevent contents of a HID device
+fn hid_uevent(device: &FixtureDevice, phys: &str) -> String {
+    let bus = device.bus_type.raw();
+    let (vid, pid) = (device.vendor_id, device.product_id);
+    let name = device.product.as_deref().unwrap_or("");
+    let uniq = device.serial.as_deref().unwrap_or("");
+    format!(
+        "DRIVER={driver}\n\
+         HID_ID={bus:04X}:{vid:08X}:{pid:08X}\n\
+         HID_NAME={name}\n\
+         HID_PHYS={phys}\n\
+         HID_UNIQ={uniq}\n\
+         MODALIAS=hid:b{bus:04X}g0001v{vid:08X}p{pid:08X}",
+        driver = device.driver
+    )
+}
+
+/// Write the attributes common to USB devices and hubs
+fn write_usb_device(
+    dir: &Path,
+    bus: &str,
+    devnum: u16,
+    vendor_id: u16,
+    product_id: u16,
+    product: &str,
+    speed: &str,
+) -> Result<()> {
+    write_attr(dir, "idVendor", &format!("{vendor_id:04x}"))?;
+    write_attr(dir, "idProduct", &format!("{product_id:04x}"))?;
+    write_attr(dir, "busnum", bus)?;
+    write_attr(dir, "devnum", &devnum.to_string())?;
+    write_attr(dir, "speed", speed)?;
+    if !product.is_empty() {
+        write_attr(dir, "product", product)?;
+    }
+    Ok(())
+}
+
+/// Write a sysfs attribute with the kernel's trailing newline
+fn write_attr(dir: &Path, name: &str, value: &str) -> Result<()> {
+    let path = dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, format!("{value}\n"))?;
+    Ok(())
+}
+
+/// Create a relative symlink at `link_path` pointing to `target`, like sysfs
+fn link(target: &Path, link_path: &Path) -> Result<()> {
+    let from = link_path.parent().unwrap_or(Path::new("/"));
+    symlink(relative_path(target, from), link_path)?;
+    Ok(())
+}
+
+/// Express `target` relative to the directory `from` (both absolute)
+fn relative_path(target: &Path, from: &Path) -> PathBuf {
+    let target: Vec<Component> = target.components().collect();
+    let from: Vec<Component> = from.components().collect();
+    let common = target.iter().zip(&from).take_while(|(a, b)| a == b).count();
+
+    from[common..]
+        .iter()
+        .map(|_| Component::ParentDir)
+        .chain(target[common..].iter().copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() -> Result<()> {
+        assert_eq!(
+            relative_path(
+                Path::new("/sys/devices/a/hidraw/hidraw0"),
+                Path::new("/sys/class/hidraw")
+            ),
+            PathBuf::from("../../devices/a/hidraw/hidraw0")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_links_are_relative() -> Result<()> {
+        let mut fixture = SysfsFixture::new()?;
+        fixture.add(&FixtureDevice::usb("hidraw0", 0x1234, 0x5678))?;
+
+        let class_link = fixture.sysfs_root().join("class/hidraw/hidraw0");
+        let target = fs::read_link(&class_link)?;
+        assert!(target.starts_with("../../devices"));
+        assert!(fs::canonicalize(class_link.join("device"))?.ends_with("0003:1234:5678.0001"));
+
+        Ok(())
+    }
+}
@@ -4,6 +4,11 @@
 //! - rustix: For fixed-size operations where the size is known at compile time
 //! - libc: For runtime-sized operations where the size is computed dynamically
 
+use crate::hidraw::{MAX_REPORT_LEN, sys};
+use crate::{Error, Result};
+use rustix::fd::AsFd;
+use std::sync::OnceLock;
+
 // Re-export rustix implementations for fixed-size operations
 pub use ioctl_rustix::{
     get_raw_info, get_raw_name, get_raw_phys, get_raw_uniq, get_report_descriptor,
@@ -15,3 +20,132 @@ pub use ioctl_libc::{ioctl_read_buf, ioctl_write_buf};
 
 // Import modules
 use super::{ioctl_libc, ioctl_rustix};
+
+/// Get the current input report via the control pipe (HIDIOCGINPUT)
+///
+/// The first byte of `buf` is set to `report_id`.
+pub fn get_input_report<Fd: AsFd>(fd: Fd, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+    check_control_report_len(buf.len())?;
+
+    // First byte must be the report ID
+    buf[0] = report_id;
+
+    ioctl_read_buf(fd, sys::hidiocginput(buf.len()), buf)
+        .map_err(|e| unsupported_if_unknown(e, "HIDIOCGINPUT"))
+}
+
+/// Send an output report via the control pipe (HIDIOCSOUTPUT)
+pub fn send_output_report<Fd: AsFd>(fd: Fd, data: &[u8]) -> Result<()> {
+    check_control_report_len(data.len())?;
+
+    ioctl_write_buf(fd, sys::hidiocsoutput(data.len()), data)
+        .map_err(|e| unsupported_if_unknown(e, "HIDIOCSOUTPUT"))?;
+    Ok(())
+}
+
+/// Check a buffer length against the bounds hidraw enforces on
+/// HIDIOCGINPUT and HIDIOCSOUTPUT
+///
+/// The kernel rejects anything shorter than a report ID plus one byte or
+/// longer than `HID_MAX_BUFFER_SIZE` with `EINVAL`, which would otherwise be
+/// indistinguishable from the pre-5.11 direction check.
+pub fn check_control_report_len(len: usize) -> Result<()> {
+    if !(2..=MAX_REPORT_LEN).contains(&len) {
+        return Err(Error::InvalidParameter(format!(
+            "Report length must be between 2 and {MAX_REPORT_LEN} bytes, got {len}"
+        )));
+    }
+    Ok(())
+}
+
+/// Map the errors an optional hidraw ioctl gets from older kernels to
+/// `Error::NotSupported`
+///
+/// Kernels that predate an ioctl reject it with `ENOTTY`; before Linux 5.11
+/// the read-write HIDIOCGINPUT and HIDIOCSOUTPUT fail hidraw's direction
+/// check with `EINVAL` instead. On newer kernels `EINVAL` is a real error and
+/// is returned unchanged, as is any other error.
+pub fn unsupported_if_unknown(err: Error, ioctl_name: &str) -> Error {
+    map_unsupported(err, ioctl_name, kernel_older_than(5, 11))
+}
+
+fn map_unsupported(err: Error, ioctl_name: &str, pre_5_11: bool) -> Error {
+    match &err {
+        Error::Io(io_err)
+            if io_err.raw_os_error() == Some(libc::ENOTTY)
+                || (pre_5_11 && io_err.raw_os_error() == Some(libc::EINVAL)) =>
+        {
+            Error::NotSupported(format!(
+                "{ioctl_name} is not supported by this kernel (requires Linux 5.11+)"
+            ))
+        }
+        _ => err,
+    }
+}
+
+/// Whether the running kernel's release is below `major.minor`
+///
+/// An unparseable release string counts as new enough.
+fn kernel_older_than(major: u32, minor: u32) -> bool {
+    static RELEASE: OnceLock<Option<(u32, u32)>> = OnceLock::new();
+
+    let release = RELEASE.get_or_init(|| {
+        let uname = rustix::system::uname();
+        parse_kernel_release(&uname.release().to_string_lossy())
+    });
+    release.is_some_and(|running| running < (major, minor))
+}
+
+/// Parse the `major.minor` prefix of a release such as `6.8.0-45-generic`
+fn parse_kernel_release(release: &str) -> Option<(u32, u32)> {
+    let mut parts = release.split(['.', '-']);
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next()?.parse().ok()?;
+    Some((major, minor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unsupported_if_unknown() -> Result<()> {
+        for pre_5_11 in [false, true] {
+            let err = map_unsupported(Error::from(libc::ENOTTY), "HIDIOCGINPUT", pre_5_11);
+            assert!(matches!(err, Error::NotSupported(ref msg) if msg.contains("HIDIOCGINPUT")));
+        }
+        let err = map_unsupported(Error::from(libc::EINVAL), "HIDIOCGINPUT", true);
+        assert!(matches!(err, Error::NotSupported(_)));
+        let err = map_unsupported(Error::from(libc::EINVAL), "HIDIOCGINPUT", false);
+        assert!(matches!(err, Error::Io(ref e) if e.raw_os_error() == Some(libc::EINVAL)));
+        let err = map_unsupported(Error::from(libc::EPIPE), "HIDIOCSOUTPUT", true);
+        assert!(matches!(err, Error::Io(ref e) if e.raw_os_error() == Some(libc::EPIPE)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_control_report_len() {
+        assert!(matches!(
+            check_control_report_len(0),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            check_control_report_len(1),
+            Err(Error::InvalidParameter(_))
+        ));
+        assert!(check_control_report_len(2).is_ok());
+        assert!(check_control_report_len(MAX_REPORT_LEN).is_ok());
+        assert!(matches!(
+            check_control_report_len(MAX_REPORT_LEN + 1),
+            Err(Error::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_kernel_release() {
+        assert_eq!(parse_kernel_release("6.8.0-45-generic"), Some((6, 8)));
+        assert_eq!(parse_kernel_release("5.10-rc1"), Some((5, 10)));
+        assert_eq!(parse_kernel_release("4.19.0"), Some((4, 19)));
+        assert_eq!(parse_kernel_release("garbage"), None);
+    }
+}
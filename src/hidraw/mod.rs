@@ -1,11 +1,21 @@
 //! Linux hidraw backend implementation
 
+mod details;
 mod device;
 mod enumerate;
+#[cfg(any(test, feature = "test-util"))]
+mod fixture;
 pub(crate) mod ioctl;
 pub(crate) mod ioctl_libc;
 pub(crate) mod ioctl_rustix;
 pub(crate) mod sys;
 
+/// Largest report hidraw reads or writes (`HID_MAX_BUFFER_SIZE` in <linux/hid.h>)
+pub(crate) const MAX_REPORT_LEN: usize = 4096;
+
+pub use details::{DeviceDetails, UsbDetails, UsbHub};
 pub use device::HidrawDevice;
-pub use enumerate::{enumerate, get_device_info};
+pub(crate) use device::is_unplugged;
+pub use enumerate::{Enumerator, enumerate, get_device_details, get_device_info, usb_port_path};
+#[cfg(any(test, feature = "test-util"))]
+pub use fixture::{FixtureDevice, SysfsFixture};
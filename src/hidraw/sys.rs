@@ -68,6 +68,16 @@ pub fn hidiocsfeature(len: usize) -> u32 {
 /// Get raw unique ID
 pub const HIDIOCGRAWUNIQ: u32 = _ior(HID_TYPE, 0x08, 256);
 
+/// Get input report (Linux 5.11+)
+pub fn hidiocginput(len: usize) -> u32 {
+    _iowr(HID_TYPE, 0x0A, len as u32)
+}
+
+/// Send output report (Linux 5.11+)
+pub fn hidiocsoutput(len: usize) -> u32 {
+    _iowr(HID_TYPE, 0x0B, len as u32)
+}
+
 /// Raw device info structure
 #[repr(C)]
 pub struct HidrawDevInfo {
@@ -82,3 +92,17 @@ pub struct HidrawReportDescriptor {
     pub size: u32,
     pub value: [u8; 4096],
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_opcodes_match_kernel() {
+        // Values of HIDIOC*(64) as computed by <linux/hidraw.h>
+        assert_eq!(hidiocgfeature(64), 0xC040_4806);
+        assert_eq!(hidiocsfeature(64), 0xC040_4807);
+        assert_eq!(hidiocginput(64), 0xC040_480A);
+        assert_eq!(hidiocsoutput(64), 0xC040_480B);
+    }
+}
@@ -33,32 +33,58 @@
 //! }
 //! ```
 
+pub mod capture;
+pub mod descriptor;
 pub mod device;
+pub mod device_id;
 pub mod error;
+pub mod filter;
 pub mod hidraw;
+pub mod monitor;
+pub mod permissions;
+pub mod poller;
 pub mod protocol;
+pub mod reconnect;
+pub mod split;
+pub mod transport;
+pub mod uhid;
 
 #[cfg(feature = "async")]
 pub mod async_io;
+#[cfg(feature = "async")]
+pub mod multiplexer;
 
 pub mod coldcard;
 
 // Re-exports for convenience
-pub use device::{DeviceInfo, HidDevice, ReportDescriptor};
+pub use device::{BusType, DeviceInfo, HidDevice, ReportDescriptor};
+pub use device_id::DeviceId;
 pub use error::{Error, Result};
-pub use hidraw::enumerate;
+pub use filter::DeviceFilter;
+pub use hidraw::{DeviceDetails, enumerate};
+pub use split::{HidReader, HidWriter};
+pub use transport::Transport;
 
 /// Prelude module for convenient imports
 pub mod prelude {
-    pub use crate::{DeviceInfo, HidDevice};
+    pub use crate::{DeviceFilter, DeviceInfo, HidDevice};
     pub use crate::{Error, Result};
-    pub use crate::{enumerate, find_devices};
+    pub use crate::{enumerate, enumerate_with, find_devices};
 }
 
 /// Find devices matching vendor and product ID
 pub fn find_devices(vendor_id: u16, product_id: u16) -> Result<Vec<DeviceInfo>> {
-    Ok(enumerate()?
-        .into_iter()
-        .filter(|d| d.vendor_id == vendor_id && d.product_id == product_id)
-        .collect())
+    enumerate_with(
+        &DeviceFilter::new()
+            .vendor_id(vendor_id)
+            .product_id(product_id),
+    )
+}
+
+/// Find devices matching a [`DeviceFilter`]
+///
+/// Devices whose bus and IDs don't match are skipped before their string
+/// attributes are read from sysfs.
+pub fn enumerate_with(filter: &DeviceFilter) -> Result<Vec<DeviceInfo>> {
+    hidraw::Enumerator::new().enumerate_with(filter)
 }
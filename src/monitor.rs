@@ -0,0 +1,421 @@
+//! Continuous input report monitoring
+//!
+//! A [`ReportMonitor`] reads input reports from one or more devices as they
+//! arrive, stamps them with the time since monitoring started and the gap to
+//! the previous report, and decodes their fields with the device's report
+//! descriptor. Reports identical to the previous one with the same report ID
+//! can be suppressed, which makes it easy to spot the bytes that change when
+//! a button is pressed or a knob is turned.
+//!
+//! ```no_run
+//! use hidraw_rs::monitor::ReportMonitor;
+//! use hidraw_rs::{HidDevice, Result};
+//!
+//! fn main() -> Result<()> {
+//!     let device = HidDevice::open_path("/dev/hidraw0")?;
+//!     let monitor = ReportMonitor::new([device]).suppress_unchanged(true);
+//!
+//!     for report in monitor.take(10) {
+//!         let report = report?;
+//!         println!("{secs:.6}s", secs = report.timestamp.as_secs_f64());
+//!         print!("{dump}", dump = report.hexdump());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::descriptor::{FieldValue, ReportLayout};
+use crate::protocol::ReportType;
+use crate::{Error, HidDevice, Result};
+use rustix::event::{PollFd, PollFlags, poll};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Read buffer size for devices whose descriptor could not be parsed
+const FALLBACK_REPORT_LEN: usize = 64;
+
+/// How long the blocking iterator waits per poll before checking again
+const ITERATOR_POLL: Duration = Duration::from_secs(1);
+
+/// One input report observed by a [`ReportMonitor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitoredReport {
+    /// Index of the device in the order passed to [`ReportMonitor::new`]
+    pub source: usize,
+    /// Time since the monitor was created
+    pub timestamp: Duration,
+    /// Time since the previous reported report from the same device
+    pub delta: Option<Duration>,
+    /// Report ID (0 when the device does not use numbered reports)
+    pub report_id: u8,
+    /// Report bytes as read, including any report ID prefix
+    pub data: Vec<u8>,
+    /// Field values decoded with the report descriptor
+    pub fields: Vec<FieldValue>,
+    /// Number of identical reports suppressed since this report ID last changed
+    pub suppressed: usize,
+}
+
+impl MonitoredReport {
+    /// Hexdump of the report bytes with offsets and an ASCII column
+    pub fn hexdump(&self) -> String {
+        hexdump(&self.data)
+    }
+}
+
+/// Format bytes as a hexdump, 16 bytes per line with offsets and ASCII
+pub fn hexdump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        out.push_str(&format!(
+            "{offset:04x}  {hex:<47}  |{ascii}|\n",
+            offset = row * 16,
+            hex = hex.join(" ")
+        ));
+    }
+    out
+}
+
+/// Per-device timing, change tracking and decoding state
+#[derive(Debug, Default)]
+struct Tracker {
+    layout: Option<ReportLayout>,
+    uses_report_ids: bool,
+    last_time: Option<Duration>,
+    last_data: HashMap<u8, Vec<u8>>,
+    suppressed: HashMap<u8, usize>,
+}
+
+impl Tracker {
+    fn new(layout: Option<ReportLayout>) -> Self {
+        Self {
+            uses_report_ids: layout.as_ref().is_some_and(ReportLayout::uses_report_ids),
+            layout,
+            ..Self::default()
+        }
+    }
+
+    /// Turn raw report bytes into a report, or `None` if it is suppressed
+    fn observe(
+        &mut self,
+        source: usize,
+        data: &[u8],
+        now: Duration,
+        suppress_unchanged: bool,
+    ) -> Option<MonitoredReport> {
+        let (report_id, payload) = match data.split_first() {
+            Some((&id, payload)) if self.uses_report_ids => (id, payload),
+            _ => (0, data),
+        };
+
+        if suppress_unchanged
+            && self
+                .last_data
+                .get(&report_id)
+                .is_some_and(|last| last == data)
+        {
+            *self.suppressed.entry(report_id).or_default() += 1;
+            return None;
+        }
+
+        let delta = self.last_time.map(|last| now.saturating_sub(last));
+        self.last_time = Some(now);
+        self.last_data.insert(report_id, data.to_vec());
+
+        Some(MonitoredReport {
+            source,
+            timestamp: now,
+            delta,
+            report_id,
+            data: data.to_vec(),
+            fields: self
+                .layout
+                .as_ref()
+                .map(|layout| layout.decode(ReportType::Input, report_id, payload))
+                .unwrap_or_default(),
+            suppressed: self.suppressed.remove(&report_id).unwrap_or(0),
+        })
+    }
+}
+
+struct Source {
+    /// `None` once the device failed and was dropped from monitoring
+    device: Option<HidDevice>,
+    buf: Vec<u8>,
+    tracker: Tracker,
+}
+
+/// Streams input reports from one or more devices
+///
+/// Reports are returned in arrival order across devices. A device that fails
+/// (for example because it was unplugged) returns its error once and is then
+/// dropped; the remaining devices keep being monitored.
+pub struct ReportMonitor {
+    sources: Vec<Source>,
+    ready: VecDeque<usize>,
+    start: Instant,
+    suppress_unchanged: bool,
+}
+
+impl ReportMonitor {
+    /// Monitor the given devices
+    ///
+    /// Each device's report descriptor is parsed up front for sizing the
+    /// read buffer and decoding fields. Devices without a usable descriptor
+    /// are still monitored, just without decoded fields.
+    pub fn new(devices: impl IntoIterator<Item = HidDevice>) -> Self {
+        let sources = devices
+            .into_iter()
+            .map(|device| {
+                let layout = device.report_layout().ok().cloned();
+                let len = device
+                    .max_input_report_len()
+                    .ok()
+                    .filter(|&len| len > 0)
+                    .unwrap_or(FALLBACK_REPORT_LEN);
+                Source {
+                    device: Some(device),
+                    buf: vec![0u8; len],
+                    tracker: Tracker::new(layout),
+                }
+            })
+            .collect();
+
+        Self {
+            sources,
+            ready: VecDeque::new(),
+            start: Instant::now(),
+            suppress_unchanged: false,
+        }
+    }
+
+    /// Skip reports identical to the previous one with the same report ID
+    pub fn suppress_unchanged(mut self, suppress: bool) -> Self {
+        self.suppress_unchanged = suppress;
+        self
+    }
+
+    /// Device at index `source`, unless it has been dropped after an error
+    pub fn device(&self, source: usize) -> Option<&HidDevice> {
+        self.sources.get(source)?.device.as_ref()
+    }
+
+    /// Number of devices still being monitored
+    pub fn active_devices(&self) -> usize {
+        self.sources.iter().filter(|s| s.device.is_some()).count()
+    }
+
+    /// Wait up to `timeout` for the next report
+    ///
+    /// Returns `Ok(None)` on timeout and `Err(Error::Disconnected)` once no
+    /// devices are left.
+    pub fn next_report(&mut self, timeout: Duration) -> Result<Option<MonitoredReport>> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            while let Some(index) = self.ready.pop_front() {
+                if let Some(report) = self.read_source(index)? {
+                    return Ok(Some(report));
+                }
+            }
+
+            if self.active_devices() == 0 {
+                return Err(Error::Disconnected);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            self.poll_sources(remaining)?;
+            if self.ready.is_empty() && Instant::now() >= deadline {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Wait for readable devices and queue them in `ready`
+    fn poll_sources(&mut self, timeout: Duration) -> Result<()> {
+        let (indexes, mut fds): (Vec<usize>, Vec<PollFd<'_>>) = self
+            .sources
+            .iter()
+            .enumerate()
+            .filter_map(|(index, source)| {
+                let device = source.device.as_ref()?;
+                Some((index, PollFd::new(device, PollFlags::IN)))
+            })
+            .unzip();
+
+        let timeout_spec = rustix::time::Timespec {
+            tv_sec: timeout.as_secs() as i64,
+            tv_nsec: timeout.subsec_nanos() as i64,
+        };
+        match poll(&mut fds, Some(&timeout_spec)) {
+            Ok(_) | Err(rustix::io::Errno::INTR) => {}
+            Err(e) => return Err(Error::Io(e.into())),
+        }
+
+        let ready: Vec<usize> = indexes
+            .into_iter()
+            .zip(&fds)
+            .filter(|(_, fd)| !fd.revents().is_empty())
+            .map(|(index, _)| index)
+            .collect();
+        self.ready.extend(ready);
+        Ok(())
+    }
+
+    /// Read one report from a readable device
+    fn read_source(&mut self, index: usize) -> Result<Option<MonitoredReport>> {
+        let now = self.start.elapsed();
+        let suppress = self.suppress_unchanged;
+        let Some(source) = self.sources.get_mut(index) else {
+            return Ok(None);
+        };
+        let Some(device) = source.device.as_mut() else {
+            return Ok(None);
+        };
+
+        match device.read_timeout(&mut source.buf, Duration::ZERO) {
+            Ok(n) => Ok(source
+                .tracker
+                .observe(index, &source.buf[..n], now, suppress)),
+            Err(Error::Timeout) => Ok(None),
+            Err(e) => {
+                source.device = None;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Iterator for ReportMonitor {
+    type Item = Result<MonitoredReport>;
+
+    /// Block until the next report; ends once every device has been dropped
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_report(ITERATOR_POLL) {
+                Ok(Some(report)) => return Some(Ok(report)),
+                Ok(None) => continue,
+                Err(Error::Disconnected) if self.active_devices() == 0 => return None,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ReportMonitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReportMonitor")
+            .field("devices", &self.sources.len())
+            .field("active_devices", &self.active_devices())
+            .field("suppress_unchanged", &self.suppress_unchanged)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::descriptor::{CollectionType, DescriptorBuilder, MainFlags};
+
+    fn numbered_layout() -> Result<ReportLayout> {
+        let bytes = DescriptorBuilder::new()
+            .usage_page(0xFF00)
+            .usage(0x01)
+            .collection(CollectionType::Application, |c| {
+                c.report_id(1)
+                    .usage(0x10)
+                    .logical_minimum(0)
+                    .logical_maximum(255)
+                    .report_size(8)
+                    .report_count(1)
+                    .input(MainFlags::VARIABLE)
+                    .report_id(2)
+                    .usage(0x20)
+                    .input(MainFlags::VARIABLE)
+            })
+            .to_bytes();
+        ReportLayout::parse(&bytes)
+    }
+
+    #[test]
+    fn test_tracker_timing_and_decoding() -> Result<()> {
+        let mut tracker = Tracker::new(Some(numbered_layout()?));
+
+        let first = tracker
+            .observe(0, &[1, 0x2A], Duration::from_millis(10), false)
+            .ok_or(Error::Timeout)?;
+        assert_eq!(first.report_id, 1);
+        assert_eq!(first.delta, None);
+        assert_eq!(
+            first.fields,
+            [FieldValue {
+                usage: 0xFF00_0010,
+                value: 42
+            }]
+        );
+
+        let second = tracker
+            .observe(0, &[2, 0x07], Duration::from_millis(25), false)
+            .ok_or(Error::Timeout)?;
+        assert_eq!(second.delta, Some(Duration::from_millis(15)));
+        assert_eq!(second.fields[0].usage_id(), 0x20);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracker_suppresses_unchanged_per_report_id() -> Result<()> {
+        let mut tracker = Tracker::new(Some(numbered_layout()?));
+        let at = Duration::from_millis;
+
+        assert!(tracker.observe(0, &[1, 5], at(0), true).is_some());
+        assert!(tracker.observe(0, &[2, 5], at(1), true).is_some());
+        assert!(tracker.observe(0, &[1, 5], at(2), true).is_none());
+        assert!(tracker.observe(0, &[1, 5], at(3), true).is_none());
+
+        let changed = tracker
+            .observe(0, &[1, 6], at(4), true)
+            .ok_or(Error::Timeout)?;
+        assert_eq!(changed.suppressed, 2);
+        // Delta is measured from the last report that was returned
+        assert_eq!(changed.delta, Some(at(3)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tracker_without_descriptor() -> Result<()> {
+        let mut tracker = Tracker::new(None);
+        let report = tracker
+            .observe(3, &[1, 2, 3], Duration::ZERO, false)
+            .ok_or(Error::Timeout)?;
+
+        assert_eq!(report.source, 3);
+        assert_eq!(report.report_id, 0);
+        assert!(report.fields.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hexdump() -> Result<()> {
+        let data: Vec<u8> = (0x40..0x52).collect();
+        assert_eq!(
+            hexdump(&data),
+            "0000  40 41 42 43 44 45 46 47 48 49 4a 4b 4c 4d 4e 4f  |@ABCDEFGHIJKLMNO|\n\
+             0010  50 51                                            |PQ|\n"
+        );
+        Ok(())
+    }
+}
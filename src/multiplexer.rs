@@ -0,0 +1,469 @@
+//! Request/response routing for devices shared by several logical channels
+//!
+//! Protocols such as CTAPHID (channel ID in the first four bytes) or vendor
+//! protocols using report IDs as channels multiplex independent
+//! conversations over one device. A [`Multiplexer`] owns the read half of an
+//! [`AsyncHidDevice`] and routes every input report to the queue of the
+//! channel a user-supplied key extractor names; reports for channels nobody
+//! claimed go to the [`Fallback`] queue.
+//!
+//! ```no_run
+//! use hidraw_rs::async_io::AsyncHidDevice;
+//! use hidraw_rs::multiplexer::Multiplexer;
+//!
+//! # async fn run() -> hidraw_rs::Result<()> {
+//! let device = AsyncHidDevice::open_first(0x1050, 0x0407).await?;
+//! // CTAPHID: the channel ID is the first four bytes of every report
+//! let (mux, _fallback) = Multiplexer::new(device, |report: &[u8]| {
+//!     report.first_chunk::<4>().map(|cid| u32::from_be_bytes(*cid))
+//! })?;
+//! let response = mux.request(0x0102_0304, &[0x00, 0x01, 0x02, 0x03, 0x04, 0x81]).await?;
+//! # drop(response);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::async_io::AsyncHidDevice;
+use crate::hidraw::{HidrawDevice, MAX_REPORT_LEN};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::sync::{Notify, mpsc};
+use tokio::task::JoinHandle;
+
+/// Extracts the channel key of an input report, `None` for unclaimable reports
+type KeyFn<K> = dyn Fn(&[u8]) -> Option<K> + Send + Sync;
+
+/// Routing state shared by the reader task, the multiplexer and its channels
+struct Router<K> {
+    key: Box<KeyFn<K>>,
+    queues: Mutex<Queues<K>>,
+    /// Signalled whenever a channel is released
+    released: Notify,
+}
+
+struct Queues<K> {
+    routes: HashMap<K, mpsc::UnboundedSender<Vec<u8>>>,
+    /// `None` once the router is closed
+    fallback: Option<mpsc::UnboundedSender<Vec<u8>>>,
+    /// Read error that stopped the reader task, if any
+    failure: Option<io::Error>,
+}
+
+impl<K> Router<K> {
+    fn queues(&self) -> MutexGuard<'_, Queues<K>> {
+        self.queues.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Close every queue so waiters see the device is gone, recording the
+    /// read error that ended the reader task
+    fn close(&self, failure: Option<io::Error>) {
+        let mut queues = self.queues();
+        queues.routes.clear();
+        queues.fallback = None;
+        if queues.failure.is_none() {
+            queues.failure = failure;
+        }
+        drop(queues);
+        self.released.notify_waiters();
+    }
+
+    /// Why the queues were closed: the read error, or `Error::Disconnected`
+    /// if the device went away or the multiplexer was dropped
+    fn failure(&self) -> Error {
+        match &self.queues().failure {
+            Some(e) => Error::Io(match e.raw_os_error() {
+                Some(errno) => io::Error::from_raw_os_error(errno),
+                None => io::Error::new(e.kind(), e.to_string()),
+            }),
+            None => Error::Disconnected,
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone> Router<K> {
+    fn new(key: Box<KeyFn<K>>, fallback: mpsc::UnboundedSender<Vec<u8>>) -> Self {
+        Self {
+            key,
+            queues: Mutex::new(Queues {
+                routes: HashMap::new(),
+                fallback: Some(fallback),
+                failure: None,
+            }),
+            released: Notify::new(),
+        }
+    }
+
+    /// Register a queue for `key`, `None` if it is already claimed
+    ///
+    /// After [`close`](Self::close) the queue is returned already closed.
+    fn claim(&self, key: K) -> Option<mpsc::UnboundedReceiver<Vec<u8>>> {
+        let mut queues = self.queues();
+        if queues.routes.contains_key(&key) {
+            return None;
+        }
+        let (tx, rx) = mpsc::unbounded_channel();
+        if queues.fallback.is_some() {
+            queues.routes.insert(key, tx);
+        }
+        Some(rx)
+    }
+
+    fn release(&self, key: &K) {
+        self.queues().routes.remove(key);
+        self.released.notify_waiters();
+    }
+
+    /// Deliver a report to its channel or the fallback queue
+    fn dispatch(&self, report: Vec<u8>) {
+        let key = (self.key)(&report);
+        let queues = self.queues();
+        let report = match key.and_then(|key| queues.routes.get(&key)) {
+            Some(route) => match route.send(report) {
+                Ok(()) => return,
+                // The channel was dropped mid-flight
+                Err(mpsc::error::SendError(report)) => report,
+            },
+            None => report,
+        };
+        // Nobody listening for unclaimed reports is fine
+        if let Some(fallback) = &queues.fallback {
+            let _ = fallback.send(report);
+        }
+    }
+}
+
+/// Routes input reports of one device to per-channel queues
+///
+/// Dropping the multiplexer stops the reader task and closes its handle on
+/// the device; channels then fail with `Error::Disconnected` and the
+/// fallback yields `None`.
+pub struct Multiplexer<K> {
+    router: Arc<Router<K>>,
+    writer: tokio::sync::Mutex<AsyncHidDevice>,
+    reader: JoinHandle<()>,
+}
+
+/// Queue of the reports routed to one channel, from [`Multiplexer::channel`]
+///
+/// Dropping it releases the channel.
+pub struct Channel<K: Eq + Hash + Clone> {
+    key: K,
+    reports: mpsc::UnboundedReceiver<Vec<u8>>,
+    router: Arc<Router<K>>,
+}
+
+/// Queue of the reports no channel claimed, from [`Multiplexer::new`]
+///
+/// Dropping it discards such reports.
+pub struct Fallback {
+    reports: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+impl<K> Multiplexer<K>
+where
+    K: Eq + Hash + Clone + Debug + Send + 'static,
+{
+    /// Take over `device`, routing reports by the key `key` extracts
+    ///
+    /// Spawns the reader task on the current tokio runtime. It reads through
+    /// a duplicate of `device`'s descriptor, switched to non-blocking and
+    /// driven by the runtime's reactor rather than the blocking pool, so
+    /// aborting it takes effect right away.
+    pub fn new(
+        device: AsyncHidDevice,
+        key: impl Fn(&[u8]) -> Option<K> + Send + Sync + 'static,
+    ) -> Result<(Self, Fallback)> {
+        // The duplicate shares the open file description, so O_NONBLOCK
+        // applies to the writer too. That's harmless: hidraw writes and
+        // report ioctls never return EAGAIN
+        let reader = device.try_clone_sync()?;
+        reader.set_nonblocking(true)?;
+        let reader = AsyncFd::new(reader)?;
+        let writer = device;
+        let (fallback_tx, fallback_rx) = mpsc::unbounded_channel();
+        let router = Arc::new(Router::new(Box::new(key), fallback_tx));
+
+        let reader = tokio::spawn(pump(reader, Arc::clone(&router)));
+        let multiplexer = Self {
+            router,
+            writer: tokio::sync::Mutex::new(writer),
+            reader,
+        };
+        Ok((
+            multiplexer,
+            Fallback {
+                reports: fallback_rx,
+            },
+        ))
+    }
+
+    /// Claim the queue for `key`
+    ///
+    /// Fails with `Error::InvalidParameter` if the channel is already claimed.
+    pub fn channel(&self, key: K) -> Result<Channel<K>> {
+        claim(&self.router, key.clone())
+            .ok_or_else(|| Error::InvalidParameter(format!("Channel {key:?} is already claimed")))
+    }
+
+    /// Write an output report
+    pub async fn send(&self, data: &[u8]) -> Result<usize> {
+        self.writer.lock().await.write(data).await
+    }
+
+    /// Send `data` and wait for the next report on channel `key`
+    ///
+    /// Only that single report is returned. Responses spanning several
+    /// reports, such as CTAPHID messages with continuation frames, need a
+    /// [`channel`](Self::channel) claimed before sending, then one
+    /// [`Channel::recv`] per frame.
+    ///
+    /// Requests on different channels run concurrently; a request on a
+    /// channel that is claimed waits until it is released.
+    pub async fn request(&self, key: K, data: &[u8]) -> Result<Vec<u8>> {
+        let mut channel = self.wait_for_channel(key).await;
+        self.send(data).await?;
+        channel.recv().await
+    }
+
+    /// Like [`request`](Self::request), failing with `Error::Timeout` if no
+    /// response arrives within `timeout`
+    pub async fn request_timeout(&self, key: K, data: &[u8], timeout: Duration) -> Result<Vec<u8>> {
+        tokio::time::timeout(timeout, self.request(key, data))
+            .await
+            .map_err(|_| Error::Timeout)?
+    }
+
+    async fn wait_for_channel(&self, key: K) -> Channel<K> {
+        loop {
+            // Register for the wakeup before checking, so a release between
+            // the check and the wait is not missed
+            let released = self.router.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            if let Some(channel) = claim(&self.router, key.clone()) {
+                return channel;
+            }
+            released.await;
+        }
+    }
+}
+
+impl<K> Drop for Multiplexer<K> {
+    fn drop(&mut self) {
+        self.reader.abort();
+        // The aborted task can't close the router itself
+        self.router.close(None);
+    }
+}
+
+impl<K: Debug> Debug for Multiplexer<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Multiplexer")
+            .field("running", &!self.reader.is_finished())
+            .finish()
+    }
+}
+
+fn claim<K: Eq + Hash + Clone>(router: &Arc<Router<K>>, key: K) -> Option<Channel<K>> {
+    let reports = router.claim(key.clone())?;
+    Some(Channel {
+        key,
+        reports,
+        router: Arc::clone(router),
+    })
+}
+
+/// Reader task: route reports until the device fails or disconnects
+async fn pump<K: Eq + Hash + Clone>(mut reader: AsyncFd<HidrawDevice>, router: Arc<Router<K>>) {
+    let mut buf = vec![0u8; MAX_REPORT_LEN];
+    let failure = loop {
+        let mut ready = match reader.readable_mut().await {
+            Ok(ready) => ready,
+            Err(e) => break Some(e),
+        };
+        let read = ready.try_io(|device| match device.get_mut().read(&mut buf) {
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock => Err(e),
+            result => Ok(result),
+        });
+        match read {
+            // Spurious wakeup; readiness was cleared
+            Err(_would_block) => {}
+            Ok(Ok(Ok(n))) if n > 0 => router.dispatch(buf[..n].to_vec()),
+            // EOF or unplugged
+            Ok(Ok(Ok(_) | Err(Error::Disconnected))) => break None,
+            Ok(Ok(Err(Error::Io(e))) | Err(e)) => break Some(e),
+            Ok(Ok(Err(e))) => break Some(io::Error::other(e)),
+        }
+    };
+    router.close(failure);
+}
+
+impl<K: Eq + Hash + Clone> Channel<K> {
+    /// The channel's key
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Wait for the next report
+    ///
+    /// Once the reader task has stopped, fails with the read error that
+    /// stopped it, or `Error::Disconnected` if the device went away.
+    pub async fn recv(&mut self) -> Result<Vec<u8>> {
+        match self.reports.recv().await {
+            Some(report) => Ok(report),
+            None => Err(self.router.failure()),
+        }
+    }
+
+    /// Take a queued report without waiting
+    pub fn try_recv(&mut self) -> Option<Vec<u8>> {
+        self.reports.try_recv().ok()
+    }
+}
+
+impl<K: Eq + Hash + Clone> Drop for Channel<K> {
+    fn drop(&mut self) {
+        self.router.release(&self.key);
+    }
+}
+
+impl<K: Eq + Hash + Clone + Debug> Debug for Channel<K> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Channel").field("key", &self.key).finish()
+    }
+}
+
+impl Fallback {
+    /// Wait for the next unclaimed report, `None` once the device is gone
+    pub async fn recv(&mut self) -> Option<Vec<u8>> {
+        self.reports.recv().await
+    }
+
+    /// Take a queued unclaimed report without waiting
+    pub fn try_recv(&mut self) -> Option<Vec<u8>> {
+        self.reports.try_recv().ok()
+    }
+}
+
+impl Debug for Fallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Fallback").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn router() -> (Arc<Router<u8>>, Fallback) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let router = Arc::new(Router::new(
+            Box::new(|report: &[u8]| report.first().copied()),
+            tx,
+        ));
+        (router, Fallback { reports: rx })
+    }
+
+    #[test]
+    fn test_dispatch() -> Result<()> {
+        let (router, mut fallback) = router();
+        let mut one = claim(&router, 1).ok_or(Error::Timeout)?;
+        assert!(claim(&router, 1).is_none());
+
+        router.dispatch(vec![1, 0xAA]);
+        router.dispatch(vec![2, 0xBB]);
+        router.dispatch(Vec::new());
+
+        assert_eq!(one.try_recv(), Some(vec![1, 0xAA]));
+        assert_eq!(one.try_recv(), None);
+        assert_eq!(fallback.try_recv(), Some(vec![2, 0xBB]));
+        assert_eq!(fallback.try_recv(), Some(Vec::new()));
+
+        // Released channels fall back again and can be claimed anew
+        drop(one);
+        router.dispatch(vec![1, 0xCC]);
+        assert_eq!(fallback.try_recv(), Some(vec![1, 0xCC]));
+        assert!(claim(&router, 1).is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reads_the_given_device() -> Result<()> {
+        use std::io::{Read, Write};
+
+        // A socket pair has no device node to reopen, so reports only
+        // arrive if the reader uses the descriptor it was given
+        let path = "/dev/hidraw-test0";
+        let (raw, mut peer) = HidrawDevice::socket_pair(path)?;
+        let device = AsyncHidDevice::from_raw(raw, crate::DeviceInfo::new(path, 0xFFFF, 0xFFFE));
+        let (mux, mut fallback) =
+            Multiplexer::new(device, |report: &[u8]| report.first().copied())?;
+
+        peer.write_all(&[7, 0xAA])?;
+        assert_eq!(fallback.recv().await, Some(vec![7, 0xAA]));
+
+        assert_eq!(mux.send(&[0, 1, 2]).await?, 3);
+        let mut sent = [0u8; 3];
+        peer.read_exact(&mut sent)?;
+        assert_eq!(sent, [0, 1, 2]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pump_stops_when_aborted() -> Result<()> {
+        use std::io::{Read, Write};
+
+        let (device, mut peer) = HidrawDevice::socket_pair("/dev/hidraw-test0")?;
+        device.set_nonblocking(true)?;
+        let (router, mut fallback) = router();
+        let reader = tokio::spawn(pump(AsyncFd::new(device)?, Arc::clone(&router)));
+
+        peer.write_all(&[9, 0xAA])?;
+        assert_eq!(fallback.recv().await, Some(vec![9, 0xAA]));
+
+        // Aborting while waiting for the next report closes the handle
+        // right away instead of after another report arrives
+        reader.abort();
+        assert!(reader.await.is_err_and(|e| e.is_cancelled()));
+        let mut buf = [0u8; 1];
+        assert_eq!(peer.read(&mut buf)?, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_ends_channels() -> Result<()> {
+        let (router, mut fallback) = router();
+        let mut channel = claim(&router, 7).ok_or(Error::Timeout)?;
+        router.close(None);
+        assert!(matches!(channel.recv().await, Err(Error::Disconnected)));
+        assert_eq!(fallback.recv().await, None);
+
+        // Claims after a disconnect don't wait forever
+        let mut late = claim(&router, 8).ok_or(Error::Timeout)?;
+        assert!(matches!(late.recv().await, Err(Error::Disconnected)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_close_reports_read_error() -> Result<()> {
+        let (router, _fallback) = router();
+        let mut channel = claim(&router, 7).ok_or(Error::Timeout)?;
+        router.close(Some(io::Error::from_raw_os_error(libc::EPERM)));
+
+        assert!(matches!(
+            channel.recv().await,
+            Err(Error::Io(e)) if e.raw_os_error() == Some(libc::EPERM)
+        ));
+        // A later close by dropping the multiplexer keeps the cause
+        router.close(None);
+        assert!(matches!(router.failure(), Error::Io(_)));
+        Ok(())
+    }
+}
@@ -0,0 +1,512 @@
+//! Permission diagnostics and udev rule generation
+//!
+//! Opening `/dev/hidrawN` as a regular user usually fails with
+//! [`Error::PermissionDenied`](crate::Error::PermissionDenied) until a udev
+//! rule grants access. [`diagnose`] explains why a node cannot be opened and
+//! [`UdevRule`] produces a rule that fixes it.
+//!
+//! ```no_run
+//! use hidraw_rs::permissions::{UdevRule, diagnose};
+//! use hidraw_rs::prelude::*;
+//!
+//! fn main() -> Result<()> {
+//!     for info in enumerate()? {
+//!         let report = diagnose(&info.path)?;
+//!         if !report.can_open() {
+//!             println!("{report}");
+//!             let rule = UdevRule::for_device(&info);
+//!             println!("# /etc/udev/rules.d/{file}\n{rule}", file = rule.file_name());
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{BusType, DeviceInfo, Result};
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// `CAP_DAC_OVERRIDE` bit in the capability sets
+const CAP_DAC_OVERRIDE: u32 = 1;
+
+/// POSIX ACL xattr tags (`<linux/posix_acl_xattr.h>`)
+const ACL_USER: u16 = 0x02;
+const ACL_GROUP: u16 = 0x08;
+const ACL_MASK: u16 = 0x10;
+const ACL_XATTR_VERSION: u32 = 2;
+
+/// Named user or group entry of a POSIX access ACL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AclEntry {
+    /// `user:<uid>:<perms>`, as added by the `uaccess` tag
+    User { uid: u32, read: bool, write: bool },
+    /// `group:<gid>:<perms>`
+    Group { gid: u32, read: bool, write: bool },
+}
+
+/// Why a hidraw node can or cannot be opened by this process
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PermissionReport {
+    /// Device node examined
+    pub path: PathBuf,
+    /// Whether the node exists
+    pub exists: bool,
+    /// Owner user ID and name
+    pub owner: Option<(u32, Option<String>)>,
+    /// Owner group ID and name
+    pub group: Option<(u32, Option<String>)>,
+    /// Permission bits (e.g. 0o600)
+    pub mode: Option<u32>,
+    /// Whether the current user is a member of the node's group
+    pub in_group: bool,
+    /// Named entries of the node's access ACL
+    pub acl: Vec<AclEntry>,
+    /// Whether the process can read and write the node (`access(2)`)
+    pub readable: bool,
+    /// See `readable`
+    pub writable: bool,
+    /// Whether the process has `CAP_DAC_OVERRIDE` (e.g. running as root)
+    pub cap_dac_override: bool,
+    /// Whether the kernel exposes hidraw devices (`/sys/class/hidraw`)
+    pub hidraw_available: bool,
+    /// Whether the uhid module is loaded (`/dev/uhid`)
+    pub uhid_available: bool,
+}
+
+impl PermissionReport {
+    /// Whether the node can be opened for reading and writing
+    pub fn can_open(&self) -> bool {
+        self.exists && self.readable && self.writable
+    }
+
+    /// Human readable next steps, empty when the node can be opened
+    pub fn suggestions(&self) -> Vec<String> {
+        let mut hints = Vec::new();
+        if !self.hidraw_available {
+            hints.push(
+                "The kernel has no hidraw support; load it with `modprobe hidraw` \
+                 or enable CONFIG_HIDRAW"
+                    .to_string(),
+            );
+        }
+        if !self.exists {
+            hints.push(format!(
+                "{path} does not exist; check the device is plugged in",
+                path = self.path.display()
+            ));
+            return hints;
+        }
+        if self.can_open() {
+            return hints;
+        }
+
+        hints.push(
+            "Install a udev rule granting access (see UdevRule), then run \
+             `sudo udevadm control --reload-rules && sudo udevadm trigger` and replug the device"
+                .to_string(),
+        );
+        if let Some((_, Some(name))) = &self.group
+            && !self.in_group
+            && self.mode.is_some_and(|mode| mode & 0o060 == 0o060)
+        {
+            hints.push(format!(
+                "The node is group-accessible; add yourself with `sudo usermod -aG {name} $USER` and log in again"
+            ));
+        }
+        hints
+    }
+}
+
+impl fmt::Display for PermissionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |id: &Option<(u32, Option<String>)>| match id {
+            Some((id, Some(name))) => format!("{name} ({id})"),
+            Some((id, None)) => id.to_string(),
+            None => "?".to_string(),
+        };
+        let yes_no = |b: bool| if b { "yes" } else { "no" };
+
+        writeln!(f, "Device: {path}", path = self.path.display())?;
+        if !self.exists {
+            writeln!(f, "  Node does not exist")?;
+        } else {
+            writeln!(f, "  Owner: {owner}", owner = describe(&self.owner))?;
+            writeln!(
+                f,
+                "  Group: {group} (member: {member})",
+                group = describe(&self.group),
+                member = yes_no(self.in_group)
+            )?;
+            if let Some(mode) = self.mode {
+                writeln!(f, "  Mode: {mode:04o}")?;
+            }
+            for entry in &self.acl {
+                let (kind, id, read, write) = match *entry {
+                    AclEntry::User { uid, read, write } => ("user", uid, read, write),
+                    AclEntry::Group { gid, read, write } => ("group", gid, read, write),
+                };
+                writeln!(
+                    f,
+                    "  ACL: {kind}:{id}:{r}{w}",
+                    r = if read { 'r' } else { '-' },
+                    w = if write { 'w' } else { '-' }
+                )?;
+            }
+            writeln!(
+                f,
+                "  Access: read {read}, write {write}",
+                read = yes_no(self.readable),
+                write = yes_no(self.writable)
+            )?;
+        }
+        writeln!(
+            f,
+            "  CAP_DAC_OVERRIDE: {cap}",
+            cap = yes_no(self.cap_dac_override)
+        )?;
+        writeln!(
+            f,
+            "  hidraw: {hidraw}, uhid: {uhid}",
+            hidraw = yes_no(self.hidraw_available),
+            uhid = yes_no(self.uhid_available)
+        )?;
+        for hint in self.suggestions() {
+            writeln!(f, "  Hint: {hint}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Examine why the current process can or cannot open a hidraw node
+pub fn diagnose(path: &Path) -> Result<PermissionReport> {
+    let mut report = PermissionReport {
+        path: path.to_owned(),
+        exists: false,
+        owner: None,
+        group: None,
+        mode: None,
+        in_group: false,
+        acl: Vec::new(),
+        readable: false,
+        writable: false,
+        cap_dac_override: fs::read_to_string("/proc/self/status")
+            .map(|status| has_capability(&status, CAP_DAC_OVERRIDE))
+            .unwrap_or(false),
+        hidraw_available: Path::new("/sys/class/hidraw").exists(),
+        uhid_available: Path::new("/dev/uhid").exists() || Path::new("/sys/module/uhid").exists(),
+    };
+
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(e.into()),
+    };
+
+    let passwd = fs::read_to_string("/etc/passwd").unwrap_or_default();
+    let groups = fs::read_to_string("/etc/group").unwrap_or_default();
+    let (uid, gid) = (metadata.uid(), metadata.gid());
+
+    report.exists = true;
+    report.owner = Some((uid, lookup_name(&passwd, uid)));
+    report.group = Some((gid, lookup_name(&groups, gid)));
+    report.mode = Some(metadata.mode() & 0o7777);
+    report.in_group = rustix::process::getegid().as_raw() == gid
+        || rustix::process::getgroups()
+            .map(|list| list.iter().any(|g| g.as_raw() == gid))
+            .unwrap_or(false);
+    report.acl = read_access_acl(path);
+    report.readable = rustix::fs::access(path, rustix::fs::Access::READ_OK).is_ok();
+    report.writable = rustix::fs::access(path, rustix::fs::Access::WRITE_OK).is_ok();
+
+    Ok(report)
+}
+
+/// Check a bit in the effective capability set from `/proc/self/status`
+fn has_capability(status: &str, capability: u32) -> bool {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        .is_some_and(|mask| mask & (1 << capability) != 0)
+}
+
+/// Name for an ID from `/etc/passwd` or `/etc/group` contents (`name:x:id:...`)
+fn lookup_name(database: &str, id: u32) -> Option<String> {
+    database.lines().find_map(|line| {
+        let mut fields = line.split(':');
+        let name = fields.next()?;
+        let entry_id: u32 = fields.nth(1)?.parse().ok()?;
+        (entry_id == id).then(|| name.to_string())
+    })
+}
+
+fn read_access_acl(path: &Path) -> Vec<AclEntry> {
+    let mut buf = [0u8; 512];
+    rustix::fs::getxattr(path, "system.posix_acl_access", &mut buf[..])
+        .map(|len| parse_posix_acl(&buf[..len]))
+        .unwrap_or_default()
+}
+
+/// Decode the named entries of a `system.posix_acl_access` xattr
+///
+/// Effective permissions of named entries are limited by the mask entry.
+fn parse_posix_acl(bytes: &[u8]) -> Vec<AclEntry> {
+    let Some((header, body)) = bytes.split_first_chunk::<4>() else {
+        return Vec::new();
+    };
+    if u32::from_le_bytes(*header) != ACL_XATTR_VERSION {
+        return Vec::new();
+    }
+
+    // Each entry: u16 tag, u16 permissions, u32 id
+    let entries: Vec<(u16, u16, u32)> = body
+        .chunks_exact(8)
+        .map(|e| {
+            (
+                u16::from_le_bytes([e[0], e[1]]),
+                u16::from_le_bytes([e[2], e[3]]),
+                u32::from_le_bytes([e[4], e[5], e[6], e[7]]),
+            )
+        })
+        .collect();
+    let mask = entries
+        .iter()
+        .find(|(tag, _, _)| *tag == ACL_MASK)
+        .map_or(0o7, |(_, perm, _)| *perm);
+
+    entries
+        .iter()
+        .filter_map(|&(tag, perm, id)| {
+            let perm = perm & mask;
+            let (read, write) = (perm & 0o4 != 0, perm & 0o2 != 0);
+            match tag {
+                ACL_USER => Some(AclEntry::User {
+                    uid: id,
+                    read,
+                    write,
+                }),
+                ACL_GROUP => Some(AclEntry::Group {
+                    gid: id,
+                    read,
+                    write,
+                }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// A udev rule granting access to a device's hidraw nodes
+///
+/// By default the rule uses `TAG+="uaccess"`, which gives the user logged in
+/// at the seat access through an ACL. On systems without systemd-logind, set
+/// a group and mode instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UdevRule {
+    vendor_id: u16,
+    product_id: u16,
+    bus_type: BusType,
+    comment: Option<String>,
+    group: Option<String>,
+    mode: Option<u32>,
+}
+
+impl UdevRule {
+    /// Rule matching a device's vendor and product ID on its bus
+    pub fn for_device(info: &DeviceInfo) -> Self {
+        Self {
+            vendor_id: info.vendor_id,
+            product_id: info.product_id,
+            bus_type: info.bus_type,
+            comment: info.product.clone(),
+            group: None,
+            mode: None,
+        }
+    }
+
+    /// Rule matching a USB vendor and product ID
+    pub fn usb(vendor_id: u16, product_id: u16) -> Self {
+        Self {
+            vendor_id,
+            product_id,
+            bus_type: BusType::Usb,
+            comment: None,
+            group: None,
+            mode: None,
+        }
+    }
+
+    /// Grant access to a group (e.g. "plugdev") instead of the seat user
+    pub fn group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Set the node's permission bits (e.g. 0o660)
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.mode = Some(mode);
+        self
+    }
+
+    /// Suggested file name in `/etc/udev/rules.d`
+    ///
+    /// The `70-` prefix orders the rule before `73-seat-late.rules`, which
+    /// applies the `uaccess` tag.
+    pub fn file_name(&self) -> String {
+        format!(
+            "70-hidraw-{vid:04x}-{pid:04x}.rules",
+            vid = self.vendor_id,
+            pid = self.product_id
+        )
+    }
+}
+
+impl fmt::Display for UdevRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (vid, pid) = (self.vendor_id, self.product_id);
+        writeln!(
+            f,
+            "# {name} ({vid:04x}:{pid:04x})",
+            name = self.comment.as_deref().unwrap_or("HID device")
+        )?;
+
+        write!(f, "KERNEL==\"hidraw*\", ")?;
+        match self.bus_type {
+            // USB devices expose their IDs as attributes of a parent device
+            BusType::Usb | BusType::Unknown => write!(
+                f,
+                "ATTRS{{idVendor}}==\"{vid:04x}\", ATTRS{{idProduct}}==\"{pid:04x}\""
+            )?,
+            // Other buses only have the HID device name, BUS:VID:PID.N
+            other => write!(
+                f,
+                "KERNELS==\"{bus:04X}:{vid:04X}:{pid:04X}.*\"",
+                bus = other.raw()
+            )?,
+        }
+
+        match (&self.group, self.mode) {
+            (None, None) => write!(f, ", TAG+=\"uaccess\"")?,
+            (group, mode) => {
+                if let Some(group) = group {
+                    write!(f, ", GROUP=\"{group}\"")?;
+                }
+                write!(f, ", MODE=\"{mode:04o}\"", mode = mode.unwrap_or(0o660))?;
+            }
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(bus_type: BusType) -> DeviceInfo {
+        DeviceInfo {
+            path: PathBuf::from("/dev/hidraw0"),
+            vendor_id: 0xd13e,
+            product_id: 0xcc10,
+            serial_number: None,
+            manufacturer: None,
+            product: Some("Coldcard".to_string()),
+            interface_number: 0,
+            bus_type,
+            usage_page: 0,
+            usage: 0,
+            physical_path: None,
+        }
+    }
+
+    #[test]
+    fn test_udev_rule() -> Result<()> {
+        let rule = UdevRule::for_device(&info(BusType::Usb));
+        assert_eq!(
+            rule.to_string(),
+            "# Coldcard (d13e:cc10)\n\
+             KERNEL==\"hidraw*\", ATTRS{idVendor}==\"d13e\", ATTRS{idProduct}==\"cc10\", TAG+=\"uaccess\"\n"
+        );
+        assert_eq!(rule.file_name(), "70-hidraw-d13e-cc10.rules");
+
+        let rule = UdevRule::for_device(&info(BusType::Bluetooth)).group("plugdev");
+        assert_eq!(
+            rule.to_string().lines().nth(1),
+            Some(
+                "KERNEL==\"hidraw*\", KERNELS==\"0005:D13E:CC10.*\", GROUP=\"plugdev\", MODE=\"0660\""
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_posix_acl() -> Result<()> {
+        let mut bytes = ACL_XATTR_VERSION.to_le_bytes().to_vec();
+        for (tag, perm, id) in [
+            (0x01u16, 0o6u16, u32::MAX), // USER_OBJ rw
+            (ACL_USER, 0o6, 1000),
+            (0x04, 0o6, u32::MAX), // GROUP_OBJ rw
+            (ACL_GROUP, 0o6, 46),
+            (ACL_MASK, 0o4, u32::MAX),
+            (0x20, 0o0, u32::MAX), // OTHER
+        ] {
+            bytes.extend_from_slice(&tag.to_le_bytes());
+            bytes.extend_from_slice(&perm.to_le_bytes());
+            bytes.extend_from_slice(&id.to_le_bytes());
+        }
+
+        assert_eq!(
+            parse_posix_acl(&bytes),
+            vec![
+                // The read-only mask limits both named entries
+                AclEntry::User {
+                    uid: 1000,
+                    read: true,
+                    write: false
+                },
+                AclEntry::Group {
+                    gid: 46,
+                    read: true,
+                    write: false
+                },
+            ]
+        );
+        assert!(parse_posix_acl(&[1, 0, 0, 0]).is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_capability_and_names() -> Result<()> {
+        let status = "Name:\tcat\nCapPrm:\t0000000000000000\nCapEff:\t0000000000000002\n";
+        assert!(has_capability(status, CAP_DAC_OVERRIDE));
+        assert!(!has_capability(
+            "CapEff:\t0000000000000000\n",
+            CAP_DAC_OVERRIDE
+        ));
+
+        let group = "root:x:0:\nplugdev:x:46:alice\n";
+        assert_eq!(lookup_name(group, 46).as_deref(), Some("plugdev"));
+        assert_eq!(lookup_name(group, 7), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnose_missing_node() -> Result<()> {
+        let report = diagnose(Path::new("/nonexistent/hidraw99"))?;
+
+        assert!(!report.exists);
+        assert!(!report.can_open());
+        assert!(
+            report
+                .suggestions()
+                .iter()
+                .any(|hint| hint.contains("does not exist"))
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,360 @@
+//! Waiting on many hidraw devices from one thread
+//!
+//! [`HidDevice::read_timeout`](crate::HidDevice::read_timeout) polls a single
+//! descriptor, so serving many devices that way takes a thread each. A
+//! [`HidPoller`] registers any number of [`HidrawDevice`]s, plus
+//! caller-supplied descriptors such as a hotplug socket or an eventfd, with
+//! one epoll instance and waits on all of them with a shared timeout.
+//!
+//! ```no_run
+//! use hidraw_rs::hidraw::HidrawDevice;
+//! use hidraw_rs::poller::{HidPoller, PollEvent};
+//! use std::time::Duration;
+//!
+//! # fn main() -> hidraw_rs::Result<()> {
+//! let mut poller = HidPoller::new()?;
+//! for info in hidraw_rs::find_devices(0xd13e, 0xcc10)? {
+//!     poller.add(HidrawDevice::open(&info.path)?)?;
+//! }
+//! while !poller.is_empty() {
+//!     for (token, event) in poller.poll(Some(Duration::from_secs(1)))? {
+//!         match event {
+//!             PollEvent::Report(data) => println!("{token:?}: {data:02x?}"),
+//!             PollEvent::Disconnected => println!("{token:?} went away"),
+//!             PollEvent::Error(e) => eprintln!("{token:?}: {e}"),
+//!             PollEvent::Ready => {}
+//!         }
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::hidraw::{HidrawDevice, MAX_REPORT_LEN};
+use crate::{Error, Result};
+use rustix::event::epoll;
+use rustix::fd::{AsFd, OwnedFd};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::Duration;
+
+/// Events collected per wait; more ready sources are picked up next time
+const MAX_EVENTS: usize = 64;
+
+/// Identifies a device or descriptor registered with a [`HidPoller`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(pub u64);
+
+/// What happened to a registered source
+#[derive(Debug)]
+pub enum PollEvent {
+    /// An input report from a device
+    Report(Vec<u8>),
+    /// The device is gone; it has been removed from the poller
+    Disconnected,
+    /// Reading the device failed for another reason; it stays registered
+    Error(Error),
+    /// A caller-supplied descriptor is readable; reading it is up to the caller
+    Ready,
+}
+
+/// epoll-based wait on many hidraw devices and extra descriptors
+pub struct HidPoller {
+    epoll: OwnedFd,
+    devices: HashMap<Token, HidrawDevice>,
+    fds: HashSet<Token>,
+    next_token: u64,
+    buf: Vec<u8>,
+}
+
+impl HidPoller {
+    /// Create an empty poller
+    pub fn new() -> Result<Self> {
+        let epoll = epoll::create(epoll::CreateFlags::CLOEXEC).map_err(|e| Error::Io(e.into()))?;
+        Ok(Self {
+            epoll,
+            devices: HashMap::new(),
+            fds: HashSet::new(),
+            next_token: 0,
+            buf: vec![0u8; MAX_REPORT_LEN],
+        })
+    }
+
+    /// Take ownership of `device` and wait for its input reports
+    ///
+    /// The device is switched to non-blocking mode, so a report taken by
+    /// another handle in the meantime (see [`HidrawDevice::try_clone`])
+    /// can't stall the loop. It stays non-blocking when handed back by
+    /// [`remove`](Self::remove).
+    pub fn add(&mut self, device: HidrawDevice) -> Result<Token> {
+        device.set_nonblocking(true)?;
+        let token = self.register(&device)?;
+        self.devices.insert(token, device);
+        Ok(token)
+    }
+
+    /// Stop polling a device and hand it back
+    ///
+    /// If deregistering fails the device is dropped, which closes it.
+    pub fn remove(&mut self, token: Token) -> Result<HidrawDevice> {
+        let device = self.devices.remove(&token).ok_or_else(|| {
+            Error::InvalidParameter(format!("{token:?} is not a registered device"))
+        })?;
+        epoll::delete(&self.epoll, &device).map_err(|e| Error::Io(e.into()))?;
+        Ok(device)
+    }
+
+    /// Wait for `fd` to become readable too, reported as [`PollEvent::Ready`]
+    ///
+    /// The caller keeps ownership and must [`remove_fd`](Self::remove_fd)
+    /// it before closing it.
+    pub fn add_fd(&mut self, fd: impl AsFd) -> Result<Token> {
+        let token = self.register(fd)?;
+        self.fds.insert(token);
+        Ok(token)
+    }
+
+    /// Stop polling a descriptor added with [`add_fd`](Self::add_fd)
+    pub fn remove_fd(&mut self, token: Token, fd: impl AsFd) -> Result<()> {
+        if !self.fds.remove(&token) {
+            return Err(Error::InvalidParameter(format!(
+                "{token:?} is not a registered descriptor"
+            )));
+        }
+        epoll::delete(&self.epoll, fd).map_err(|e| Error::Io(e.into()))
+    }
+
+    /// A registered device, e.g. to write to it
+    pub fn device(&self, token: Token) -> Option<&HidrawDevice> {
+        self.devices.get(&token)
+    }
+
+    /// A registered device, mutably
+    pub fn device_mut(&mut self, token: Token) -> Option<&mut HidrawDevice> {
+        self.devices.get_mut(&token)
+    }
+
+    /// Tokens of the registered devices
+    pub fn tokens(&self) -> impl Iterator<Item = Token> + '_ {
+        self.devices.keys().copied()
+    }
+
+    /// Number of registered devices
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Whether no devices are registered
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Wait up to `timeout` (`None` waits forever) for any source
+    ///
+    /// Returns one event per ready source, with at most one report per
+    /// device; further queued reports are returned by the next call. A
+    /// device that hung up is drained instead: all its queued reports come
+    /// before its [`PollEvent::Disconnected`]. An empty result means the
+    /// timeout expired or a signal interrupted the wait.
+    pub fn poll(&mut self, timeout: Option<Duration>) -> Result<Vec<(Token, PollEvent)>> {
+        let timeout = timeout.map(|timeout| rustix::time::Timespec {
+            tv_sec: timeout.as_secs() as i64,
+            tv_nsec: timeout.subsec_nanos() as i64,
+        });
+
+        let mut ready = Vec::with_capacity(MAX_EVENTS);
+        match epoll::wait(
+            &self.epoll,
+            rustix::buffer::spare_capacity(&mut ready),
+            timeout.as_ref(),
+        ) {
+            Ok(_) => {}
+            Err(rustix::io::Errno::INTR) => return Ok(Vec::new()),
+            Err(e) => return Err(Error::Io(e.into())),
+        }
+
+        let mut events = Vec::with_capacity(ready.len());
+        for event in ready {
+            let token = Token(event.data.u64());
+            if self.fds.contains(&token) {
+                events.push((token, PollEvent::Ready));
+            } else {
+                self.read_device(token, event.flags, &mut events);
+            }
+        }
+        Ok(events)
+    }
+
+    /// Read the report a ready device has queued, removing it on hangup
+    ///
+    /// On hangup, the reports queued before the unplug are drained first.
+    fn read_device(
+        &mut self,
+        token: Token,
+        flags: epoll::EventFlags,
+        events: &mut Vec<(Token, PollEvent)>,
+    ) {
+        let Some(device) = self.devices.get_mut(&token) else {
+            return;
+        };
+        // hidraw reports EPOLLHUP | EPOLLERR once the device is unplugged
+        let hangup = flags.intersects(epoll::EventFlags::HUP | epoll::EventFlags::ERR);
+
+        loop {
+            match device.read(&mut self.buf) {
+                Ok(n) if n > 0 => {
+                    events.push((token, PollEvent::Report(self.buf[..n].to_vec())));
+                    if !hangup {
+                        return;
+                    }
+                }
+                // EOF; EIO and ENODEV already map to Error::Disconnected
+                Ok(_) | Err(Error::Disconnected) => break,
+                Err(Error::Io(e)) if e.kind() == io::ErrorKind::Interrupted && hangup => {}
+                // Another handle on the same device took the report, or a
+                // signal arrived; still readable next time if not
+                Err(Error::Io(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) =>
+                {
+                    if !hangup {
+                        return;
+                    }
+                    break;
+                }
+                Err(e) => {
+                    events.push((token, PollEvent::Error(e)));
+                    if !hangup {
+                        return;
+                    }
+                    break;
+                }
+            }
+        }
+
+        let event = match self.remove(token) {
+            Ok(_) => PollEvent::Disconnected,
+            Err(e) => PollEvent::Error(e),
+        };
+        events.push((token, event));
+    }
+
+    fn register(&mut self, fd: impl AsFd) -> Result<Token> {
+        let token = Token(self.next_token);
+        epoll::add(
+            &self.epoll,
+            fd,
+            epoll::EventData::new_u64(token.0),
+            epoll::EventFlags::IN,
+        )
+        .map_err(|e| Error::Io(e.into()))?;
+        self.next_token += 1;
+        Ok(token)
+    }
+}
+
+impl std::fmt::Debug for HidPoller {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HidPoller")
+            .field("devices", &self.devices.len())
+            .field("fds", &self.fds.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_extra_fd_ready() -> Result<()> {
+        let mut poller = HidPoller::new()?;
+        let (mut tx, rx) = UnixStream::pair()?;
+        let token = poller.add_fd(&rx)?;
+        assert!(poller.is_empty());
+
+        assert!(poller.poll(Some(Duration::from_millis(1)))?.is_empty());
+
+        tx.write_all(b"x")?;
+        let events = poller.poll(Some(Duration::from_millis(100)))?;
+        assert!(matches!(events[..], [(ready, PollEvent::Ready)] if ready == token));
+
+        poller.remove_fd(token, &rx)?;
+        assert!(poller.poll(Some(Duration::from_millis(1)))?.is_empty());
+        assert!(poller.remove_fd(token, &rx).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_device_reports_and_disconnect() -> Result<()> {
+        let mut poller = HidPoller::new()?;
+        let (device, mut peer) = HidrawDevice::socket_pair("/dev/hidraw-test0")?;
+        let mut other = device.try_clone()?;
+        let token = poller.add(device)?;
+
+        peer.write_all(b"\x01report")?;
+        let events = poller.poll(Some(Duration::from_millis(100)))?;
+        assert!(
+            matches!(&events[..], [(t, PollEvent::Report(data))] if *t == token && data == b"\x01report")
+        );
+
+        // A report taken by another handle doesn't block the loop: the
+        // shared file description is non-blocking now
+        peer.write_all(b"\x02stolen")?;
+        let mut buf = [0u8; 16];
+        assert_eq!(other.read(&mut buf)?, 7);
+        assert!(matches!(
+            other.read(&mut buf),
+            Err(Error::Io(e)) if e.kind() == io::ErrorKind::WouldBlock
+        ));
+        assert!(poller.poll(Some(Duration::from_millis(10)))?.is_empty());
+
+        drop(peer);
+        let events = poller.poll(Some(Duration::from_millis(100)))?;
+        assert!(matches!(events[..], [(t, PollEvent::Disconnected)] if t == token));
+        assert!(poller.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_queued_before_hangup() -> Result<()> {
+        let mut poller = HidPoller::new()?;
+        let (device, mut peer) = HidrawDevice::socket_pair("/dev/hidraw-test0")?;
+        device.set_nonblocking(true)?;
+        let token = poller.add(device)?;
+
+        // Two reports queued, then the device goes away before any poll
+        peer.write_all(b"\x01first")?;
+        peer.write_all(b"\x02second")?;
+        drop(peer);
+
+        let events = poller.poll(Some(Duration::from_millis(100)))?;
+        let reports: Vec<_> = events
+            .iter()
+            .filter_map(|(t, event)| match event {
+                PollEvent::Report(data) if *t == token => Some(data.as_slice()),
+                _ => None,
+            })
+            .collect();
+        // A stream socket may merge the two writes into one read
+        assert_eq!(reports.concat(), b"\x01first\x02second");
+        assert!(matches!(events.last(), Some((t, PollEvent::Disconnected)) if *t == token));
+        assert!(poller.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tokens_are_distinct() -> Result<()> {
+        let mut poller = HidPoller::new()?;
+        let (a, b) = UnixStream::pair()?;
+        let first = poller.add_fd(&a)?;
+        let second = poller.add_fd(&b)?;
+        assert_ne!(first, second);
+        assert!(poller.remove(first).is_err());
+        Ok(())
+    }
+}
@@ -44,6 +44,9 @@ impl HidReport {
     }
 
     /// Convert report to bytes for transmission
+    ///
+    /// Always prepends `report_id`. Use [`HidReport::to_report_bytes`] to
+    /// validate the ID against the device's report numbering.
     pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::with_capacity(self.data.len() + 1);
         bytes.push(self.report_id);
@@ -51,6 +54,43 @@ impl HidReport {
         bytes
     }
 
+    /// Encode the report for hidraw, checking the ID against report numbering
+    ///
+    /// hidraw expects a report ID byte in front of every output and feature
+    /// report. Devices with numbered reports need a non-zero ID; devices
+    /// without them need ID 0, which the kernel strips before sending.
+    pub fn to_report_bytes(&self, uses_report_ids: bool) -> Result<Vec<u8>> {
+        if uses_report_ids && self.report_id == 0 {
+            return Err(Error::InvalidParameter(
+                "Report ID 0 is reserved on devices with numbered reports".to_string(),
+            ));
+        }
+        if !uses_report_ids && self.report_id != 0 {
+            return Err(Error::InvalidParameter(format!(
+                "Device does not use numbered reports, but report ID {id} was given",
+                id = self.report_id
+            )));
+        }
+
+        Ok(self.to_bytes())
+    }
+
+    /// Parse a report as read from hidraw
+    ///
+    /// Reports from devices with numbered reports start with the report ID;
+    /// reports from other devices carry no prefix and get report ID 0.
+    pub fn from_report_bytes(
+        bytes: &[u8],
+        report_type: ReportType,
+        uses_report_ids: bool,
+    ) -> Result<Self> {
+        if uses_report_ids {
+            Self::from_bytes(bytes, report_type)
+        } else {
+            Ok(Self::new(0, report_type, bytes.to_vec()))
+        }
+    }
+
     /// Parse a report from bytes
     pub fn from_bytes(bytes: &[u8], report_type: ReportType) -> Result<Self> {
         if bytes.is_empty() {
@@ -64,3 +104,34 @@ impl HidReport {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_bytes_numbered() -> Result<()> {
+        let report = HidReport::output(2, vec![0xAA, 0xBB]);
+        assert_eq!(report.to_report_bytes(true)?, vec![2, 0xAA, 0xBB]);
+        assert!(report.to_report_bytes(false).is_err());
+        assert!(HidReport::output(0, vec![1]).to_report_bytes(true).is_err());
+
+        let parsed = HidReport::from_report_bytes(&[2, 0xAA], ReportType::Input, true)?;
+        assert_eq!(parsed.report_id, 2);
+        assert_eq!(parsed.data, vec![0xAA]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_report_bytes_unnumbered() -> Result<()> {
+        let report = HidReport::output(0, vec![0xAA, 0xBB]);
+        assert_eq!(report.to_report_bytes(false)?, vec![0, 0xAA, 0xBB]);
+
+        let parsed = HidReport::from_report_bytes(&[2, 0xAA], ReportType::Input, false)?;
+        assert_eq!(parsed.report_id, 0);
+        assert_eq!(parsed.data, vec![2, 0xAA]);
+
+        Ok(())
+    }
+}
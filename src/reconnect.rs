@@ -0,0 +1,530 @@
+//! Automatic reconnection for devices that reboot or get replugged
+//!
+//! A [`ReconnectingDevice`] remembers the [`DeviceId`] of the device it wraps.
+//! When an operation fails because the device went away, it polls for the
+//! device to reappear (up to a deadline), reopens it with the same read
+//! timeout and runs an optional re-initialization callback.
+//!
+//! Single [`Transport`] operations are never repeated, since resending one
+//! packet of a multi-packet message would corrupt its framing; they fail
+//! with [`Error::Disconnected`] and the next call uses the reopened device.
+//! Wrap a whole exchange in [`ReconnectingDevice::transaction`] to have it
+//! retried from the start instead.
+//!
+//! ```no_run
+//! use hidraw_rs::coldcard::{COINKITE_VID, COLDCARD_PID, ColdcardProtocol};
+//! use hidraw_rs::reconnect::{ConnectionEvent, ReconnectingDevice};
+//! use hidraw_rs::{HidDevice, Result};
+//!
+//! fn main() -> Result<()> {
+//!     let device = HidDevice::open_first(COINKITE_VID, COLDCARD_PID)?;
+//!     let mut device = ReconnectingDevice::new(device);
+//!     let events = device.subscribe();
+//!
+//!     let pong = device.transaction(|device| {
+//!         ColdcardProtocol::new(device).send_command(b"ping", Some(b"hello"))
+//!     })?;
+//!     assert_eq!(pong, b"hello");
+//!
+//!     for event in events.try_iter() {
+//!         if let ConnectionEvent::Reconnected { path } = event {
+//!             println!("Device came back as {path}", path = path.display());
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::transport::Transport;
+use crate::{DeviceId, Error, HidDevice, Result};
+use std::path::PathBuf;
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::{Duration, Instant};
+
+/// Callback run on every freshly reopened device before it is used
+pub type ReinitFn = Box<dyn FnMut(&mut HidDevice) -> Result<()> + Send>;
+
+/// Finds and opens the device for an ID; swapped out in tests
+type OpenFn = Box<dyn FnMut(&DeviceId) -> Result<HidDevice> + Send>;
+
+/// Reconnection behavior
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconnectConfig {
+    /// How long to wait for the device to reappear
+    pub deadline: Duration,
+    /// Delay between attempts to find and reopen the device
+    pub poll_interval: Duration,
+    /// Retry a failed [`ReconnectingDevice::transaction`] once after
+    /// reconnecting
+    ///
+    /// When disabled the original error is returned, with the device already
+    /// reconnected for the next call.
+    pub retry_operation: bool,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            deadline: Duration::from_secs(10),
+            poll_interval: Duration::from_millis(250),
+            retry_operation: true,
+        }
+    }
+}
+
+/// Connection state change reported to subscribers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The device stopped responding
+    Disconnected,
+    /// The device was reopened (and re-initialized) at `path`
+    Reconnected { path: PathBuf },
+    /// The device did not come back before the deadline
+    ReconnectFailed,
+}
+
+/// A [`HidDevice`] that reopens itself after a disconnect
+pub struct ReconnectingDevice {
+    id: DeviceId,
+    device: Option<HidDevice>,
+    read_timeout: Option<Duration>,
+    config: ReconnectConfig,
+    open: OpenFn,
+    reinit: Option<ReinitFn>,
+    subscribers: Vec<Sender<ConnectionEvent>>,
+}
+
+impl ReconnectingDevice {
+    /// Wrap an open device, remembering its identity and read timeout
+    pub fn new(device: HidDevice) -> Self {
+        Self {
+            id: device.device_id(),
+            read_timeout: device.get_read_timeout(),
+            device: Some(device),
+            config: ReconnectConfig::default(),
+            open: Box::new(HidDevice::open_by_id),
+            reinit: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Start disconnected; the device is opened on first use
+    ///
+    /// Useful for services that start before the device is plugged in.
+    pub fn from_id(id: DeviceId) -> Self {
+        Self {
+            id,
+            device: None,
+            read_timeout: None,
+            config: ReconnectConfig::default(),
+            open: Box::new(HidDevice::open_by_id),
+            reinit: None,
+            subscribers: Vec::new(),
+        }
+    }
+
+    /// Set the reconnection behavior
+    pub fn with_config(mut self, config: ReconnectConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Run `reinit` on every reopened device before resuming
+    ///
+    /// Use this to restore session state the device lost, such as a
+    /// negotiated encryption key.
+    pub fn on_reconnect(
+        mut self,
+        reinit: impl FnMut(&mut HidDevice) -> Result<()> + Send + 'static,
+    ) -> Self {
+        self.reinit = Some(Box::new(reinit));
+        self
+    }
+
+    /// Set the read timeout, kept for devices reopened later
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+        if let Some(device) = self.device.as_mut() {
+            device.set_read_timeout(timeout);
+        }
+    }
+
+    /// Receive connection events from now on
+    pub fn subscribe(&mut self) -> Receiver<ConnectionEvent> {
+        let (sender, receiver) = channel();
+        self.subscribers.push(sender);
+        receiver
+    }
+
+    /// Identity used to find the device again
+    pub fn device_id(&self) -> &DeviceId {
+        &self.id
+    }
+
+    /// Whether a device is currently open
+    pub fn is_connected(&self) -> bool {
+        self.device.is_some()
+    }
+
+    /// The currently open device, if any
+    pub fn device(&self) -> Option<&HidDevice> {
+        self.device.as_ref()
+    }
+
+    /// Unwrap the currently open device, if any
+    pub fn into_inner(self) -> Option<HidDevice> {
+        self.device
+    }
+
+    fn emit(&mut self, event: ConnectionEvent) {
+        // Drop subscribers whose receiver is gone
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    fn mark_disconnected(&mut self) {
+        if self.device.take().is_some() {
+            self.emit(ConnectionEvent::Disconnected);
+        }
+    }
+
+    /// Reopen the device, waiting up to the configured deadline
+    ///
+    /// Does nothing if the device is already open. A freshly plugged device
+    /// may briefly refuse access until udev has set its permissions, or lack
+    /// sysfs entries; both are retried like a missing device. Returns the
+    /// last such error, or [`Error::Disconnected`] if the device was simply
+    /// absent, when it does not come back in time.
+    pub fn reconnect(&mut self) -> Result<()> {
+        if self.device.is_some() {
+            return Ok(());
+        }
+
+        let deadline = Instant::now() + self.config.deadline;
+        loop {
+            let last_error = match self.try_open() {
+                Ok(device) => {
+                    let path = device.info().path.clone();
+                    self.device = Some(device);
+                    self.emit(ConnectionEvent::Reconnected { path });
+                    return Ok(());
+                }
+                // Not back yet, or it vanished again during re-init
+                Err(e) if matches!(e, Error::DeviceNotFound) || e.is_disconnected() => None,
+                // Back, but udev or sysfs are not done setting it up
+                Err(e @ (Error::PermissionDenied | Error::NotSupported(_))) => Some(e),
+                Err(e) => return Err(e),
+            };
+
+            let now = Instant::now();
+            if now >= deadline {
+                self.emit(ConnectionEvent::ReconnectFailed);
+                return Err(last_error.unwrap_or(Error::Disconnected));
+            }
+            std::thread::sleep(self.config.poll_interval.min(deadline - now));
+        }
+    }
+
+    fn try_open(&mut self) -> Result<HidDevice> {
+        let mut device = (self.open)(&self.id)?;
+        device.set_read_timeout(self.read_timeout);
+        if let Some(reinit) = self.reinit.as_mut() {
+            reinit(&mut device)?;
+        }
+        Ok(device)
+    }
+
+    /// Run a complete exchange on the device, reconnecting and running it
+    /// again from the start if the device goes away midway
+    ///
+    /// `op` is retried at most once, and only if
+    /// [`ReconnectConfig::retry_operation`] is set.
+    pub fn transaction<T>(&mut self, op: impl FnMut(&mut HidDevice) -> Result<T>) -> Result<T> {
+        self.run(self.config.retry_operation, op)
+    }
+
+    /// Run `op` on the device, reconnecting if it has gone away and then
+    /// running it again if `retry` is set
+    fn run<T>(
+        &mut self,
+        retry: bool,
+        mut op: impl FnMut(&mut HidDevice) -> Result<T>,
+    ) -> Result<T> {
+        self.reconnect()?;
+        let device = self.device.as_mut().ok_or(Error::Disconnected)?;
+
+        match op(device) {
+            Err(e) if e.is_disconnected() => {
+                self.mark_disconnected();
+                self.reconnect()?;
+                if !retry {
+                    return Err(e);
+                }
+                let device = self.device.as_mut().ok_or(Error::Disconnected)?;
+                op(device)
+            }
+            result => result,
+        }
+    }
+
+    /// Run a single report operation, which is never retried
+    fn with_device<T>(&mut self, op: impl FnMut(&mut HidDevice) -> Result<T>) -> Result<T> {
+        self.run(false, op)
+    }
+}
+
+impl Transport for ReconnectingDevice {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.with_device(|device| device.read(buf))
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.with_device(|device| device.read_timeout(buf, timeout))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.with_device(|device| device.write(data))
+    }
+
+    fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.with_device(|device| device.get_feature_report(report_id, buf))
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        self.with_device(|device| device.send_feature_report(data))
+    }
+
+    fn max_input_report_len(&self) -> Result<usize> {
+        self.device
+            .as_ref()
+            .ok_or(Error::Disconnected)?
+            .max_input_report_len()
+    }
+}
+
+impl std::fmt::Debug for ReconnectingDevice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReconnectingDevice")
+            .field("id", &self.id)
+            .field("connected", &self.is_connected())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DeviceInfo;
+    use crate::hidraw::HidrawDevice;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    fn missing_device() -> DeviceId {
+        DeviceId {
+            port_path: Some("99-9.9:1.0".to_string()),
+            vendor_id: 0xFFFF,
+            product_id: 0xFFFE,
+            serial_number: Some("NOT-PLUGGED-IN".to_string()),
+            interface_number: 0,
+        }
+    }
+
+    /// A device backed by one end of a socket pair; dropping the returned
+    /// peer unplugs it
+    fn socket_device(path: &str) -> Result<(HidDevice, UnixStream)> {
+        let (raw, peer) = HidrawDevice::socket_pair(path)?;
+        let info = DeviceInfo::new(path, 0xFFFF, 0xFFFE);
+        Ok((HidDevice::from_raw(raw, info), peer))
+    }
+
+    /// Wrap `first`, reopening as `second` after a disconnect
+    fn replugged(first: HidDevice, second: HidDevice) -> ReconnectingDevice {
+        let mut second = Some(second);
+        let mut device = ReconnectingDevice::new(first).with_config(ReconnectConfig {
+            deadline: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(10),
+            ..ReconnectConfig::default()
+        });
+        device.open = Box::new(move |_| second.take().ok_or(Error::DeviceNotFound));
+        device
+    }
+
+    #[test]
+    fn test_reconnects_and_retries_transaction() -> Result<()> {
+        let (mut first, unplugged) = socket_device("/dev/hidraw-test0")?;
+        let (second, mut replugged_peer) = socket_device("/dev/hidraw-test1")?;
+        first.set_read_timeout(Some(Duration::from_millis(100)));
+
+        let mut device = replugged(first, second).on_reconnect(|device| {
+            device.write(b"\x00init")?;
+            Ok(())
+        });
+        let events = device.subscribe();
+
+        drop(unplugged);
+        replugged_peer.write_all(b"\x01pong")?;
+
+        let mut attempts = 0;
+        let response = device.transaction(|device| {
+            attempts += 1;
+            let mut buf = [0u8; 64];
+            let n = device.read(&mut buf)?;
+            Ok(buf[..n].to_vec())
+        })?;
+
+        assert_eq!(response, b"\x01pong");
+        assert_eq!(attempts, 2);
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                ConnectionEvent::Disconnected,
+                ConnectionEvent::Reconnected {
+                    path: PathBuf::from("/dev/hidraw-test1")
+                }
+            ]
+        );
+
+        // Re-initialized and still using the configured read timeout
+        let mut init = [0u8; 5];
+        replugged_peer.read_exact(&mut init)?;
+        assert_eq!(&init, b"\x00init");
+        assert_eq!(
+            device.device().and_then(HidDevice::get_read_timeout),
+            Some(Duration::from_millis(100))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_single_operations_are_not_retried() -> Result<()> {
+        let (first, unplugged) = socket_device("/dev/hidraw-test0")?;
+        let (second, mut replugged_peer) = socket_device("/dev/hidraw-test1")?;
+        let mut device = replugged(first, second);
+
+        drop(unplugged);
+        replugged_peer.write_all(b"\x01pong")?;
+
+        // The packet is not resent to the reopened device
+        let mut buf = [0u8; 64];
+        let result = device.read_timeout(&mut buf, Duration::from_millis(100));
+        assert!(matches!(result, Err(Error::Disconnected)));
+        assert!(device.is_connected());
+
+        // but the next call uses it
+        let n = device.read_timeout(&mut buf, Duration::from_millis(100))?;
+        assert_eq!(&buf[..n], b"\x01pong");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reply_queued_before_unplug_is_delivered() -> Result<()> {
+        let (first, mut unplugged) = socket_device("/dev/hidraw-test0")?;
+        let (second, _replugged_peer) = socket_device("/dev/hidraw-test1")?;
+        let mut device = replugged(first, second);
+
+        unplugged.write_all(b"\x01pong")?;
+        drop(unplugged);
+
+        let mut attempts = 0;
+        let response = device.transaction(|device| {
+            attempts += 1;
+            let mut buf = [0u8; 64];
+            let n = device.read_timeout(&mut buf, Duration::from_millis(100))?;
+            Ok(buf[..n].to_vec())
+        })?;
+
+        assert_eq!(response, b"\x01pong");
+        assert_eq!(attempts, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gives_up_after_deadline() -> Result<()> {
+        let config = ReconnectConfig {
+            deadline: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(10),
+            ..ReconnectConfig::default()
+        };
+        let mut device = ReconnectingDevice::from_id(missing_device()).with_config(config);
+        device.open = Box::new(|_| Err(Error::DeviceNotFound));
+        let events = device.subscribe();
+
+        let start = Instant::now();
+        let mut buf = [0u8; 64];
+        let result = device.read_timeout(&mut buf, Duration::from_millis(10));
+
+        assert!(matches!(result, Err(Error::Disconnected)));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(!device.is_connected());
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![ConnectionEvent::ReconnectFailed]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retries_until_permissions_are_applied() -> Result<()> {
+        let (first, unplugged) = socket_device("/dev/hidraw-test0")?;
+        let (second, _replugged_peer) = socket_device("/dev/hidraw-test1")?;
+        let mut device = replugged(first, second);
+
+        // The new node shows up before udev has set its permissions
+        let mut reopen =
+            std::mem::replace(&mut device.open, Box::new(|_| Err(Error::Disconnected)));
+        let mut attempts = 0;
+        device.open = Box::new(move |id| {
+            attempts += 1;
+            if attempts < 3 {
+                return Err(Error::PermissionDenied);
+            }
+            reopen(id)
+        });
+
+        drop(unplugged);
+        let mut buf = [0u8; 64];
+        let result = device.read_timeout(&mut buf, Duration::from_millis(100));
+        assert!(matches!(result, Err(Error::Disconnected)));
+        assert!(device.is_connected());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reports_last_error_at_deadline() -> Result<()> {
+        let config = ReconnectConfig {
+            deadline: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(10),
+            ..ReconnectConfig::default()
+        };
+        let mut device = ReconnectingDevice::from_id(missing_device()).with_config(config);
+        device.open = Box::new(|_| Err(Error::PermissionDenied));
+
+        let mut buf = [0u8; 64];
+        let result = device.read_timeout(&mut buf, Duration::from_millis(10));
+
+        assert!(matches!(result, Err(Error::PermissionDenied)));
+        assert!(!device.is_connected());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dropped_subscribers_are_pruned() -> Result<()> {
+        let mut device = ReconnectingDevice::from_id(missing_device());
+        drop(device.subscribe());
+        let events = device.subscribe();
+
+        device.emit(ConnectionEvent::Disconnected);
+
+        assert_eq!(device.subscribers.len(), 1);
+        assert_eq!(events.try_recv().ok(), Some(ConnectionEvent::Disconnected));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,198 @@
+//! Independent reader and writer halves of a [`HidDevice`]
+//!
+//! hidraw allows reading and writing the same device concurrently, but
+//! [`HidDevice`] needs `&mut self` for both. [`HidDevice::split`] hands out
+//! a [`HidReader`] and a [`HidWriter`] on duplicated file descriptors, so a
+//! dedicated thread can pump input reports while others send commands.
+//!
+//! ```no_run
+//! use hidraw_rs::HidDevice;
+//!
+//! # fn main() -> hidraw_rs::Result<()> {
+//! let (mut reader, mut writer) = HidDevice::open_first(0xd13e, 0xcc10)?.split()?;
+//! let pump = std::thread::spawn(move || -> hidraw_rs::Result<()> {
+//!     loop {
+//!         let report = reader.read_report()?;
+//!         println!("{report:?}");
+//!     }
+//! });
+//! writer.write(&[0x00, 0x01])?;
+//! # drop(pump);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::protocol::HidReport;
+use crate::{DeviceInfo, HidDevice, Result};
+use rustix::fd::{AsFd, BorrowedFd};
+use std::time::Duration;
+
+/// Read half of a [`HidDevice`], from [`HidDevice::split`]
+#[derive(Debug)]
+pub struct HidReader {
+    device: HidDevice,
+}
+
+/// Write half of a [`HidDevice`], from [`HidDevice::split`]
+///
+/// Also carries the control-pipe operations (feature, input and output
+/// reports), which never wait for input reports.
+#[derive(Debug)]
+pub struct HidWriter {
+    device: HidDevice,
+}
+
+impl HidDevice {
+    /// Split into a reader and a writer that can be used from different
+    /// threads at the same time
+    ///
+    /// The writer uses a duplicated file descriptor (see
+    /// [`try_clone`](Self::try_clone)); the read timeout carries over to the
+    /// reader.
+    pub fn split(self) -> Result<(HidReader, HidWriter)> {
+        let writer = self.try_clone()?;
+        Ok((HidReader { device: self }, HidWriter { device: writer }))
+    }
+}
+
+impl HidReader {
+    /// Get device information
+    pub fn info(&self) -> &DeviceInfo {
+        self.device.info()
+    }
+
+    /// Set the timeout used by [`read`](Self::read) and
+    /// [`read_report`](Self::read_report)
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.device.set_read_timeout(timeout);
+    }
+
+    /// Read an input report, honoring the read timeout
+    pub fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.device.read(buf)
+    }
+
+    /// Read with explicit timeout
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        self.device.read_timeout(buf, timeout)
+    }
+
+    /// Read one input report, see [`HidDevice::read_report`]
+    pub fn read_report(&mut self) -> Result<HidReport> {
+        self.device.read_report()
+    }
+
+    /// Put the halves back together
+    ///
+    /// The writer's duplicated descriptor is closed; the reader's handle
+    /// becomes the device again. Halves of different devices, by path or
+    /// [`DeviceId`](crate::DeviceId), are handed back unchanged.
+    #[allow(
+        clippy::result_large_err,
+        reason = "the halves are handed back by value, as with tokio's ReuniteError"
+    )]
+    pub fn reunite(self, writer: HidWriter) -> std::result::Result<HidDevice, (Self, HidWriter)> {
+        if !same_device(self.info(), writer.info()) {
+            return Err((self, writer));
+        }
+        drop(writer);
+        Ok(self.device)
+    }
+}
+
+impl HidWriter {
+    /// Get device information
+    pub fn info(&self) -> &DeviceInfo {
+        self.device.info()
+    }
+
+    /// Write an output report
+    pub fn write(&mut self, data: &[u8]) -> Result<usize> {
+        self.device.write(data)
+    }
+
+    /// Write with explicit timeout
+    pub fn write_timeout(&mut self, data: &[u8], timeout: Duration) -> Result<usize> {
+        self.device.write_timeout(data, timeout)
+    }
+
+    /// Send an output or feature report, see [`HidDevice::write_report`]
+    pub fn write_report(&mut self, report: &HidReport) -> Result<usize> {
+        self.device.write_report(report)
+    }
+
+    /// Get a feature report
+    pub fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.device.get_feature_report(report_id, buf)
+    }
+
+    /// Send a feature report
+    pub fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        self.device.send_feature_report(data)
+    }
+
+    /// Get feature report `report_id`, see [`HidDevice::get_feature`]
+    pub fn get_feature(&mut self, report_id: u8) -> Result<HidReport> {
+        self.device.get_feature(report_id)
+    }
+
+    /// Get the current input report over the control pipe (Linux 5.11+)
+    pub fn get_input_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        self.device.get_input_report(report_id, buf)
+    }
+
+    /// Send an output report over the control pipe (Linux 5.11+)
+    pub fn send_output_report(&mut self, data: &[u8]) -> Result<()> {
+        self.device.send_output_report(data)
+    }
+}
+
+/// Whether two halves belong to the same device, for `reunite`
+pub(crate) fn same_device(reader: &DeviceInfo, writer: &DeviceInfo) -> bool {
+    reader.path == writer.path && reader.id() == writer.id()
+}
+
+impl AsFd for HidReader {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.device.as_fd()
+    }
+}
+
+impl AsFd for HidWriter {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.device.as_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hidraw::HidrawDevice;
+    use std::path::Path;
+
+    fn device(path: &str) -> Result<HidDevice> {
+        let (raw, _peer) = HidrawDevice::socket_pair(path)?;
+        Ok(HidDevice::from_raw(
+            raw,
+            DeviceInfo::new(path, 0xFFFF, 0xFFFE),
+        ))
+    }
+
+    #[test]
+    fn test_reunite_checks_device() -> Result<()> {
+        let (reader, writer) = device("/dev/hidraw-test0")?.split()?;
+        let (other_reader, other_writer) = device("/dev/hidraw-test1")?.split()?;
+
+        let Err((reader, other_writer)) = reader.reunite(other_writer) else {
+            return Err(crate::Error::InvalidData(
+                "Halves of different devices were reunited".to_string(),
+            ));
+        };
+        assert_eq!(reader.info().path, Path::new("/dev/hidraw-test0"));
+        assert_eq!(other_writer.info().path, Path::new("/dev/hidraw-test1"));
+
+        assert!(reader.reunite(writer).is_ok());
+        assert!(other_reader.reunite(other_writer).is_ok());
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+//! Transport abstraction over HID report I/O
+//!
+//! Protocol handlers such as [`ColdcardProtocol`](crate::coldcard::ColdcardProtocol)
+//! are written against this trait so they can run over a live [`HidDevice`],
+//! a recording wrapper, or a replayed capture.
+
+use crate::{Error, HidDevice, Result};
+use std::time::Duration;
+
+/// Report-level I/O operations shared by live and replayed devices
+pub trait Transport {
+    /// Read an input report, blocking or honoring a configured timeout
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+
+    /// Read an input report with an explicit timeout
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize>;
+
+    /// Write an output report (first byte is the report ID)
+    fn write(&mut self, data: &[u8]) -> Result<usize>;
+
+    /// Get a feature report; `buf[0]` is set to `report_id`
+    fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize>;
+
+    /// Send a feature report (first byte is the report ID)
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Maximum input report length, if known from the report descriptor
+    fn max_input_report_len(&self) -> Result<usize> {
+        Err(Error::NotSupported(
+            "Transport does not expose a report descriptor".to_string(),
+        ))
+    }
+}
+
+impl Transport for HidDevice {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        HidDevice::read(self, buf)
+    }
+
+    fn read_timeout(&mut self, buf: &mut [u8], timeout: Duration) -> Result<usize> {
+        HidDevice::read_timeout(self, buf, timeout)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<usize> {
+        HidDevice::write(self, data)
+    }
+
+    fn get_feature_report(&mut self, report_id: u8, buf: &mut [u8]) -> Result<usize> {
+        HidDevice::get_feature_report(self, report_id, buf)
+    }
+
+    fn send_feature_report(&mut self, data: &[u8]) -> Result<()> {
+        HidDevice::send_feature_report(self, data)
+    }
+
+    fn max_input_report_len(&self) -> Result<usize> {
+        HidDevice::max_input_report_len(self)
+    }
+}
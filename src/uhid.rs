@@ -0,0 +1,571 @@
+//! Virtual HID devices through `/dev/uhid`
+//!
+//! A [`UhidDevice`] registers a HID device with the kernel from user space.
+//! It shows up as a regular hidraw node, so code under test talks to it
+//! exactly like real hardware. Its main use here is replaying hid-tools
+//! recordings ([`RecordedDevice`]) from libinput or kernel bug reports.
+//!
+//! Creating uhid devices usually requires root or write access to
+//! `/dev/uhid`. The device is removed when the [`UhidDevice`] is dropped.
+//!
+//! ```no_run
+//! use hidraw_rs::capture::HidRecording;
+//! use hidraw_rs::uhid::UhidDevice;
+//! use hidraw_rs::{HidDevice, Result};
+//! use std::path::Path;
+//! use std::time::Duration;
+//!
+//! fn main() -> Result<()> {
+//!     let recording = HidRecording::open(Path::new("mouse.hid"))?;
+//!     for recorded in &recording.devices {
+//!         let mut uhid = UhidDevice::from_recording(recorded)?;
+//!         let info = uhid.find_hidraw(Duration::from_secs(2))?;
+//!         let _device = HidDevice::open(&info)?;
+//!
+//!         uhid.wait_open(Duration::from_secs(2))?;
+//!         uhid.replay(&recorded.events, true)?;
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use crate::capture::{RecordedDevice, RecordedEvent};
+use crate::protocol::ReportType;
+use crate::{BusType, DeviceInfo, Error, Result};
+use rustix::event::{PollFd, PollFlags, poll};
+use rustix::fd::OwnedFd;
+use rustix::fs::{Mode, OFlags, open};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Path of the uhid character device
+pub const UHID_PATH: &str = "/dev/uhid";
+
+/// Maximum report descriptor size accepted by the kernel
+pub const MAX_DESCRIPTOR_SIZE: usize = 4096;
+
+/// Maximum report size in a uhid event
+pub const MAX_REPORT_SIZE: usize = crate::hidraw::MAX_REPORT_LEN;
+
+// Event types from <linux/uhid.h>
+const UHID_DESTROY: u32 = 1;
+const UHID_START: u32 = 2;
+const UHID_STOP: u32 = 3;
+const UHID_OPEN: u32 = 4;
+const UHID_CLOSE: u32 = 5;
+const UHID_OUTPUT: u32 = 6;
+const UHID_GET_REPORT: u32 = 9;
+const UHID_GET_REPORT_REPLY: u32 = 10;
+const UHID_CREATE2: u32 = 11;
+const UHID_INPUT2: u32 = 12;
+const UHID_SET_REPORT: u32 = 13;
+const UHID_SET_REPORT_REPLY: u32 = 14;
+
+/// `sizeof(struct uhid_event)`: the type plus the largest request
+/// (`uhid_create2_req`)
+const EVENT_SIZE: usize = 4 + 128 + 64 + 64 + 2 + 2 + 4 * 4 + MAX_DESCRIPTOR_SIZE;
+
+/// Offset of the request payload after the event type
+const PAYLOAD: usize = 4;
+
+/// Counter for generated unique IDs within this process
+static NEXT_UNIQ: AtomicU32 = AtomicU32::new(0);
+
+/// Identity and descriptor of a virtual device
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UhidConfig {
+    /// Device name (at most 127 bytes)
+    pub name: String,
+    /// Physical path (at most 63 bytes)
+    pub phys: String,
+    /// Unique ID (at most 63 bytes); generated when empty so the hidraw
+    /// node can be found with [`UhidDevice::find_hidraw`]
+    pub uniq: String,
+    /// Bus type
+    pub bus: BusType,
+    /// Vendor ID
+    pub vendor_id: u16,
+    /// Product ID
+    pub product_id: u16,
+    /// Device version
+    pub version: u32,
+    /// HID country code
+    pub country: u32,
+    /// Report descriptor
+    pub descriptor: Vec<u8>,
+}
+
+impl From<&RecordedDevice> for UhidConfig {
+    fn from(recorded: &RecordedDevice) -> Self {
+        Self {
+            name: recorded.name.clone(),
+            phys: recorded.phys.clone(),
+            bus: recorded.bus,
+            vendor_id: recorded.vendor_id,
+            product_id: recorded.product_id,
+            descriptor: recorded.descriptor.clone(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Event sent by the kernel to a uhid device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UhidEvent {
+    /// The HID driver bound to the device
+    Start {
+        /// `UHID_DEV_*` flags
+        flags: u64,
+    },
+    /// The HID driver unbound from the device
+    Stop,
+    /// Someone opened the device (e.g. the hidraw node)
+    Open,
+    /// The last user closed the device
+    Close,
+    /// An output report written to the device
+    Output {
+        /// Report bytes, including the report ID byte
+        data: Vec<u8>,
+        /// Report type
+        report_type: ReportType,
+    },
+    /// A GET_REPORT request; answer with [`UhidDevice::reply_get_report`]
+    GetReport {
+        /// Request ID to pass to the reply
+        id: u32,
+        /// Report number
+        report_number: u8,
+        /// Report type
+        report_type: ReportType,
+    },
+    /// A SET_REPORT request; answer with [`UhidDevice::reply_set_report`]
+    SetReport {
+        /// Request ID to pass to the reply
+        id: u32,
+        /// Report number
+        report_number: u8,
+        /// Report type
+        report_type: ReportType,
+        /// Report bytes
+        data: Vec<u8>,
+    },
+    /// An event type this library does not know
+    Other(u32),
+}
+
+/// A virtual HID device backed by `/dev/uhid`
+#[derive(Debug)]
+pub struct UhidDevice {
+    fd: OwnedFd,
+    config: UhidConfig,
+}
+
+impl UhidDevice {
+    /// Create a virtual device
+    pub fn create(config: UhidConfig) -> Result<Self> {
+        Self::create_at(Path::new(UHID_PATH), config)
+    }
+
+    /// Create a virtual device through a uhid node at a custom path
+    pub fn create_at(path: &Path, mut config: UhidConfig) -> Result<Self> {
+        if config.uniq.is_empty() {
+            config.uniq = format!(
+                "hidraw-rs-{pid}-{n}",
+                pid = std::process::id(),
+                n = NEXT_UNIQ.fetch_add(1, Ordering::Relaxed)
+            );
+        }
+        let event = encode_create(&config)?;
+
+        let fd =
+            open(path, OFlags::RDWR | OFlags::CLOEXEC, Mode::empty()).map_err(|e| match e {
+                rustix::io::Errno::ACCESS | rustix::io::Errno::PERM => Error::PermissionDenied,
+                rustix::io::Errno::NOENT => Error::NotSupported(format!(
+                    "{path} not found (is the uhid module loaded?)",
+                    path = path.display()
+                )),
+                e => Error::Io(e.into()),
+            })?;
+
+        let mut device = Self { fd, config };
+        device.write_event(&event)?;
+        Ok(device)
+    }
+
+    /// Create a virtual device matching a recorded one
+    pub fn from_recording(recorded: &RecordedDevice) -> Result<Self> {
+        Self::create(recorded.into())
+    }
+
+    /// Configuration the device was created with
+    pub fn config(&self) -> &UhidConfig {
+        &self.config
+    }
+
+    /// Send an input report, including any report ID prefix
+    pub fn input(&mut self, data: &[u8]) -> Result<()> {
+        let size = checked_report_len(data)?;
+        let mut event = new_event(UHID_INPUT2);
+        event[PAYLOAD..PAYLOAD + 2].copy_from_slice(&size.to_ne_bytes());
+        event[PAYLOAD + 2..PAYLOAD + 2 + data.len()].copy_from_slice(data);
+        self.write_event(&event)
+    }
+
+    /// Answer a [`UhidEvent::GetReport`] request
+    ///
+    /// `error` is an errno value, or 0 on success.
+    pub fn reply_get_report(&mut self, id: u32, error: u16, data: &[u8]) -> Result<()> {
+        let size = checked_report_len(data)?;
+        let mut event = new_event(UHID_GET_REPORT_REPLY);
+        event[PAYLOAD..PAYLOAD + 4].copy_from_slice(&id.to_ne_bytes());
+        event[PAYLOAD + 4..PAYLOAD + 6].copy_from_slice(&error.to_ne_bytes());
+        event[PAYLOAD + 6..PAYLOAD + 8].copy_from_slice(&size.to_ne_bytes());
+        event[PAYLOAD + 8..PAYLOAD + 8 + data.len()].copy_from_slice(data);
+        self.write_event(&event)
+    }
+
+    /// Answer a [`UhidEvent::SetReport`] request
+    ///
+    /// `error` is an errno value, or 0 on success.
+    pub fn reply_set_report(&mut self, id: u32, error: u16) -> Result<()> {
+        let mut event = new_event(UHID_SET_REPORT_REPLY);
+        event[PAYLOAD..PAYLOAD + 4].copy_from_slice(&id.to_ne_bytes());
+        event[PAYLOAD + 4..PAYLOAD + 6].copy_from_slice(&error.to_ne_bytes());
+        self.write_event(&event)
+    }
+
+    /// Wait up to `timeout` for the next event from the kernel
+    pub fn read_event(&mut self, timeout: Duration) -> Result<UhidEvent> {
+        let timeout_spec = rustix::time::Timespec {
+            tv_sec: timeout.as_secs() as i64,
+            tv_nsec: timeout.subsec_nanos() as i64,
+        };
+        let mut fds = [PollFd::new(&self.fd, PollFlags::IN)];
+        if poll(&mut fds, Some(&timeout_spec)).map_err(|e| Error::Io(e.into()))? == 0 {
+            return Err(Error::Timeout);
+        }
+
+        let mut buf = vec![0u8; EVENT_SIZE];
+        let n = rustix::io::read(&self.fd, &mut buf).map_err(|e| Error::Io(e.into()))?;
+        decode_event(&buf[..n])
+    }
+
+    /// Wait until the device has been opened, answering report requests
+    /// with EIO in the meantime
+    ///
+    /// hidraw drops input reports while nobody has the node open, so a
+    /// replay should start only after the reader is ready.
+    pub fn wait_open(&mut self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match self.read_event(remaining)? {
+                UhidEvent::Open => return Ok(()),
+                event => self.reject_request(&event)?,
+            }
+        }
+    }
+
+    /// Find the hidraw node the kernel created for this device
+    ///
+    /// Polls enumeration until the node appears or `timeout` expires.
+    pub fn find_hidraw(&self, timeout: Duration) -> Result<DeviceInfo> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let found = crate::enumerate()?.into_iter().find(|info| {
+                info.vendor_id == self.config.vendor_id
+                    && info.product_id == self.config.product_id
+                    && info.serial_number.as_deref() == Some(self.config.uniq.as_str())
+            });
+            if let Some(info) = found {
+                return Ok(info);
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::DeviceNotFound);
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    /// Send recorded input reports in order
+    ///
+    /// With `realtime`, the gaps between events are reproduced, measured
+    /// from the first event; otherwise reports are sent back to back.
+    /// Pending GET/SET_REPORT requests are answered with EIO so the
+    /// kernel does not stall waiting for them.
+    pub fn replay(&mut self, events: &[RecordedEvent], realtime: bool) -> Result<()> {
+        let Some(first) = events.first() else {
+            return Ok(());
+        };
+        let start = Instant::now();
+
+        for event in events {
+            if realtime {
+                let due = event.timestamp.saturating_sub(first.timestamp);
+                thread::sleep(due.saturating_sub(start.elapsed()));
+            }
+            self.answer_pending()?;
+            self.input(&event.data)?;
+        }
+        Ok(())
+    }
+
+    /// Answer the GET/SET_REPORT requests already queued with EIO, without
+    /// waiting for more
+    ///
+    /// Readers issuing feature requests block until they are answered, so
+    /// anything feeding reports through [`input`](Self::input) should call
+    /// this regularly.
+    pub fn answer_pending(&mut self) -> Result<()> {
+        loop {
+            match self.read_event(Duration::ZERO) {
+                Ok(request) => self.reject_request(&request)?,
+                // Timeout means none left
+                Err(Error::Timeout) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Fail a report request; other events need no answer
+    fn reject_request(&mut self, event: &UhidEvent) -> Result<()> {
+        const EIO: u16 = 5;
+        match *event {
+            UhidEvent::GetReport { id, .. } => self.reply_get_report(id, EIO, &[]),
+            UhidEvent::SetReport { id, .. } => self.reply_set_report(id, EIO),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_event(&mut self, event: &[u8]) -> Result<()> {
+        rustix::io::write(&self.fd, event).map_err(|e| Error::Io(e.into()))?;
+        Ok(())
+    }
+}
+
+impl Drop for UhidDevice {
+    fn drop(&mut self) {
+        // Closing the fd destroys the device as well; this just makes it
+        // explicit. Errors cannot be reported from drop.
+        let _ = self.write_event(&new_event(UHID_DESTROY));
+    }
+}
+
+fn new_event(event_type: u32) -> Vec<u8> {
+    let mut event = vec![0u8; EVENT_SIZE];
+    event[..4].copy_from_slice(&event_type.to_ne_bytes());
+    event
+}
+
+fn checked_report_len(data: &[u8]) -> Result<u16> {
+    if data.len() > MAX_REPORT_SIZE {
+        return Err(Error::InvalidParameter(format!(
+            "Report too large for uhid: {len} bytes",
+            len = data.len()
+        )));
+    }
+    Ok(data.len() as u16)
+}
+
+/// Copy a string into a fixed-size, NUL-terminated field
+fn put_str(field: &mut [u8], value: &str, what: &str) -> Result<()> {
+    if value.len() >= field.len() {
+        return Err(Error::InvalidParameter(format!(
+            "uhid {what} too long: {len} bytes, at most {max}",
+            len = value.len(),
+            max = field.len() - 1
+        )));
+    }
+    field[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(())
+}
+
+/// Build a `UHID_CREATE2` event
+fn encode_create(config: &UhidConfig) -> Result<Vec<u8>> {
+    if config.descriptor.len() > MAX_DESCRIPTOR_SIZE {
+        return Err(Error::InvalidParameter(format!(
+            "Report descriptor too large for uhid: {len} bytes",
+            len = config.descriptor.len()
+        )));
+    }
+
+    let mut event = new_event(UHID_CREATE2);
+    let req = &mut event[PAYLOAD..];
+    put_str(&mut req[..128], &config.name, "name")?;
+    put_str(&mut req[128..192], &config.phys, "phys")?;
+    put_str(&mut req[192..256], &config.uniq, "uniq")?;
+    req[256..258].copy_from_slice(&(config.descriptor.len() as u16).to_ne_bytes());
+    req[258..260].copy_from_slice(&config.bus.raw().to_ne_bytes());
+    req[260..264].copy_from_slice(&u32::from(config.vendor_id).to_ne_bytes());
+    req[264..268].copy_from_slice(&u32::from(config.product_id).to_ne_bytes());
+    req[268..272].copy_from_slice(&config.version.to_ne_bytes());
+    req[272..276].copy_from_slice(&config.country.to_ne_bytes());
+    req[276..276 + config.descriptor.len()].copy_from_slice(&config.descriptor);
+    Ok(event)
+}
+
+fn report_type(rtype: u8) -> ReportType {
+    // UHID_FEATURE_REPORT = 0, UHID_OUTPUT_REPORT = 1, UHID_INPUT_REPORT = 2
+    match rtype {
+        0 => ReportType::Feature,
+        1 => ReportType::Output,
+        _ => ReportType::Input,
+    }
+}
+
+fn read_u16(buf: &[u8], at: usize) -> Result<u16> {
+    buf.get(at..at + 2)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u16::from_ne_bytes)
+        .ok_or_else(|| Error::Protocol("Truncated uhid event".to_string()))
+}
+
+fn read_u32(buf: &[u8], at: usize) -> Result<u32> {
+    buf.get(at..at + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_ne_bytes)
+        .ok_or_else(|| Error::Protocol("Truncated uhid event".to_string()))
+}
+
+fn read_bytes(buf: &[u8], at: usize, len: usize) -> Result<Vec<u8>> {
+    buf.get(at..at + len)
+        .map(<[u8]>::to_vec)
+        .ok_or_else(|| Error::Protocol("Truncated uhid event".to_string()))
+}
+
+fn read_byte(buf: &[u8], at: usize) -> Result<u8> {
+    buf.get(at)
+        .copied()
+        .ok_or_else(|| Error::Protocol("Truncated uhid event".to_string()))
+}
+
+/// Decode an event read from `/dev/uhid`
+fn decode_event(buf: &[u8]) -> Result<UhidEvent> {
+    Ok(match read_u32(buf, 0)? {
+        UHID_START => {
+            let flags = buf
+                .get(PAYLOAD..PAYLOAD + 8)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u64::from_ne_bytes)
+                .unwrap_or(0);
+            UhidEvent::Start { flags }
+        }
+        UHID_STOP => UhidEvent::Stop,
+        UHID_OPEN => UhidEvent::Open,
+        UHID_CLOSE => UhidEvent::Close,
+        UHID_OUTPUT => {
+            let size = usize::from(read_u16(buf, PAYLOAD + MAX_REPORT_SIZE)?);
+            UhidEvent::Output {
+                data: read_bytes(buf, PAYLOAD, size.min(MAX_REPORT_SIZE))?,
+                report_type: report_type(read_byte(buf, PAYLOAD + MAX_REPORT_SIZE + 2)?),
+            }
+        }
+        UHID_GET_REPORT => UhidEvent::GetReport {
+            id: read_u32(buf, PAYLOAD)?,
+            report_number: read_byte(buf, PAYLOAD + 4)?,
+            report_type: report_type(read_byte(buf, PAYLOAD + 5)?),
+        },
+        UHID_SET_REPORT => {
+            let size = usize::from(read_u16(buf, PAYLOAD + 6)?);
+            UhidEvent::SetReport {
+                id: read_u32(buf, PAYLOAD)?,
+                report_number: read_byte(buf, PAYLOAD + 4)?,
+                report_type: report_type(read_byte(buf, PAYLOAD + 5)?),
+                data: read_bytes(buf, PAYLOAD + 8, size.min(MAX_REPORT_SIZE))?,
+            }
+        }
+        other => UhidEvent::Other(other),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_create() -> Result<()> {
+        let config = UhidConfig {
+            name: "Test".to_string(),
+            uniq: "u1".to_string(),
+            bus: BusType::Usb,
+            vendor_id: 0x046d,
+            product_id: 0xc52b,
+            descriptor: vec![0x05, 0x01],
+            ..UhidConfig::default()
+        };
+        let event = encode_create(&config)?;
+
+        assert_eq!(event.len(), 4376);
+        assert_eq!(read_u32(&event, 0)?, UHID_CREATE2);
+        assert_eq!(&event[4..9], b"Test\0");
+        assert_eq!(&event[196..199], b"u1\0");
+        assert_eq!(read_u16(&event, 260)?, 2);
+        assert_eq!(read_u16(&event, 262)?, 0x03);
+        assert_eq!(read_u32(&event, 264)?, 0x046d);
+        assert_eq!(read_u32(&event, 268)?, 0xc52b);
+        assert_eq!(&event[280..282], [0x05, 0x01]);
+
+        let long_name = UhidConfig {
+            name: "x".repeat(128),
+            ..UhidConfig::default()
+        };
+        assert!(encode_create(&long_name).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_events() -> Result<()> {
+        assert_eq!(decode_event(&new_event(UHID_OPEN))?, UhidEvent::Open);
+
+        let mut output = new_event(UHID_OUTPUT);
+        output[4..7].copy_from_slice(&[0x02, 0xAA, 0xBB]);
+        output[4100..4102].copy_from_slice(&3u16.to_ne_bytes());
+        output[4102] = 1;
+        assert_eq!(
+            decode_event(&output)?,
+            UhidEvent::Output {
+                data: vec![0x02, 0xAA, 0xBB],
+                report_type: ReportType::Output,
+            }
+        );
+
+        let mut get = new_event(UHID_GET_REPORT);
+        get[4..8].copy_from_slice(&7u32.to_ne_bytes());
+        get[8] = 3;
+        assert_eq!(
+            decode_event(&get)?,
+            UhidEvent::GetReport {
+                id: 7,
+                report_number: 3,
+                report_type: ReportType::Feature,
+            }
+        );
+
+        assert!(decode_event(&[0x06, 0x00]).is_err());
+        assert_eq!(decode_event(&new_event(99))?, UhidEvent::Other(99));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_config_from_recording() -> Result<()> {
+        let recorded = RecordedDevice {
+            name: "Mouse".to_string(),
+            bus: BusType::Bluetooth,
+            vendor_id: 1,
+            product_id: 2,
+            ..RecordedDevice::default()
+        };
+        let config = UhidConfig::from(&recorded);
+
+        assert_eq!(config.name, "Mouse");
+        assert_eq!(config.bus, BusType::Bluetooth);
+        assert!(config.uniq.is_empty());
+
+        Ok(())
+    }
+}
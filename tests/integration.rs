@@ -100,15 +100,14 @@ async fn test_async_timeout() {
 #[cfg(feature = "async")]
 #[test]
 fn test_device_info_display() {
-    let info = DeviceInfo {
-        path: "/dev/hidraw0".into(),
-        vendor_id: 0x1234,
-        product_id: 0x5678,
-        serial_number: Some("SN123456".to_string()),
-        manufacturer: Some("Test Manufacturer".to_string()),
-        product: Some("Test Device".to_string()),
-        interface_number: 0,
-    };
+    let mut info = DeviceInfo::new("/dev/hidraw0", 0x1234, 0x5678);
+    info.serial_number = Some("SN123456".to_string());
+    info.manufacturer = Some("Test Manufacturer".to_string());
+    info.product = Some("Test Device".to_string());
+    info.bus_type = hidraw_rs::BusType::Usb;
+    info.usage_page = 0xFF00;
+    info.usage = 0x01;
+    info.physical_path = Some("usb-0000:00:14.0-1/input0".to_string());
 
     assert_eq!(info.display_name(), "Test Device (1234:5678)");
     assert!(info.matches(0x1234, 0x5678));
@@ -141,3 +140,15 @@ fn test_error_edge_cases() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_split_halves_are_send() {
+    fn assert_send<T: Send + 'static>() {}
+    assert_send::<hidraw_rs::HidReader>();
+    assert_send::<hidraw_rs::HidWriter>();
+    #[cfg(feature = "async")]
+    {
+        assert_send::<hidraw_rs::async_io::AsyncHidReader>();
+        assert_send::<hidraw_rs::async_io::AsyncHidWriter>();
+    }
+}